@@ -0,0 +1,112 @@
+// Adjacency view of the undirected edges of an instance.
+// The neighbors of all nodes are stored back to back in one array, with the
+// list of node v starting at offsets[v] (compressed sparse rows). Building the
+// view takes O(n + m), a neighbor list is then read in O(degree).
+
+use crate::{Edge, SteinerInstance};
+use std::collections::HashSet;
+
+pub struct Adjacency<'a> {
+    instance: &'a SteinerInstance,
+    // Node v has the entries offsets[v]..offsets[v + 1], node 0 is unused
+    // unless the instance is 0-based
+    offsets: Vec<usize>,
+    // Neighbor and index of the connecting edge in instance.edges
+    entries: Vec<(usize, usize)>,
+    // Normalized endpoints of every edge
+    pairs: HashSet<(usize, usize)>,
+}
+
+impl<'a> Adjacency<'a> {
+    fn new(instance: &'a SteinerInstance) -> Self {
+        // Edges to nodes beyond num_nodes get lists as well
+        let max_node = instance
+            .edges
+            .iter()
+            .map(|edge| edge.from.max(edge.to))
+            .fold(instance.num_nodes, usize::max);
+
+        // Self-loops are listed once at their node
+        let endpoints = |edge: &Edge| {
+            let other = (edge.from != edge.to).then_some(edge.to);
+            std::iter::once(edge.from).chain(other)
+        };
+
+        let mut offsets = vec![0; max_node + 2];
+        for edge in &instance.edges {
+            for node in endpoints(edge) {
+                offsets[node + 1] += 1;
+            }
+        }
+        for node in 1..offsets.len() {
+            offsets[node] += offsets[node - 1];
+        }
+
+        let mut next = offsets.clone();
+        let mut entries = vec![(0, 0); offsets[max_node + 1]];
+        for (index, edge) in instance.edges.iter().enumerate() {
+            for node in endpoints(edge) {
+                let neighbor = if node == edge.from {
+                    edge.to
+                } else {
+                    edge.from
+                };
+                entries[next[node]] = (neighbor, index);
+                next[node] += 1;
+            }
+        }
+
+        let pairs = instance
+            .edges
+            .iter()
+            .map(|edge| (edge.from.min(edge.to), edge.from.max(edge.to)))
+            .collect();
+
+        Self {
+            instance,
+            offsets,
+            entries,
+            pairs,
+        }
+    }
+
+    // Neighbors of `node` with the index of the connecting edge
+    pub(crate) fn entries(&self, node: usize) -> &[(usize, usize)] {
+        match self.offsets.get(node + 1) {
+            Some(&end) if node >= self.instance.first_node() => {
+                &self.entries[self.offsets[node]..end]
+            }
+            _ => &[],
+        }
+    }
+
+    // Neighbors of `node` with the cost of the connecting edge, once per edge
+    pub fn neighbors(&self, node: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        self.entries(node)
+            .iter()
+            .map(|&(neighbor, index)| (neighbor, self.instance.edges[index].cost))
+    }
+
+    // Edges with `node` as one of their endpoints, as stored in the instance
+    pub fn incident_edges(&self, node: usize) -> impl Iterator<Item = &'a Edge> + '_ {
+        self.entries(node)
+            .iter()
+            .map(|&(_, index)| &self.instance.edges[index])
+    }
+
+    // Whether an edge connects `u` and `v`, in either orientation
+    pub fn has_edge(&self, u: usize, v: usize) -> bool {
+        self.pairs.contains(&(u.min(v), u.max(v)))
+    }
+}
+
+impl SteinerInstance {
+    /*
+     * Adjacency view of the edges, see Adjacency. Arcs are not included.
+     * Nodes without edges have no neighbors, as do ids outside of the
+     * instance.
+     */
+    pub fn adjacency(&self) -> Adjacency<'_> {
+        Adjacency::new(self)
+    }
+}
@@ -0,0 +1,155 @@
+// Bounds on the cost of an optimal Steiner tree, e.g. for experiment tables
+// or to judge the quality of a heuristic solution.
+
+use crate::SteinerInstance;
+use crate::adjacency::Adjacency;
+use crate::shortest_paths::Entry;
+use crate::solution::{SolveError, SteinerTree};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+impl SteinerInstance {
+    /*
+     * Lower bound on the cost of an optimal Steiner tree from the metric
+     * closure of the k terminals. A minimum spanning tree of the closure
+     * costs at most 2 - 2/k times the optimum, so its cost divided by that
+     * factor is a bound. So is the largest distance between two terminals,
+     * and the larger of the two is returned. The bound is 0 for k <= 1 and
+     * exact for k = 2.
+     */
+    pub fn lower_bound(&self) -> Result<f64, SolveError> {
+        let terminals = self.solvable_terminals()?;
+        let k = terminals.len();
+        if k <= 1 {
+            return Ok(0.0);
+        }
+        let distances = self
+            .distance_matrix(&terminals)
+            .expect("terminals and costs are checked");
+
+        // Prim on the complete closure in O(k^2)
+        let mut in_tree = vec![false; k];
+        let mut connection = vec![f64::INFINITY; k];
+        connection[0] = 0.0;
+        let mut mst = 0.0;
+        for _ in 0..k {
+            let (next, cost) = (0..k)
+                .filter(|&i| !in_tree[i])
+                .map(|i| (i, connection[i]))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .expect("a terminal is left");
+            if !cost.is_finite() {
+                return Err(SolveError::DisconnectedTerminals {
+                    from: terminals[0],
+                    to: terminals[next],
+                });
+            }
+            in_tree[next] = true;
+            mst += cost;
+            for (connection, &distance) in connection.iter_mut().zip(&distances[next]) {
+                *connection = connection.min(distance);
+            }
+        }
+
+        let longest = distances.iter().flatten().copied().fold(0.0, f64::max);
+        Ok((mst / (2.0 - 2.0 / k as f64)).max(longest))
+    }
+
+    /*
+     * Upper bound from the shortest-path heuristic: starting at a terminal,
+     * the nearest terminal not yet connected is repeatedly attached to the
+     * tree by a shortest path. This is done from up to `repetitions`
+     * different random start terminals and the cheapest tree is returned,
+     * after replacing it by the MST of its nodes without the leaves that
+     * aren't terminals. The result only depends on the state of `rng`.
+     */
+    pub fn upper_bound(
+        &self,
+        repetitions: usize,
+        rng: &mut impl Rng,
+    ) -> Result<SteinerTree, SolveError> {
+        let mut terminals = self.solvable_terminals()?;
+        if terminals.len() <= 1 {
+            return Ok(SteinerTree::default());
+        }
+        let mut is_terminal = vec![false; self.num_nodes + 1];
+        for &terminal in &terminals {
+            is_terminal[terminal] = true;
+        }
+
+        let adjacency = self.adjacency();
+        terminals.shuffle(rng);
+        let mut best: Option<SteinerTree> = None;
+        for &start in &terminals[..repetitions.clamp(1, terminals.len())] {
+            let in_tree = self.shortest_path_tree_from(&adjacency, start, &is_terminal)?;
+            let tree = self.spanning_tree_of(&in_tree, &is_terminal);
+            if best.as_ref().is_none_or(|best| tree.cost < best.cost) {
+                best = Some(tree);
+            }
+        }
+        Ok(best.expect("at least one repetition"))
+    }
+
+    // The nodes of the shortest-path heuristic's tree grown from `start`
+    fn shortest_path_tree_from(
+        &self,
+        adjacency: &Adjacency,
+        start: usize,
+        is_terminal: &[bool],
+    ) -> Result<Vec<bool>, SolveError> {
+        let n = self.num_nodes;
+        let mut in_tree = vec![false; n + 1];
+        in_tree[start] = true;
+        let mut tree_nodes = vec![start];
+        let mut missing = is_terminal.iter().filter(|&&t| t).count() - 1;
+
+        let mut distance = vec![f64::INFINITY; n + 1];
+        let mut previous = vec![0; n + 1];
+        while missing > 0 {
+            // Dijkstra from the whole tree up to the nearest terminal
+            distance.fill(f64::INFINITY);
+            let mut heap = BinaryHeap::new();
+            for &node in &tree_nodes {
+                distance[node] = 0.0;
+                heap.push(Reverse(Entry(0.0, node)));
+            }
+            let mut reached = None;
+            while let Some(Reverse(Entry(d, node))) = heap.pop() {
+                if d > distance[node] {
+                    continue;
+                }
+                if is_terminal[node] && !in_tree[node] {
+                    reached = Some(node);
+                    break;
+                }
+                for &(neighbor, index) in adjacency.entries(node) {
+                    let candidate = d + self.edges[index].cost;
+                    if candidate < distance[neighbor] {
+                        distance[neighbor] = candidate;
+                        previous[neighbor] = node;
+                        heap.push(Reverse(Entry(candidate, neighbor)));
+                    }
+                }
+            }
+
+            let Some(mut node) = reached else {
+                let to = (1..=n)
+                    .find(|&node| is_terminal[node] && !in_tree[node])
+                    .expect("a terminal is missing");
+                return Err(SolveError::DisconnectedTerminals { from: start, to });
+            };
+            // With edges of cost 0 the path may pass further terminals
+            while !in_tree[node] {
+                in_tree[node] = true;
+                tree_nodes.push(node);
+                if is_terminal[node] {
+                    missing -= 1;
+                }
+                node = previous[node];
+            }
+        }
+        Ok(in_tree)
+    }
+}
@@ -0,0 +1,215 @@
+// Programmatic construction of instances.
+// The builder collects nodes, edges and terminals and fills in all count
+// fields of the instance, so they can't get out of sync with the data.
+
+use crate::{Coordinate, DuplicateEdgePolicy, Edge, SelfLoopPolicy, SteinerInstance};
+use std::collections::HashSet;
+
+/*
+ * Problem found by SteinerInstanceBuilder::build, naming the call that
+ * caused it.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    EdgeOutOfRange {
+        from: usize,
+        to: usize,
+        node: usize,
+        num_nodes: usize,
+    },
+    ArcOutOfRange {
+        from: usize,
+        to: usize,
+        node: usize,
+        num_nodes: usize,
+    },
+    TerminalOutOfRange {
+        terminal: usize,
+        num_nodes: usize,
+    },
+    CoordinateOutOfRange {
+        node: usize,
+        num_nodes: usize,
+    },
+    // Only with SelfLoopPolicy::Error
+    SelfLoop {
+        node: usize,
+    },
+    // Only with DuplicateEdgePolicy::Error, all surplus copies
+    DuplicateEdges(Vec<Edge>),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::EdgeOutOfRange {
+                from,
+                to,
+                node,
+                num_nodes,
+            } => write!(
+                f,
+                "edge ({}, {}) references node {} but only {} nodes declared",
+                from, to, node, num_nodes
+            ),
+            BuildError::ArcOutOfRange {
+                from,
+                to,
+                node,
+                num_nodes,
+            } => write!(
+                f,
+                "arc ({}, {}) references node {} but only {} nodes declared",
+                from, to, node, num_nodes
+            ),
+            BuildError::TerminalOutOfRange {
+                terminal,
+                num_nodes,
+            } => write!(
+                f,
+                "terminal {} is not a node, only {} nodes declared",
+                terminal, num_nodes
+            ),
+            BuildError::CoordinateOutOfRange { node, num_nodes } => write!(
+                f,
+                "coordinate of node {} but only {} nodes declared",
+                node, num_nodes
+            ),
+            BuildError::SelfLoop { node } => write!(f, "self-loop at node {}", node),
+            BuildError::DuplicateEdges(edges) => {
+                write!(f, "duplicate edges")?;
+                for (index, edge) in edges.iter().enumerate() {
+                    let separator = if index == 0 { ": " } else { ", " };
+                    write!(f, "{}({}, {})", separator, edge.from, edge.to)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/*
+ * Builder for a SteinerInstance, e.g.
+ * `SteinerInstance::builder().nodes(3).edge(1, 2, 1.0).terminal(1).build()`.
+ * Terminals given more than once are kept once. Self-loops and parallel
+ * edges are handled according to the policies, with Warn acting like Drop
+ * since the builder has nowhere to report warnings.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SteinerInstanceBuilder {
+    num_nodes: usize,
+    edges: Vec<Edge>,
+    arcs: Vec<Edge>,
+    terminals: Vec<usize>,
+    coordinates: Vec<(usize, Coordinate)>,
+    self_loops: SelfLoopPolicy,
+    duplicate_edges: DuplicateEdgePolicy,
+}
+
+impl SteinerInstanceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn nodes(&mut self, num_nodes: usize) -> &mut Self {
+        self.num_nodes = num_nodes;
+        self
+    }
+
+    pub fn edge(&mut self, from: usize, to: usize, cost: f64) -> &mut Self {
+        self.edges.push(Edge { from, to, cost });
+        self
+    }
+
+    pub fn arc(&mut self, from: usize, to: usize, cost: f64) -> &mut Self {
+        self.arcs.push(Edge { from, to, cost });
+        self
+    }
+
+    pub fn terminal(&mut self, terminal: usize) -> &mut Self {
+        self.terminals.push(terminal);
+        self
+    }
+
+    pub fn coordinate(&mut self, node: usize, x: f64, y: f64) -> &mut Self {
+        self.coordinates.push((node, Coordinate::Planar { x, y }));
+        self
+    }
+
+    pub fn self_loops(&mut self, policy: SelfLoopPolicy) -> &mut Self {
+        self.self_loops = policy;
+        self
+    }
+
+    pub fn duplicate_edges(&mut self, policy: DuplicateEdgePolicy) -> &mut Self {
+        self.duplicate_edges = policy;
+        self
+    }
+
+    pub fn build(&self) -> Result<SteinerInstance, BuildError> {
+        let num_nodes = self.num_nodes;
+        let out_of_range = |node: usize| node == 0 || node > num_nodes;
+
+        for edge in &self.edges {
+            if let Some(node) = [edge.from, edge.to].into_iter().find(|&n| out_of_range(n)) {
+                return Err(BuildError::EdgeOutOfRange {
+                    from: edge.from,
+                    to: edge.to,
+                    node,
+                    num_nodes,
+                });
+            }
+        }
+        for arc in &self.arcs {
+            if let Some(node) = [arc.from, arc.to].into_iter().find(|&n| out_of_range(n)) {
+                return Err(BuildError::ArcOutOfRange {
+                    from: arc.from,
+                    to: arc.to,
+                    node,
+                    num_nodes,
+                });
+            }
+        }
+        if let Some(&terminal) = self.terminals.iter().find(|&&t| out_of_range(t)) {
+            return Err(BuildError::TerminalOutOfRange {
+                terminal,
+                num_nodes,
+            });
+        }
+        if let Some(&(node, _)) = self.coordinates.iter().find(|(v, _)| out_of_range(*v)) {
+            return Err(BuildError::CoordinateOutOfRange { node, num_nodes });
+        }
+
+        let mut seen = HashSet::new();
+        let terminals = self
+            .terminals
+            .iter()
+            .copied()
+            .filter(|&terminal| seen.insert(terminal))
+            .collect();
+
+        let mut instance = SteinerInstance::new(num_nodes, self.edges.clone(), terminals);
+        instance.arcs = self.arcs.clone();
+        instance.num_arcs = self.arcs.len();
+        instance.coordinates = self.coordinates.iter().copied().collect();
+
+        let loops = instance.remove_self_loops();
+        if let Some(edge) = loops.first()
+            && self.self_loops == SelfLoopPolicy::Error
+        {
+            return Err(BuildError::SelfLoop { node: edge.from });
+        }
+        instance
+            .dedup_edges(self.duplicate_edges)
+            .map_err(BuildError::DuplicateEdges)?;
+        Ok(instance)
+    }
+}
+
+impl SteinerInstance {
+    pub fn builder() -> SteinerInstanceBuilder {
+        SteinerInstanceBuilder::new()
+    }
+}
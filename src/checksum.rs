@@ -0,0 +1,97 @@
+// Integrity checks for instances.
+// The checksum is computed over a canonical view of the instance so it does
+// not depend on the order of the lines in the source file or on how the costs
+// were formatted there.
+
+use crate::SteinerInstance;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// 64-bit FNV-1a, chosen because its output is fixed by specification and thus
+// stable across platforms and compiler versions, unlike `DefaultHasher`.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+pub(crate) fn canonical_cost_bits(cost: f64) -> u64 {
+    // Treat 0.0 and -0.0 as the same cost
+    if cost == 0.0 { 0 } else { cost.to_bits() }
+}
+
+impl SteinerInstance {
+    /*
+     * Content fingerprint for deduplicating instances. It covers the node
+     * count, the edges with their costs, the arcs and the terminals, and
+     * doesn't depend on their order, on edge orientation or on how the costs
+     * were formatted. The computation is part of the stable interface: the
+     * same instance has the same fingerprint in every version of the crate.
+     */
+    pub fn fingerprint(&self) -> u64 {
+        let mut edges: Vec<(usize, usize, u64)> = self
+            .edges
+            .iter()
+            .map(|e| {
+                (
+                    e.from.min(e.to),
+                    e.from.max(e.to),
+                    canonical_cost_bits(e.cost),
+                )
+            })
+            .collect();
+        edges.sort_unstable();
+
+        let mut arcs: Vec<(usize, usize, u64)> = self
+            .arcs
+            .iter()
+            .map(|a| (a.from, a.to, canonical_cost_bits(a.cost)))
+            .collect();
+        arcs.sort_unstable();
+
+        let mut terminals = self.terminals.clone();
+        terminals.sort_unstable();
+
+        let mut hasher = Fnv1a::new();
+        hasher.write_u64(self.num_nodes as u64);
+        for list in [&edges, &arcs] {
+            hasher.write_u64(list.len() as u64);
+            for &(from, to, cost) in list {
+                hasher.write_u64(from as u64);
+                hasher.write_u64(to as u64);
+                hasher.write_u64(cost);
+            }
+        }
+        hasher.write_u64(terminals.len() as u64);
+        for terminal in terminals {
+            hasher.write_u64(terminal as u64);
+        }
+        hasher.0
+    }
+
+    /*
+     * Checksum of the instance as a 16 digit lowercase hex string, i.e. the
+     * fingerprint in hex.
+     */
+    pub fn checksum_string(&self) -> String {
+        format!("{:016x}", self.fingerprint())
+    }
+
+    /*
+     * Compare the checksum of the instance against an expected hex string.
+     * Surrounding whitespace and the case of the hex digits are ignored.
+     */
+    pub fn verify_checksum(&self, expected: &str) -> bool {
+        self.checksum_string().eq_ignore_ascii_case(expected.trim())
+    }
+}
@@ -0,0 +1,172 @@
+// Combining two instances into one, e.g. to build composite benchmarks.
+// The nodes of the second instance are renumbered to follow the nodes of the
+// first, except for nodes glued onto a node of the first instance.
+
+use crate::{Edge, SteinerInstance};
+use std::collections::HashSet;
+use std::fmt;
+
+/*
+ * Reason SteinerInstance::merge_on rejected a pair of nodes to identify,
+ * given as (node of the first instance, node of the second instance).
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+    NodeOutOfRange {
+        pair: (usize, usize),
+        num_nodes: (usize, usize),
+    },
+    // A node of either instance appears in more than one pair
+    DuplicateNode {
+        pair: (usize, usize),
+    },
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::NodeOutOfRange { pair, num_nodes } => write!(
+                f,
+                "pair ({}, {}) refers to a node outside of the instances with {} and {} nodes",
+                pair.0, pair.1, num_nodes.0, num_nodes.1
+            ),
+            MergeError::DuplicateNode { pair } => write!(
+                f,
+                "pair ({}, {}) shares a node with an earlier pair",
+                pair.0, pair.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl SteinerInstance {
+    /*
+     * The union of both instances side by side, node v of `other` becomes
+     * node num_nodes + v. See merge_on for how the remaining data is
+     * combined.
+     */
+    pub fn disjoint_union(&self, other: &SteinerInstance) -> SteinerInstance {
+        self.merge_on(other, &[]).expect("no pairs to check")
+    }
+
+    /*
+     * The union of both instances with node b of `other` identified with
+     * node a of this instance for every pair (a, b). The other nodes of
+     * `other` follow the nodes of this instance in their order. Edges, arcs,
+     * terminals, prizes, coordinates and obstacles are concatenated and the
+     * groups of `other` follow the groups of this instance. A glued node
+     * keeps the data of this instance where both have some. The root,
+     * metadata and presolve information of this instance are kept, the root
+     * of `other` is only used if this instance has none. Maximum degrees and
+     * node weights present in only one instance are filled up with values
+     * that don't constrain the other nodes. References to nodes outside of
+     * `other` are shifted by num_nodes.
+     */
+    pub fn merge_on(
+        &self,
+        other: &SteinerInstance,
+        node_pairs: &[(usize, usize)],
+    ) -> Result<SteinerInstance, MergeError> {
+        let mut glued = vec![None; other.num_nodes + 1];
+        let mut seen = HashSet::new();
+        for &(a, b) in node_pairs {
+            if !(1..=self.num_nodes).contains(&a) || !(1..=other.num_nodes).contains(&b) {
+                return Err(MergeError::NodeOutOfRange {
+                    pair: (a, b),
+                    num_nodes: (self.num_nodes, other.num_nodes),
+                });
+            }
+            if !seen.insert(a) || glued[b].is_some() {
+                return Err(MergeError::DuplicateNode { pair: (a, b) });
+            }
+            glued[b] = Some(a);
+        }
+
+        // New id of every node of `other`, nodes outside of it are shifted
+        let mut new_id = vec![0; other.num_nodes + 1];
+        let mut next = self.num_nodes;
+        for (node, id) in new_id.iter_mut().enumerate().skip(1) {
+            *id = glued[node].unwrap_or_else(|| {
+                next += 1;
+                next
+            });
+        }
+        let map = |node: usize| match new_id.get(node) {
+            Some(&id) if node > 0 => id,
+            _ => node + self.num_nodes,
+        };
+        let map_edge = |edge: &Edge| Edge {
+            from: map(edge.from),
+            to: map(edge.to),
+            cost: edge.cost,
+        };
+
+        let mut merged = self.clone();
+        merged.num_nodes = next;
+        merged.edges.extend(other.edges.iter().map(map_edge));
+        merged.arcs.extend(other.arcs.iter().map(map_edge));
+
+        let terminals: HashSet<usize> = self.terminals.iter().copied().collect();
+        merged.terminals.extend(
+            other
+                .terminals
+                .iter()
+                .map(|&terminal| map(terminal))
+                .filter(|terminal| !terminals.contains(terminal)),
+        );
+        let prized: HashSet<usize> = self.terminal_prizes.iter().map(|&(node, _)| node).collect();
+        merged.terminal_prizes.extend(
+            other
+                .terminal_prizes
+                .iter()
+                .map(|&(node, prize)| (map(node), prize))
+                .filter(|(node, _)| !prized.contains(node)),
+        );
+        merged.groups.extend(
+            other
+                .groups
+                .iter()
+                .map(|group| group.iter().map(|&node| map(node)).collect()),
+        );
+        merged.root = self.root.or(other.root.map(map));
+        for (&node, coordinate) in &other.coordinates {
+            merged.coordinates.entry(map(node)).or_insert(*coordinate);
+        }
+        merged.obstacles.extend(other.obstacles.iter().cloned());
+
+        // Indexed by v - 1. A bound of all edges and arcs never constrains a
+        // node, neither does a weight of 0.
+        if !self.max_degrees.is_empty() || !other.max_degrees.is_empty() {
+            let unconstrained = merged.edges.len() + merged.arcs.len();
+            let bounds = |instance: &SteinerInstance| match instance.max_degrees.as_slice() {
+                [] => vec![unconstrained; instance.num_nodes],
+                bounds => bounds.to_vec(),
+            };
+            merged.max_degrees = [bounds(self), unglued(&bounds(other), &glued)].concat();
+        }
+        if self.node_weights.is_some() || other.node_weights.is_some() {
+            let weights = |instance: &SteinerInstance| {
+                instance
+                    .node_weights
+                    .clone()
+                    .unwrap_or_else(|| vec![0.0; instance.num_nodes])
+            };
+            merged.node_weights = Some([weights(self), unglued(&weights(other), &glued)].concat());
+        }
+
+        merged.recompute_counts();
+        Ok(merged)
+    }
+}
+
+// The values of the nodes that weren't glued, for values indexed by v - 1
+fn unglued<T: Copy>(values: &[T], glued: &[Option<usize>]) -> Vec<T> {
+    values
+        .iter()
+        .zip(&glued[1..])
+        .filter(|(_, glued)| glued.is_none())
+        .map(|(value, _)| *value)
+        .collect()
+}
@@ -0,0 +1,127 @@
+// Connected components of an instance, found with a union-find over the
+// edges and arcs. Arcs are treated as undirected, so these are the weakly
+// connected components for directed instances. The terminal checks use a
+// BFS from one terminal instead, so they can stop at the first component.
+
+use crate::SteinerInstance;
+use std::collections::VecDeque;
+
+// Union-find with path halving and union by size, indexed by node
+pub(crate) struct DisjointSets {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSets {
+    pub(crate) fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    pub(crate) fn find(&mut self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            self.parent[node] = self.parent[self.parent[node]];
+            node = self.parent[node];
+        }
+        node
+    }
+
+    // Whether the sets were distinct before
+    pub(crate) fn union(&mut self, a: usize, b: usize) -> bool {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        true
+    }
+}
+
+impl SteinerInstance {
+    /*
+     * The connected components of the nodes, each sorted and ordered by
+     * their smallest node. Nodes without edges form a component of their
+     * own. Edges and arcs to nodes outside of the instance are ignored.
+     */
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        let mut sets = DisjointSets::new(self.num_nodes + 1);
+        for edge in self.edges.iter().chain(&self.arcs) {
+            let in_range = |node: usize| self.node_ids().contains(&node);
+            if in_range(edge.from) && in_range(edge.to) {
+                sets.union(edge.from, edge.to);
+            }
+        }
+
+        // Index of the component of each root, in order of the first node
+        let mut component_of = vec![usize::MAX; self.num_nodes + 1];
+        let mut components: Vec<Vec<usize>> = Vec::new();
+        for node in self.node_ids() {
+            let root = sets.find(node);
+            if component_of[root] == usize::MAX {
+                component_of[root] = components.len();
+                components.push(Vec::new());
+            }
+            components[component_of[root]].push(node);
+        }
+        components
+    }
+
+    // Whether all nodes lie in one component, true for an empty instance
+    pub fn is_connected(&self) -> bool {
+        self.connected_components().len() <= 1
+    }
+
+    /*
+     * Terminals not reachable from the first terminal that is a node, in
+     * the order they are listed. Terminals outside of the node range are
+     * always reported. Runs a single BFS, treating arcs as undirected like
+     * connected_components.
+     */
+    pub fn unreachable_terminals(&self) -> Vec<usize> {
+        let in_range = |node: usize| self.node_ids().contains(&node);
+        let Some(start) = self.terminals.iter().copied().find(|&t| in_range(t)) else {
+            return self.terminals.clone();
+        };
+
+        let mut neighbors = vec![Vec::new(); self.num_nodes + 1];
+        for edge in self.edges.iter().chain(&self.arcs) {
+            if in_range(edge.from) && in_range(edge.to) {
+                neighbors[edge.from].push(edge.to);
+                neighbors[edge.to].push(edge.from);
+            }
+        }
+
+        let mut reached = vec![false; self.num_nodes + 1];
+        let mut queue = VecDeque::from([start]);
+        reached[start] = true;
+        while let Some(node) = queue.pop_front() {
+            for &neighbor in &neighbors[node] {
+                if !reached[neighbor] {
+                    reached[neighbor] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        self.terminals
+            .iter()
+            .copied()
+            .filter(|&terminal| !reached.get(terminal).copied().unwrap_or(false))
+            .collect()
+    }
+
+    /*
+     * Whether all terminals lie in one component, which a Steiner tree
+     * needs. Trivially true without terminals or with a single terminal that
+     * is a node of the instance.
+     */
+    pub fn terminals_connected(&self) -> bool {
+        self.unreachable_terminals().is_empty()
+    }
+}
@@ -0,0 +1,156 @@
+// Conversion between instances and petgraph graphs.
+// STP node v becomes NodeIndex v - 1, with v as its node weight, so every
+// node exists in the graph even if no edge touches it. Node v of a 0-based
+// instance becomes NodeIndex v. In the other direction NodeIndex i becomes
+// STP node i + 1.
+
+use crate::{Edge, ParseError, ParseErrorKind, SteinerInstance};
+use petgraph::EdgeType;
+use petgraph::graph::{DiGraph, Graph, NodeIndex, UnGraph};
+use petgraph::visit::EdgeRef;
+use std::collections::HashSet;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    // An edge or arc with an endpoint that isn't one of the node ids, as
+    // the lenient parser accepts them
+    EdgeOutOfRange {
+        from: usize,
+        to: usize,
+        nodes: Range<usize>,
+    },
+    // Node 0 in a 1-based instance has no 0-based id, see to_zero_indexed
+    NodeZero,
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::EdgeOutOfRange { from, to, nodes } => write!(
+                f,
+                "edge ({}, {}) has an endpoint outside of the nodes {}..{}",
+                from, to, nodes.start, nodes.end
+            ),
+            ConversionError::NodeZero => {
+                write!(f, "node 0 of a 1-based instance has no 0-based id")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl SteinerInstance {
+    fn petgraph_nodes<Ty: EdgeType>(&self, num_edges: usize) -> Graph<usize, f64, Ty> {
+        let mut graph = Graph::with_capacity(self.num_nodes, num_edges);
+        for node in self.node_ids() {
+            graph.add_node(node);
+        }
+        graph
+    }
+
+    // The indices of both endpoints, if they are nodes of the instance
+    fn node_indices(&self, edge: &Edge) -> Result<(NodeIndex, NodeIndex), ConversionError> {
+        let nodes = self.node_ids();
+        let index = |node: usize| {
+            node.checked_sub(nodes.start)
+                .filter(|&index| index < self.num_nodes)
+                .map(NodeIndex::new)
+        };
+        match (index(edge.from), index(edge.to)) {
+            (Some(from), Some(to)) => Ok((from, to)),
+            _ => Err(ConversionError::EdgeOutOfRange {
+                from: edge.from,
+                to: edge.to,
+                nodes,
+            }),
+        }
+    }
+
+    /*
+     * The undirected graph of the edges, with the costs as edge weights.
+     * Arcs are left out, see to_petgraph_directed. Fails on the first edge
+     * with an endpoint outside of the node ids.
+     */
+    pub fn to_petgraph(&self) -> Result<UnGraph<usize, f64>, ConversionError> {
+        let mut graph = self.petgraph_nodes(self.edges.len());
+        for edge in &self.edges {
+            let (from, to) = self.node_indices(edge)?;
+            graph.add_edge(from, to, edge.cost);
+        }
+        Ok(graph)
+    }
+
+    /*
+     * The directed graph of the arcs, with the costs as edge weights. Edges
+     * of mixed instances become a pair of opposite arcs. Fails like
+     * to_petgraph.
+     */
+    pub fn to_petgraph_directed(&self) -> Result<DiGraph<usize, f64>, ConversionError> {
+        let mut graph = self.petgraph_nodes(self.arcs.len() + 2 * self.edges.len());
+        for edge in &self.edges {
+            let (from, to) = self.node_indices(edge)?;
+            graph.add_edge(from, to, edge.cost);
+            graph.add_edge(to, from, edge.cost);
+        }
+        for arc in &self.arcs {
+            let (from, to) = self.node_indices(arc)?;
+            graph.add_edge(from, to, arc.cost);
+        }
+        Ok(graph)
+    }
+
+    /*
+     * Build an instance from an undirected graph, with the edge weights as
+     * costs. Self-loops and parallel edges are copied as they are, use
+     * remove_self_loops and dedup_edges to handle them according to a policy,
+     * or TryFrom to refuse them.
+     */
+    pub fn from_petgraph<N>(graph: &UnGraph<N, f64>, terminals: &[NodeIndex]) -> SteinerInstance {
+        let edges = graph
+            .edge_references()
+            .map(|edge| Edge {
+                from: edge.source().index() + 1,
+                to: edge.target().index() + 1,
+                cost: *edge.weight(),
+            })
+            .collect();
+        let terminals = terminals.iter().map(|node| node.index() + 1).collect();
+        SteinerInstance::new(graph.node_count(), edges, terminals)
+    }
+}
+
+// Error without a line, as the graph wasn't read from a file
+fn graph_error(kind: ParseErrorKind) -> ParseError {
+    ParseError {
+        line_number: 0,
+        line: String::new(),
+        kind,
+    }
+}
+
+/*
+ * Build an instance without terminals from a simple graph. Self-loops and
+ * parallel edges are refused with the errors the parser uses for them.
+ */
+impl<N> TryFrom<&UnGraph<N, f64>> for SteinerInstance {
+    type Error = ParseError;
+
+    fn try_from(graph: &UnGraph<N, f64>) -> Result<Self, Self::Error> {
+        let instance = SteinerInstance::from_petgraph(graph, &[]);
+        let mut seen = HashSet::new();
+        for edge in &instance.edges {
+            if edge.from == edge.to {
+                return Err(graph_error(ParseErrorKind::SelfLoop { node: edge.from }));
+            }
+            if !seen.insert((edge.from.min(edge.to), edge.from.max(edge.to))) {
+                return Err(graph_error(ParseErrorKind::DuplicateEdge {
+                    from: edge.from,
+                    to: edge.to,
+                }));
+            }
+        }
+        Ok(instance)
+    }
+}
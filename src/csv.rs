@@ -0,0 +1,193 @@
+// Plain CSV edge lists as used by many preprocessing pipelines.
+// An instance is split into an edge file with `from,to,cost` rows and a
+// terminal file with one node per row. Nodes are 1-based as in STP.
+
+use crate::{Edge, IndexBase, ParseError, ParseErrorKind, SteinerInstance, StpError};
+use std::io::{self, BufRead, Error, ErrorKind, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    // Write a header row, and skip the first row when reading
+    pub header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header: false,
+        }
+    }
+}
+
+/*
+ * Call `row` with the line number, the trimmed line and the fields of every
+ * non-empty row, skipping the header row if there is one.
+ */
+fn read_rows<R: BufRead>(
+    reader: R,
+    options: &CsvOptions,
+    mut row: impl FnMut(usize, &str, &[&str]) -> Result<(), ParseError>,
+) -> Result<(), StpError> {
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || (options.header && index == 0) {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(options.delimiter).map(str::trim).collect();
+        row(index + 1, line, &fields)?;
+    }
+    Ok(())
+}
+
+fn field<T: FromStr>(
+    line_number: usize,
+    line: &str,
+    fields: &[&str],
+    n: usize,
+    expected: &'static str,
+) -> Result<T, ParseError> {
+    let error = |kind| ParseError {
+        line_number,
+        line: line.to_string(),
+        kind,
+    };
+    let target = fields
+        .get(n)
+        .filter(|target| !target.is_empty())
+        .ok_or_else(|| error(ParseErrorKind::MissingArgument { expected }))?;
+    target.parse::<T>().map_err(|_| {
+        error(ParseErrorKind::InvalidArgument {
+            expected,
+            found: target.to_string(),
+        })
+    })
+}
+
+/*
+ * Nodes must lie in 1..=num_nodes. Without a given node count only 0 is
+ * refused, and the count reported is the largest node seen so far.
+ */
+fn check_node(
+    line_number: usize,
+    line: &str,
+    node: usize,
+    num_nodes: Option<usize>,
+    max_node: usize,
+) -> Result<(), ParseError> {
+    if node == 0 || num_nodes.is_some_and(|num_nodes| node > num_nodes) {
+        return Err(ParseError {
+            line_number,
+            line: line.to_string(),
+            kind: ParseErrorKind::NodeOutOfRange {
+                node,
+                num_nodes: num_nodes.unwrap_or(max_node),
+            },
+        });
+    }
+    Ok(())
+}
+
+impl SteinerInstance {
+    /*
+     * Write the edges and the terminals as two CSV files. Arcs have no place
+     * in the format and are refused.
+     */
+    pub fn write_csv<E: Write, T: Write>(
+        &self,
+        mut edges: E,
+        mut terminals: T,
+        options: &CsvOptions,
+    ) -> io::Result<()> {
+        if self.index_base == IndexBase::Zero {
+            return self.to_one_indexed().write_csv(edges, terminals, options);
+        }
+        if !self.arcs.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the CSV format has no arcs",
+            ));
+        }
+
+        let delimiter = options.delimiter;
+        if options.header {
+            writeln!(edges, "from{0}to{0}cost", delimiter)?;
+            writeln!(terminals, "terminal")?;
+        }
+        for edge in &self.edges {
+            writeln!(
+                edges,
+                "{1}{0}{2}{0}{3}",
+                delimiter, edge.from, edge.to, edge.cost
+            )?;
+        }
+        for terminal in &self.terminals {
+            writeln!(terminals, "{}", terminal)?;
+        }
+        edges.flush()?;
+        terminals.flush()
+    }
+
+    // The edge and terminal CSV files as strings, see write_csv
+    pub fn to_csv(&self, options: &CsvOptions) -> io::Result<(String, String)> {
+        let mut edges = Vec::new();
+        let mut terminals = Vec::new();
+        self.write_csv(&mut edges, &mut terminals, options)?;
+        Ok((
+            String::from_utf8(edges).expect("the CSV output is UTF-8"),
+            String::from_utf8(terminals).expect("the CSV output is UTF-8"),
+        ))
+    }
+
+    /*
+     * Read an instance from an edge and a terminal CSV file. Bad rows are
+     * reported with the same errors as the STP parser in strict mode, with
+     * line numbers counted per file. Without `num_nodes` the largest node
+     * in either file is taken as the node count.
+     */
+    pub fn from_csv<E: BufRead, T: BufRead>(
+        edges: E,
+        terminals: T,
+        num_nodes: Option<usize>,
+        options: &CsvOptions,
+    ) -> Result<SteinerInstance, StpError> {
+        let mut max_node = 0;
+
+        let mut edge_list = Vec::new();
+        read_rows(edges, options, |line_number, line, fields| {
+            let from = field(line_number, line, fields, 0, "integer node id")?;
+            let to = field(line_number, line, fields, 1, "integer node id")?;
+            let cost: f64 = field(line_number, line, fields, 2, "edge cost")?;
+            check_node(line_number, line, from, num_nodes, max_node)?;
+            check_node(line_number, line, to, num_nodes, max_node)?;
+            if !cost.is_finite() || cost < 0.0 {
+                return Err(ParseError {
+                    line_number,
+                    line: line.to_string(),
+                    kind: ParseErrorKind::InvalidCost(cost),
+                });
+            }
+            max_node = max_node.max(from).max(to);
+            edge_list.push(Edge { from, to, cost });
+            Ok(())
+        })?;
+
+        let mut terminal_list = Vec::new();
+        read_rows(terminals, options, |line_number, line, fields| {
+            let terminal = field(line_number, line, fields, 0, "integer node id")?;
+            check_node(line_number, line, terminal, num_nodes, max_node)?;
+            max_node = max_node.max(terminal);
+            terminal_list.push(terminal);
+            Ok(())
+        })?;
+
+        Ok(SteinerInstance::new(
+            num_nodes.unwrap_or(max_node),
+            edge_list,
+            terminal_list,
+        ))
+    }
+}
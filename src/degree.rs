@@ -0,0 +1,74 @@
+// Degree queries over the edges and arcs of an instance.
+// Edges contribute to the degree of both endpoints, a self-loop counts twice.
+// Arcs are counted separately as in- and out-degrees.
+
+use crate::SteinerInstance;
+use std::collections::BTreeMap;
+
+impl SteinerInstance {
+    /*
+     * Number of edges at `node`. Scans all edges, so use degrees() when
+     * asking for many nodes.
+     */
+    pub fn degree(&self, node: usize) -> usize {
+        self.edges
+            .iter()
+            .map(|edge| (edge.from == node) as usize + (edge.to == node) as usize)
+            .sum()
+    }
+
+    pub fn out_degree(&self, node: usize) -> usize {
+        self.arcs.iter().filter(|arc| arc.from == node).count()
+    }
+
+    pub fn in_degree(&self, node: usize) -> usize {
+        self.arcs.iter().filter(|arc| arc.to == node).count()
+    }
+
+    /*
+     * Degree of node v at index v - 1, like max_degrees, computed in a single
+     * pass over the edges. In a 0-based instance the index is v. Endpoints
+     * outside of the node ids are ignored.
+     */
+    pub fn degrees(&self) -> Vec<usize> {
+        let first = self.first_node();
+        let mut degrees = vec![0; self.num_nodes];
+        for edge in &self.edges {
+            for node in [edge.from, edge.to] {
+                if let Some(degree) = node.checked_sub(first).and_then(|i| degrees.get_mut(i)) {
+                    *degree += 1;
+                }
+            }
+        }
+        degrees
+    }
+
+    // Number of nodes with each degree, e.g. {0: 1, 2: 3}
+    pub fn degree_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for degree in self.degrees() {
+            *histogram.entry(degree).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /*
+     * Terminals without any edge or arc, usually a sign of broken data since
+     * no Steiner tree can reach them.
+     */
+    pub fn isolated_terminals(&self) -> Vec<usize> {
+        let mut touched = vec![false; self.num_nodes + 1];
+        for edge in self.edges.iter().chain(&self.arcs) {
+            for node in [edge.from, edge.to] {
+                if let Some(touched) = touched.get_mut(node) {
+                    *touched = true;
+                }
+            }
+        }
+        self.terminals
+            .iter()
+            .copied()
+            .filter(|&terminal| !touched.get(terminal).copied().unwrap_or(false))
+            .collect()
+    }
+}
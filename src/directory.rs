@@ -0,0 +1,80 @@
+// Batch parsing of whole test sets, e.g. one directory per SteinLib series.
+// With the `rayon` feature the files are parsed concurrently.
+
+use crate::{Parser, SteinerInstance, StpError};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/*
+ * Result of parsing a directory. Instances are keyed by file stem and sorted
+ * by it, failures keep the full path of the offending file.
+ */
+#[derive(Debug, Default)]
+pub struct DirectoryParse {
+    pub instances: Vec<(String, SteinerInstance)>,
+    pub failures: Vec<(PathBuf, StpError)>,
+}
+
+fn collect_stp_files(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                collect_stp_files(&path, recursive, files)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("stp"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl Parser {
+    /*
+     * Parse every `.stp` file in a directory, and in its subdirectories if
+     * requested, with the options of this parser. A file that fails to parse
+     * is reported in the failures instead of aborting the batch, only errors
+     * while listing the directory are returned directly. The order of the
+     * results doesn't depend on the order the files are parsed in.
+     */
+    pub fn parse_stp_dir(
+        &self,
+        path: impl AsRef<Path>,
+        recursive: bool,
+    ) -> std::io::Result<DirectoryParse> {
+        let mut files = Vec::new();
+        collect_stp_files(path.as_ref(), recursive, &mut files)?;
+        files.sort();
+
+        let parse = |path: &PathBuf| {
+            let result = Parser::new(self.options.clone()).parse_stp_file(path);
+            (path.clone(), result)
+        };
+        #[cfg(feature = "rayon")]
+        let results: Vec<_> = files.par_iter().map(parse).collect();
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<_> = files.iter().map(parse).collect();
+
+        let mut parsed = DirectoryParse::default();
+        for (path, result) in results {
+            match result {
+                Ok(instance) => {
+                    let stem = path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    parsed.instances.push((stem, instance));
+                }
+                Err(err) => parsed.failures.push((path, err)),
+            }
+        }
+        // Stable, so equal stems from different subdirectories keep the path order
+        parsed.instances.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(parsed)
+    }
+}
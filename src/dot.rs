@@ -0,0 +1,94 @@
+// Export to the DOT language of Graphviz, for looking at small instances.
+// Render the output with e.g. `dot -Tsvg instance.dot > instance.svg`.
+
+use crate::{Edge, IndexBase, SteinerInstance};
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone)]
+pub struct DotOptions {
+    // Label edges with their costs, which clutters large graphs
+    pub edge_labels: bool,
+    // Edges and arcs drawn in red, e.g. a computed Steiner tree. Edges match
+    // regardless of their orientation, arcs only in their direction.
+    pub highlight: Vec<Edge>,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        Self {
+            edge_labels: true,
+            highlight: Vec::new(),
+        }
+    }
+}
+
+// Quote a DOT identifier, escaping the characters special inside quotes
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl SteinerInstance {
+    /*
+     * Write the instance as an undirected Graphviz graph. Terminals are filled
+     * and arcs are drawn as edges with an arrow head, since a DOT graph can't
+     * mix `--` and `->` edges.
+     */
+    pub fn write_dot<W: Write>(&self, writer: &mut W, options: &DotOptions) -> io::Result<()> {
+        if self.index_base == IndexBase::Zero {
+            return self.to_one_indexed().write_dot(writer, options);
+        }
+        let highlighted: HashSet<(usize, usize)> = options
+            .highlight
+            .iter()
+            .map(|edge| (edge.from, edge.to))
+            .collect();
+        let terminals: HashSet<usize> = self.terminals.iter().copied().collect();
+
+        let name = self.metadata.name.as_deref().unwrap_or("steiner");
+        writeln!(writer, "graph {} {{", quote(name))?;
+        writeln!(writer, "  node [shape=circle];")?;
+        for node in 1..=self.num_nodes {
+            if terminals.contains(&node) {
+                writeln!(
+                    writer,
+                    "  {} [style=filled, fillcolor=gold, shape=doublecircle];",
+                    node
+                )?;
+            } else {
+                writeln!(writer, "  {};", node)?;
+            }
+        }
+
+        let edges = self.edges.iter().map(|edge| (edge, false));
+        let arcs = self.arcs.iter().map(|arc| (arc, true));
+        for (edge, directed) in edges.chain(arcs) {
+            let mut attributes = Vec::new();
+            if directed {
+                attributes.push("dir=forward".to_string());
+            }
+            if options.edge_labels {
+                attributes.push(format!("label={}", quote(&edge.cost.to_string())));
+            }
+            let is_highlighted = highlighted.contains(&(edge.from, edge.to))
+                || (!directed && highlighted.contains(&(edge.to, edge.from)));
+            if is_highlighted {
+                attributes.push("color=red, penwidth=2".to_string());
+            }
+
+            write!(writer, "  {} -- {}", edge.from, edge.to)?;
+            if !attributes.is_empty() {
+                write!(writer, " [{}]", attributes.join(", "))?;
+            }
+            writeln!(writer, ";")?;
+        }
+        writeln!(writer, "}}")
+    }
+
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        let mut output = Vec::new();
+        self.write_dot(&mut output, options)
+            .expect("writing to a Vec never fails");
+        String::from_utf8(output).expect("the DOT output is UTF-8")
+    }
+}
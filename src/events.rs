@@ -0,0 +1,181 @@
+// Event based view of an STP file.
+// Every line carrying information is turned into one event, which allows
+// processing instances too large to be kept in memory. Parsing into a
+// SteinerInstance is the special case of applying all events in order.
+
+use crate::{Coordinate, Edge, Parser, Presolve, Rectangle, Section, SteinerInstance, StpError};
+use std::io::BufRead;
+
+// Upper bound for the entries reserved ahead from a declared count, so a
+// corrupted header can't trigger a huge allocation
+pub const MAX_PREALLOCATED: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StpEvent {
+    // The `33D32945 STP File, STP Format Version x.y` line
+    Header { version: String },
+    SectionStart(Section),
+    // A line of the Comment section, with the key as written in the file
+    Comment { key: String, value: String },
+    NodeCount(usize),
+    EdgeCount(usize),
+    ArcCount(usize),
+    ObstacleCount(usize),
+    TerminalCount(usize),
+    Edge(Edge),
+    Arc(Edge),
+    Terminal(usize),
+    // A `T <node> <x> <y>` line, a terminal with its planar coordinates
+    PlacedTerminal { node: usize, x: f64, y: f64 },
+    PrizeTerminal { node: usize, prize: f64 },
+    // Groups are 1-based, as in the file
+    GroupTerminal { node: usize, group: usize },
+    Root(usize),
+    MaxDegree(usize),
+    NodeWeight(f64),
+    Coordinate(usize, Coordinate),
+    Obstacle(Rectangle),
+    Presolve(PresolveEntry),
+    End,
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresolveEntry {
+    Fixed(f64),
+    Lower(f64),
+    Upper(f64),
+    Time(f64),
+    OriginalNodes(usize),
+    OriginalEdges(usize),
+    EaEdge(Edge),
+    EcEdge(Edge),
+}
+
+/*
+ * Iterator over the events of a buffered reader, created by Parser::events.
+ * Only the current line is kept in memory. Iteration ends after the EOF line,
+ * at the end of the input or after the first error.
+ */
+pub struct StpEvents<'a, R> {
+    parser: &'a mut Parser,
+    reader: R,
+    line: String,
+    done: bool,
+}
+
+impl<'a, R: BufRead> StpEvents<'a, R> {
+    pub(crate) fn new(parser: &'a mut Parser, reader: R) -> Self {
+        Self {
+            parser,
+            reader,
+            line: String::new(),
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for StpEvents<'_, R> {
+    type Item = Result<StpEvent, StpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done && !self.parser.finished {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => self.done = true,
+                Ok(_) => match self.parser.line_event(&self.line) {
+                    Ok(Some(event)) => return Some(Ok(event)),
+                    Ok(None) => (),
+                    Err(err) => {
+                        self.done = true;
+                        return Some(Err(err.into()));
+                    }
+                },
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl SteinerInstance {
+    /*
+     * Record a single event in the instance. Events that only structure the
+     * file, like section starts, leave the instance unchanged, as do unknown
+     * comment keys. Declared counts reserve space for the entries that follow,
+     * up to MAX_PREALLOCATED.
+     */
+    pub fn apply_event(&mut self, event: StpEvent) {
+        match event {
+            StpEvent::Header { version } => self.format_version = Some(version),
+            StpEvent::Comment { key, value } => {
+                let metadata = &mut self.metadata;
+                match key.to_ascii_lowercase().as_str() {
+                    "name" => metadata.name = Some(value),
+                    "creator" => metadata.creator = Some(value),
+                    "date" => metadata.date = Some(value),
+                    "problem" => metadata.problem = Some(value),
+                    "remark" => metadata.remarks.push(value),
+                    _ => (),
+                }
+            }
+            StpEvent::NodeCount(count) => self.num_nodes = count,
+            StpEvent::EdgeCount(count) => {
+                self.num_edges = count;
+                self.edges.reserve_exact(count.min(MAX_PREALLOCATED));
+            }
+            StpEvent::ArcCount(count) => {
+                self.num_arcs = count;
+                self.arcs.reserve_exact(count.min(MAX_PREALLOCATED));
+            }
+            StpEvent::ObstacleCount(count) => self.num_obstacles = count,
+            StpEvent::TerminalCount(count) => {
+                self.num_terminals = count;
+                self.terminals.reserve_exact(count.min(MAX_PREALLOCATED));
+            }
+            StpEvent::Edge(edge) => self.edges.push(edge),
+            StpEvent::Arc(arc) => self.arcs.push(arc),
+            StpEvent::Terminal(terminal) => self.terminals.push(terminal),
+            StpEvent::PlacedTerminal { node, x, y } => {
+                self.terminals.push(node);
+                self.coordinates.insert(node, Coordinate::Planar { x, y });
+            }
+            StpEvent::PrizeTerminal { node, prize } => {
+                self.terminals.push(node);
+                self.terminal_prizes.push((node, prize));
+            }
+            StpEvent::GroupTerminal { node, group } => {
+                if self.groups.len() < group {
+                    self.groups.resize(group, Vec::new());
+                }
+                self.groups[group - 1].push(node);
+            }
+            StpEvent::Root(root) => self.root = Some(root),
+            StpEvent::MaxDegree(degree) => self.max_degrees.push(degree),
+            StpEvent::NodeWeight(weight) => {
+                self.node_weights.get_or_insert_with(Vec::new).push(weight)
+            }
+            StpEvent::Coordinate(node, coordinate) => {
+                self.coordinates.insert(node, coordinate);
+            }
+            StpEvent::Obstacle(rectangle) => self.obstacles.push(rectangle),
+            StpEvent::Presolve(entry) => {
+                let presolve = self.presolve.get_or_insert_with(Presolve::default);
+                match entry {
+                    PresolveEntry::Fixed(cost) => presolve.fixed = Some(cost),
+                    PresolveEntry::Lower(bound) => presolve.lower = Some(bound),
+                    PresolveEntry::Upper(bound) => presolve.upper = Some(bound),
+                    PresolveEntry::Time(time) => presolve.time = Some(time),
+                    PresolveEntry::OriginalNodes(count) => presolve.original_nodes = Some(count),
+                    PresolveEntry::OriginalEdges(count) => presolve.original_edges = Some(count),
+                    PresolveEntry::EaEdge(edge) => presolve.ea_edges.push(edge),
+                    PresolveEntry::EcEdge(edge) => presolve.ec_edges.push(edge),
+                }
+            }
+            StpEvent::SectionStart(_) | StpEvent::End | StpEvent::Eof => (),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Exact solver after Dreyfus and Wagner, for instances with few terminals.
+// cost[S][v] is the cost of the cheapest tree connecting the terminals in S
+// and node v, computed for all subsets S of the terminals but the last one,
+// which serves as the root. That takes O(3^k n + 2^k (m + n log n)) time and
+// O(2^k n) memory.
+
+use crate::SteinerInstance;
+use crate::components::DisjointSets;
+use crate::heuristic::other_endpoint;
+use crate::shortest_paths::Entry;
+use crate::solution::{SolveError, SteinerTree};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+#[derive(Debug, Clone)]
+pub struct ExactOptions {
+    // Instances with more terminals are refused with TooManyTerminals. The
+    // tables grow by a factor of two for every terminal.
+    pub max_terminals: usize,
+}
+
+impl Default for ExactOptions {
+    fn default() -> Self {
+        Self { max_terminals: 12 }
+    }
+}
+
+// How cost[S][v] was reached, to reconstruct the tree
+#[derive(Clone, Copy)]
+enum Step {
+    // v is the terminal of the singleton S, or v can't be reached
+    Start,
+    // Over this edge from its other endpoint, for the same S
+    Edge(usize),
+    // Joining the trees for this part of S and the rest at v
+    Split(usize),
+}
+
+impl SteinerInstance {
+    // Optimal Steiner tree with the default options, see solve_exact_with
+    pub fn solve_exact(&self) -> Result<SteinerTree, SolveError> {
+        self.solve_exact_with(&ExactOptions::default())
+    }
+
+    /*
+     * An optimal Steiner tree, with the same edge cases as
+     * approximate_steiner_tree. Fails with SolveError::TooManyTerminals
+     * before allocating anything if there are more terminals than allowed.
+     */
+    pub fn solve_exact_with(&self, options: &ExactOptions) -> Result<SteinerTree, SolveError> {
+        let terminals = self.solvable_terminals()?;
+        if terminals.len() > options.max_terminals {
+            return Err(SolveError::TooManyTerminals {
+                terminals: terminals.len(),
+                limit: options.max_terminals,
+            });
+        }
+        if terminals.len() <= 1 {
+            return Ok(SteinerTree::default());
+        }
+        let (&root, others) = terminals.split_last().expect("there are two terminals");
+
+        let width = self.num_nodes + 1;
+        let full = (1usize << others.len()) - 1;
+        let adjacency = self.adjacency();
+        let mut cost = vec![f64::INFINITY; (full + 1) * width];
+        let mut step = vec![Step::Start; (full + 1) * width];
+
+        // Subsets are visited after all of their parts, as those are smaller
+        for subset in 1..=full {
+            let row = subset * width;
+            if subset.is_power_of_two() {
+                cost[row + others[subset.trailing_zeros() as usize]] = 0.0;
+            } else {
+                // Only parts with the lowest terminal of the subset, so each
+                // split is tried once
+                let lowest = subset & subset.wrapping_neg();
+                let rest = subset ^ lowest;
+                let mut sub = (rest - 1) & rest;
+                loop {
+                    let part = lowest | sub;
+                    let (left, right) = (part * width, (subset ^ part) * width);
+                    for node in 1..width {
+                        let joined = cost[left + node] + cost[right + node];
+                        if joined < cost[row + node] {
+                            cost[row + node] = joined;
+                            step[row + node] = Step::Split(part);
+                        }
+                    }
+                    if sub == 0 {
+                        break;
+                    }
+                    sub = (sub - 1) & rest;
+                }
+            }
+
+            // Move the trees along shortest paths to all other nodes
+            let mut heap: BinaryHeap<_> = (1..width)
+                .filter(|&node| cost[row + node].is_finite())
+                .map(|node| Reverse(Entry(cost[row + node], node)))
+                .collect();
+            while let Some(Reverse(Entry(d, node))) = heap.pop() {
+                if d > cost[row + node] {
+                    continue;
+                }
+                for &(neighbor, index) in adjacency.entries(node) {
+                    let candidate = d + self.edges[index].cost;
+                    if candidate < cost[row + neighbor] {
+                        cost[row + neighbor] = candidate;
+                        step[row + neighbor] = Step::Edge(index);
+                        heap.push(Reverse(Entry(candidate, neighbor)));
+                    }
+                }
+            }
+        }
+
+        if !cost[full * width + root].is_finite() {
+            // Row 1 holds the distances from the first terminal
+            let first = others[0];
+            let to = terminals
+                .iter()
+                .copied()
+                .find(|&terminal| !cost[width + terminal].is_finite())
+                .unwrap_or(root);
+            return Err(SolveError::DisconnectedTerminals { from: first, to });
+        }
+
+        let mut selected = vec![false; self.edges.len()];
+        let mut pending = vec![(full, root)];
+        while let Some((subset, node)) = pending.pop() {
+            match step[subset * width + node] {
+                Step::Start => {}
+                Step::Edge(index) => {
+                    selected[index] = true;
+                    pending.push((subset, other_endpoint(&self.edges[index], node)));
+                }
+                Step::Split(part) => {
+                    pending.push((part, node));
+                    pending.push((subset ^ part, node));
+                }
+            }
+        }
+
+        // The partial trees may share edges of cost zero and close cycles
+        // with them, which the forest leaves out again
+        let mut components = DisjointSets::new(width);
+        let edges = self
+            .edges
+            .iter()
+            .zip(selected)
+            .filter(|&(edge, selected)| selected && components.union(edge.from, edge.to))
+            .map(|(edge, _)| edge.clone())
+            .collect();
+        Ok(SteinerTree::new(edges))
+    }
+}
@@ -1,37 +1,347 @@
 // Add capabilities to export instances to files again
-// For now, only the most important artifacts are exported
-// such as Graph and Terminals.
 
-use crate::{Edge, Section, SteinerInstance};
-use std::fmt::Write;
+use crate::{Coordinate, IndexBase, Metadata, STP_HEADER, SteinerInstance};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
 
-impl ToString for SteinerInstance {
-    fn to_string(&self) -> String {
-        let mut output = String::new();
+/*
+ * Quote a comment value, escaping quotes and backslashes so the parser
+ * restores it verbatim.
+ */
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    // Open the file with the `33D32945 STP File, STP Format Version 1.0` line,
+    // which tools such as SCIP-Jack and the SteinLib checker insist on
+    pub header: bool,
+    // Formatting of edge costs, prizes, node weights and objective bounds
+    pub costs: CostFormat,
+    // Repeat the planar coordinates of terminals on their `T <node> <x> <y>`
+    // lines, as some geographic tools expect. The Coordinates section is
+    // written either way.
+    pub terminal_coordinates: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            header: true,
+            costs: CostFormat::default(),
+            terminal_coordinates: false,
+        }
+    }
+}
+
+/*
+ * How costs are printed. Whole numbers never get a decimal point, so an
+ * instance with integral costs is written back as `E 1 2 3`.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CostFormat {
+    // Shortest representation that parses back to the same f64
+    #[default]
+    Lossless,
+    // Round to at most this many decimals and drop trailing zeros, which
+    // turns e.g. 0.30000000000000004 back into 0.3
+    Precision(usize),
+}
+
+// A cost printed according to a CostFormat
+struct Cost(f64, CostFormat);
+
+impl fmt::Display for Cost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Cost(cost, format) = *self;
+        match format {
+            CostFormat::Precision(decimals) if cost.is_finite() && cost.fract() != 0.0 => {
+                let rounded = format!("{:.*}", decimals, cost);
+                let trimmed = if rounded.contains('.') {
+                    rounded.trim_end_matches('0').trim_end_matches('.')
+                } else {
+                    &rounded
+                };
+                // Tiny negative costs round to zero, which has no sign
+                match trimmed {
+                    "-0" => write!(f, "0"),
+                    _ => write!(f, "{}", trimmed),
+                }
+            }
+            _ => write!(f, "{}", cost),
+        }
+    }
+}
+
+/*
+ * How write_to_file and write_gr_to_file treat the file system. By default
+ * existing files are never replaced, so curated benchmark files can't be
+ * clobbered by accident.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileOptions {
+    // Replace an existing file instead of failing with AlreadyExists
+    pub overwrite: bool,
+    // Create missing parent directories
+    pub create_dirs: bool,
+}
+
+/*
+ * Create the file at `path` according to `options` and fill it through a
+ * buffered writer. A file left incomplete by a failed write is removed.
+ */
+pub(crate) fn write_file(
+    path: &Path,
+    options: FileOptions,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    if options.create_dirs
+        && let Some(parent) = path.parent()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let mut open = OpenOptions::new();
+    open.write(true);
+    if options.overwrite {
+        open.create(true).truncate(true);
+    } else {
+        open.create_new(true);
+    }
+
+    let mut writer = BufWriter::new(open.open(path)?);
+    let result = write(&mut writer).and_then(|()| writer.flush());
+    if result.is_err() {
+        drop(writer);
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+/*
+ * Display adapter exporting an instance with the given options, created by
+ * SteinerInstance::display_with. Writing it streams the file piece by piece.
+ */
+pub struct StpDisplay<'a> {
+    instance: &'a SteinerInstance,
+    options: &'a ExportOptions,
+}
+
+impl fmt::Display for StpDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.instance.fmt_stp(f, self.options)
+    }
+}
+
+impl fmt::Display for SteinerInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_stp(f, &ExportOptions::default())
+    }
+}
+
+impl SteinerInstance {
+    /*
+     * Export the instance in STP format. This is the canonical exporter, the
+     * output parses back into the same instance.
+     */
+    pub fn to_stp_string(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn to_stp_string_with(&self, options: &ExportOptions) -> String {
+        self.display_with(options).to_string()
+    }
+
+    pub fn display_with<'a>(&'a self, options: &'a ExportOptions) -> StpDisplay<'a> {
+        StpDisplay {
+            instance: self,
+            options,
+        }
+    }
+
+    /*
+     * Stream the instance in STP format to a file, socket or any other writer,
+     * without building the whole file in memory first. The output is
+     * buffered internally.
+     */
+    pub fn write_stp<W: Write>(&self, w: W) -> io::Result<()> {
+        self.write_stp_with(w, &ExportOptions::default())
+    }
 
-        // Export Graph section
-        let _ = writeln!(&mut output, "SECTION Graph");
+    pub fn write_stp_with<W: Write>(&self, w: W, options: &ExportOptions) -> io::Result<()> {
+        let mut w = BufWriter::new(w);
+        write!(w, "{}", self.display_with(options))?;
+        w.flush()
+    }
+
+    // Export the instance to a new file, see FileOptions
+    pub fn write_to_file(&self, path: impl AsRef<Path>, options: FileOptions) -> io::Result<()> {
+        write_file(path.as_ref(), options, |writer| write!(writer, "{}", self))
+    }
+
+    fn fmt_stp(&self, f: &mut fmt::Formatter<'_>, options: &ExportOptions) -> fmt::Result {
+        if self.index_base == IndexBase::Zero {
+            return self.to_one_indexed().fmt_stp(f, options);
+        }
+        let cost = |value| Cost(value, options.costs);
+        if options.header {
+            writeln!(f, "{}", STP_HEADER)?;
+            writeln!(f)?;
+        }
+
+        // Export Comment section, only if there is any metadata
+        let metadata = &self.metadata;
+        if *metadata != Metadata::default() {
+            writeln!(f, "SECTION Comment")?;
+            let fields = [
+                ("Name", &metadata.name),
+                ("Creator", &metadata.creator),
+                ("Date", &metadata.date),
+                ("Problem", &metadata.problem),
+            ];
+            for (key, value) in fields {
+                if let Some(value) = value {
+                    writeln!(f, "{} {}", key, quote(value))?;
+                }
+            }
+            for remark in &metadata.remarks {
+                writeln!(f, "Remark {}", quote(remark))?;
+            }
+            writeln!(f, "END")?;
+            writeln!(f)?;
+        }
+
+        // Export Graph section. All counts are taken from the data rather than
+        // the num_* fields, so stale fields can't produce an inconsistent file
+        writeln!(f, "SECTION Graph")?;
         //  write num nodes
-        let _ = writeln!(&mut output, "Nodes {}", self.num_nodes);
+        writeln!(f, "Nodes {}", self.num_nodes)?;
         //  write num edges
-        let _ = writeln!(&mut output, "Edges {}", self.num_edges);
+        writeln!(f, "Edges {}", self.edges.len())?;
+        //  write num arcs, only for directed instances
+        if !self.arcs.is_empty() {
+            writeln!(f, "Arcs {}", self.arcs.len())?;
+        }
+        //  write num obstacles, only for obstacle-avoiding instances
+        if !self.obstacles.is_empty() {
+            writeln!(f, "Obstacles {}", self.obstacles.len())?;
+        }
         //  write every edge
         for edge in &self.edges {
-            let _ = writeln!(&mut output, "E {} {} {}", edge.from, edge.to, edge.cost);
+            writeln!(f, "E {} {} {}", edge.from, edge.to, cost(edge.cost))?;
+        }
+        //  write every arc
+        for arc in &self.arcs {
+            writeln!(f, "A {} {} {}", arc.from, arc.to, cost(arc.cost))?;
         }
-        let _ = writeln!(&mut output, "END");
-        let _ = writeln!(&mut output, "");
+        writeln!(f, "END")?;
+        writeln!(f)?;
 
         // Export Terminals Section
-        let _ = writeln!(&mut output, "SECTION Terminals");
-        let _ = writeln!(&mut output, "Terminals {}", self.num_terminals);
-        for terminal in &self.terminals {
-            let _ = writeln!(&mut output, "T {}", terminal);
+        writeln!(f, "SECTION Terminals")?;
+        writeln!(f, "Terminals {}", self.terminal_count())?;
+        if let Some(root) = self.root {
+            writeln!(f, "Root {}", root)?;
+        }
+        if self.terminal_prizes.is_empty() {
+            for terminal in &self.terminals {
+                match self.coordinates.get(terminal) {
+                    Some(Coordinate::Planar { x, y }) if options.terminal_coordinates => {
+                        writeln!(f, "T {} {} {}", terminal, x, y)?
+                    }
+                    _ => writeln!(f, "T {}", terminal)?,
+                }
+            }
+        } else {
+            for (terminal, prize) in &self.terminal_prizes {
+                writeln!(f, "TP {} {}", terminal, cost(*prize))?;
+            }
+        }
+        for (group, nodes) in self.groups.iter().enumerate() {
+            for node in nodes {
+                writeln!(f, "TG {} {}", node, group + 1)?;
+            }
+        }
+        writeln!(f, "END")?;
+        writeln!(f)?;
+
+        // Export MaximumDegrees Section, only for degree constrained instances
+        if !self.max_degrees.is_empty() {
+            writeln!(f, "SECTION MaximumDegrees")?;
+            for degree in &self.max_degrees {
+                writeln!(f, "MD {}", degree)?;
+            }
+            writeln!(f, "END")?;
+            writeln!(f)?;
         }
-        let _ = writeln!(&mut output, "END");
-        let _ = writeln!(&mut output, "");
-        let _ = writeln!(&mut output, "EOF");
 
-        return output;
+        // Export NodeWeights Section, only for node-weighted instances
+        if let Some(weights) = &self.node_weights {
+            writeln!(f, "SECTION NodeWeights")?;
+            for weight in weights {
+                writeln!(f, "NW {}", cost(*weight))?;
+            }
+            writeln!(f, "END")?;
+            writeln!(f)?;
+        }
+
+        // Export Coordinates Section, only if there are any
+        if !self.coordinates.is_empty() {
+            writeln!(f, "SECTION Coordinates")?;
+            for (node, coordinate) in &self.coordinates {
+                match coordinate {
+                    Coordinate::Planar { x, y } => writeln!(f, "DD {} {} {}", node, x, y)?,
+                    Coordinate::Spatial { x, y, z } => {
+                        writeln!(f, "DDD {} {} {} {}", node, x, y, z)?
+                    }
+                }
+            }
+            writeln!(f, "END")?;
+            writeln!(f)?;
+        }
+
+        // Export Presolve Section, only if the instance was presolved
+        if let Some(presolve) = &self.presolve {
+            writeln!(f, "SECTION Presolve")?;
+            if let Some(fixed) = presolve.fixed {
+                writeln!(f, "FIXED {}", cost(fixed))?;
+            }
+            if let Some(lower) = presolve.lower {
+                writeln!(f, "LOWER {}", cost(lower))?;
+            }
+            if let Some(upper) = presolve.upper {
+                writeln!(f, "UPPER {}", cost(upper))?;
+            }
+            if let Some(time) = presolve.time {
+                writeln!(f, "TIME {}", time)?;
+            }
+            if let Some(nodes) = presolve.original_nodes {
+                writeln!(f, "ORGNODES {}", nodes)?;
+            }
+            if let Some(edges) = presolve.original_edges {
+                writeln!(f, "ORGEDGES {}", edges)?;
+            }
+            for edge in &presolve.ea_edges {
+                writeln!(f, "EA {} {} {}", edge.from, edge.to, cost(edge.cost))?;
+            }
+            for edge in &presolve.ec_edges {
+                writeln!(f, "EC {} {} {}", edge.from, edge.to, cost(edge.cost))?;
+            }
+            writeln!(f, "END")?;
+            writeln!(f)?;
+        }
+
+        // Export Obstacles Section, only if there are any
+        if !self.obstacles.is_empty() {
+            writeln!(f, "SECTION Obstacles")?;
+            for rect in &self.obstacles {
+                writeln!(f, "RR {} {} {} {}", rect.x1, rect.y1, rect.x2, rect.y2)?;
+            }
+            writeln!(f, "END")?;
+            writeln!(f)?;
+        }
+        writeln!(f, "EOF")
     }
 }
@@ -1,21 +1,164 @@
-use crate::{Edge, Parser, SteinerInstance};
-use petgraph::graph::{NodeIndex, UnGraph};
-use petgraph::visit::Bfs;
+use crate::components::DisjointSets;
+use crate::events::MAX_PREALLOCATED;
+use crate::solution::SteinerTree;
+use crate::{Coordinate, Edge, ParseError, Parser, Rectangle, SteinerInstance};
 use rand::distr::Distribution;
 use rand::distr::weighted::WeightedIndex;
-use rand::random_bool;
-use rand::seq::IndexedRandom;
-use rand::{Rng, rng, seq::index::sample};
-use std::collections::HashSet;
-use std::fmt::Write;
-use std::fs;
+use rand::rngs::StdRng;
+use rand::seq::index::{sample, sample_weighted};
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::{Rng, SeedableRng, rng};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::RangeInclusive;
 use std::path::PathBuf;
-use std::str::FromStr;
+
+/*
+ * Distribution of the edge costs drawn by the generators, one draw per edge.
+ * Both uniform ranges include their bounds and need lo <= hi. The integral
+ * models only produce whole numbers, which are exported without decimals.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CostModel {
+    // Every edge costs 1
+    #[default]
+    Unit,
+    UniformInt {
+        lo: u64,
+        hi: u64,
+    },
+    UniformFloat {
+        lo: f64,
+        hi: f64,
+    },
+    // Exponentially distributed with rate lambda, so with mean 1 / lambda
+    Exponential {
+        lambda: f64,
+    },
+}
+
+impl CostModel {
+    pub fn sample(&self, rng: &mut impl Rng) -> f64 {
+        match *self {
+            CostModel::Unit => 1.0,
+            CostModel::UniformInt { lo, hi } => rng.random_range(lo..=hi) as f64,
+            CostModel::UniformFloat { lo, hi } => rng.random_range(lo..=hi),
+            // Inverse transform sampling, 1 - u is in (0, 1]
+            CostModel::Exponential { lambda } => -(1.0 - rng.random::<f64>()).ln() / lambda,
+        }
+    }
+
+    // Whether every sampled cost is a whole number
+    pub fn is_integral(&self) -> bool {
+        matches!(self, CostModel::Unit | CostModel::UniformInt { .. })
+    }
+}
+
+// What the G(n, p) generators do with a sample whose terminals aren't connected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectivityStrategy {
+    // Throw the sample away and draw a new one
+    #[default]
+    Reject,
+    // Keep the sample and add random edges between the components of the
+    // terminals
+    Repair,
+}
+
+// Reason a generator gave up
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenerationError {
+    // The terminals of the last sample were spread over terminal_components
+    // components after `attempts` samples
+    TerminalsDisconnected {
+        attempts: usize,
+        terminal_components: usize,
+    },
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerationError::TerminalsDisconnected {
+                attempts,
+                terminal_components,
+            } => write!(
+                f,
+                "terminals still in {} components after {} attempts; increase p or use ConnectivityStrategy::Repair",
+                terminal_components, attempts
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+/*
+ * Progress reported by the generators to an optional callback, after every
+ * sample of an instance and after every generated update.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenerationProgress {
+    Sample {
+        // Counted from 1
+        attempt: usize,
+        edges: usize,
+        // Terminals in the largest connected group of terminals
+        terminals_connected: usize,
+        terminals: usize,
+    },
+    Updates {
+        generated: usize,
+        total: usize,
+    },
+}
+
+// Where generate_random_with_fixed_vc puts the terminals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TerminalPlacement {
+    // Uniformly among all nodes, before the edges are sampled
+    #[default]
+    Uniform,
+    // On cover nodes first, and only the terminals beyond the size of the
+    // cover uniformly among the other nodes
+    CoverBiased,
+    // Uniformly among the nodes with at least one edge, chosen anew for
+    // every sample after its edges
+    PostHoc,
+}
+
+// Sensible number of samples to reject before giving up
+pub const DEFAULT_MAX_ATTEMPTS: usize = 1000;
+
+// Settings of generate_random_with_fixed_vc_with_rng that have a sensible default
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedVcOptions {
+    pub costs: CostModel,
+    pub connectivity: ConnectivityStrategy,
+    pub placement: TerminalPlacement,
+    // Samples to take at most, see GenerationError::TerminalsDisconnected
+    pub max_attempts: usize,
+}
+
+impl Default for FixedVcOptions {
+    fn default() -> Self {
+        Self {
+            costs: CostModel::default(),
+            connectivity: ConnectivityStrategy::default(),
+            placement: TerminalPlacement::default(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+}
 
 /*
 * Generate a random Steiner Tree problem instance on `num_vertices` vertices
 * with vertex cover of size at most `vc`.
-* Returns a tuple of the SteinerInstance and the vertex cover.
+* Returns a tuple of the SteinerInstance and the vertex cover, or an error if
+* none of max_attempts samples has connected terminals.
 */
 
 pub fn generate_random_with_fixed_vc(
@@ -23,88 +166,1012 @@ pub fn generate_random_with_fixed_vc(
     num_terminals: usize,
     vc: usize,
     p: f64,
-) -> (SteinerInstance, Vec<usize>) {
-    let cover = generate_vertex_subset(num_vertices, vc);
-    let terminals = generate_vertex_subset(num_vertices, num_terminals);
+    max_attempts: usize,
+) -> Result<(SteinerInstance, Vec<usize>), GenerationError> {
+    generate_random_with_fixed_vc_with_rng(
+        num_vertices,
+        num_terminals,
+        vc,
+        p,
+        &FixedVcOptions {
+            max_attempts,
+            ..Default::default()
+        },
+        None,
+        &mut rng(),
+    )
+}
 
+/*
+* Like generate_random_with_fixed_vc, but with edge costs drawn from
+* `options.costs` and all random choices drawn from `rng`, so a seeded
+* generator reproduces the same instance. Samples with disconnected terminals
+* are handled by `options.connectivity`, taking at most
+* `options.max_attempts` samples, and the terminals are placed according to
+* `options.placement`. Every sample is reported to `progress`.
+*/
+pub fn generate_random_with_fixed_vc_with_rng(
+    num_vertices: usize,
+    num_terminals: usize,
+    vc: usize,
+    p: f64,
+    options: &FixedVcOptions,
+    progress: Option<&mut dyn FnMut(GenerationProgress)>,
+    rng: &mut impl Rng,
+) -> Result<(SteinerInstance, Vec<usize>), GenerationError> {
+    let FixedVcOptions {
+        costs,
+        connectivity,
+        placement,
+        max_attempts,
+    } = *options;
+    let cover = generate_vertex_subset_with_rng(num_vertices, vc, rng);
     let mut is_in_cover = vec![false; num_vertices];
     for v in &cover {
         is_in_cover[v - 1] = true;
     }
 
-    let mut rand_generator = rng();
-    let mut edges = Vec::new();
+    let terminals = match placement {
+        TerminalPlacement::Uniform => Terminals::Fixed(generate_vertex_subset_with_rng(
+            num_vertices,
+            num_terminals,
+            rng,
+        )),
+        TerminalPlacement::CoverBiased => {
+            let mut terminals = cover.clone();
+            terminals.shuffle(rng);
+            terminals.truncate(num_terminals);
+            let others: Vec<usize> = (1..=num_vertices)
+                .filter(|&v| !is_in_cover[v - 1])
+                .collect();
+            terminals.extend(
+                sample(rng, others.len(), num_terminals - terminals.len())
+                    .into_iter()
+                    .map(|i| others[i]),
+            );
+            Terminals::Fixed(terminals)
+        }
+        TerminalPlacement::PostHoc => Terminals::NonIsolated(num_terminals),
+    };
+
+    // Only edges with at least one endpoint in the cover
+    let sampling = GnpSampling {
+        p,
+        costs,
+        connectivity,
+        max_attempts,
+    };
+    let mut instance = sampling.sample(num_vertices, terminals, progress, rng, |i, j| {
+        is_in_cover[i - 1] || is_in_cover[j - 1]
+    })?;
+    debug_assert!(is_vertex_cover(&instance, &cover));
+
+    // Record the provenance in the Comment section of exported files
+    instance.metadata.creator = Some(format!("steinlib {}", env!("CARGO_PKG_VERSION")));
+    instance.metadata.remarks = vec![format!(
+        "generate_random_with_fixed_vc n={} t={} vc={} p={} costs={:?} placement={:?}",
+        num_vertices, num_terminals, vc, p, costs, placement
+    )];
+    let cover_ids: Vec<String> = cover.iter().map(usize::to_string).collect();
+    instance.set_comment_remark("vertex_cover", &cover_ids.join(" "));
+    Ok((instance, cover))
+}
+
+/*
+ * Parameters of a series of instances from generate_random_with_fixed_vc,
+ * with constructors for the series of the experiments of Clementi et al.
+ * Presets display as `n=64,p=0.5,tau=6,t=6`, with tau the size of the
+ * vertex cover.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationPreset {
+    pub series: String,
+    pub n: usize,
+    pub p: f64,
+    pub terminals: usize,
+    pub vc: usize,
+}
+
+impl GenerationPreset {
+    /*
+     * p = c ln(n) / n, with c = 2 up to 256 nodes and c = sqrt(n) above,
+     * and log2(n) terminals and cover nodes
+     */
+    pub fn clementi_sparse(n: usize) -> Self {
+        let factor = if n > 256 { (n as f64).sqrt() } else { 2.0 };
+        let p = factor * (n as f64).ln() / n as f64;
+        Self::clementi("clementi_sparse", n, p)
+    }
+
+    // p = 0.5, and log2(n) terminals and cover nodes
+    pub fn clementi_dense(n: usize) -> Self {
+        Self::clementi("clementi_dense", n, 0.5)
+    }
+
+    pub fn custom(n: usize, p: f64, terminals: usize, vc: usize) -> Self {
+        Self {
+            series: "custom".to_string(),
+            n,
+            p,
+            terminals,
+            vc,
+        }
+    }
+
+    fn clementi(series: &str, n: usize, p: f64) -> Self {
+        let log = (n.max(1) as f64).log2().round() as usize;
+        Self {
+            series: series.to_string(),
+            n,
+            p: p.clamp(0.0, 1.0),
+            terminals: log,
+            vc: log,
+        }
+    }
+}
+
+impl fmt::Display for GenerationPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n={},p={},tau={},t={}",
+            self.n, self.p, self.vc, self.terminals
+        )
+    }
+}
+
+// Position of the preset in the batch and the replication, counted from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresetId {
+    pub preset: usize,
+    pub replication: usize,
+}
+
+/*
+ * Generate `replications` instances for every preset, in order of the
+ * presets, all drawn from one generator seeded with `seed`. Each instance is
+ * named `<replication>_<preset>`, e.g. `1_n=64,p=0.5,tau=6,t=6`, and notes
+ * the series and seed in its remarks, so exported files describe
+ * themselves. Samples with disconnected terminals are repaired, since
+ * rejection hardly ever succeeds for the sparse series, so this only fails
+ * for presets with terminals but without cover nodes.
+ */
+pub fn generate_batch(
+    presets: &[GenerationPreset],
+    replications: usize,
+    seed: u64,
+) -> Result<Vec<(PresetId, SteinerInstance, Vec<usize>)>, GenerationError> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut batch = Vec::with_capacity(presets.len() * replications);
+    for (index, preset) in presets.iter().enumerate() {
+        for replication in 1..=replications {
+            let (mut instance, cover) = generate_random_with_fixed_vc_with_rng(
+                preset.n,
+                preset.terminals,
+                preset.vc,
+                preset.p,
+                &FixedVcOptions {
+                    connectivity: ConnectivityStrategy::Repair,
+                    ..Default::default()
+                },
+                None,
+                &mut rng,
+            )?;
+            instance.metadata.name = Some(format!("{}_{}", replication, preset));
+            instance.metadata.remarks.push(format!(
+                "preset {} replication {} of {} seed {}",
+                preset.series, replication, replications, seed
+            ));
+            let id = PresetId {
+                preset: index,
+                replication,
+            };
+            batch.push((id, instance, cover));
+        }
+    }
+    Ok(batch)
+}
+
+// Whether every edge and arc has an endpoint in `cover`
+pub fn is_vertex_cover(instance: &SteinerInstance, cover: &[usize]) -> bool {
+    let cover: HashSet<usize> = cover.iter().copied().collect();
+    instance
+        .edges
+        .iter()
+        .chain(&instance.arcs)
+        .all(|edge| cover.contains(&edge.from) || cover.contains(&edge.to))
+}
+
+/*
+* Generate an Erdős–Rényi G(n, p) instance on `num_vertices` vertices with
+* `num_terminals` random terminals, each of the n (n - 1) / 2 potential edges
+* present with probability p and costs drawn from `costs`. If a sample has
+* disconnected terminals, random edges are added between their components.
+*/
+pub fn generate_random_gnp(
+    num_vertices: usize,
+    num_terminals: usize,
+    p: f64,
+    costs: CostModel,
+    rng: &mut impl Rng,
+) -> SteinerInstance {
+    let terminals = generate_vertex_subset_with_rng(num_vertices, num_terminals, rng);
+    let sampling = GnpSampling {
+        p,
+        costs,
+        connectivity: ConnectivityStrategy::Repair,
+        max_attempts: 1,
+    };
+    let mut instance = sampling
+        .sample(
+            num_vertices,
+            Terminals::Fixed(terminals),
+            None,
+            rng,
+            |_, _| true,
+        )
+        .expect("every pair of components can be joined directly");
+    instance.metadata.creator = Some(format!("steinlib {}", env!("CARGO_PKG_VERSION")));
+    instance.metadata.remarks = vec![format!(
+        "generate_random_gnp n={} t={} p={} costs={:?}",
+        num_vertices, num_terminals, p, costs
+    )];
+    instance
+}
+
+// Terminals of the samples of GnpSampling
+enum Terminals {
+    Fixed(Vec<usize>),
+    // This many nodes with at least one edge, or any nodes if there are too
+    // few of them, picked for every sample
+    NonIsolated(usize),
+}
+
+// Parameters shared by the G(n, p) generators
+struct GnpSampling {
+    p: f64,
+    costs: CostModel,
+    connectivity: ConnectivityStrategy,
+    max_attempts: usize,
+}
 
-    let i = 1;
-
-    loop {
-        // 1. CLEAR existing edges to ensure a fresh sample from G(n, p)
-        edges.clear();
-
-        // 2. Generate edges (Method A: fresh sample every time)
-        for i in 1..=num_vertices {
-            for j in (i + 1)..=num_vertices {
-                // Your logic: only add edges if at least one endpoint is in the cover
-                if is_in_cover[i - 1] || is_in_cover[j - 1] {
-                    if rand_generator.random_bool(p) {
-                        edges.push(Edge {
-                            from: i,
-                            to: j,
-                            cost: 1.0,
-                        });
+impl GnpSampling {
+    /*
+     * Sample G(n, p) restricted to the node pairs i < j accepted by `allowed`
+     * until all terminals are connected, repairing the sample or drawing a
+     * new one depending on the strategy.
+     */
+    fn sample(
+        &self,
+        num_vertices: usize,
+        terminals: Terminals,
+        mut progress: Option<&mut dyn FnMut(GenerationProgress)>,
+        rng: &mut impl Rng,
+        allowed: impl Fn(usize, usize) -> bool,
+    ) -> Result<SteinerInstance, GenerationError> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            // 1. Start from no edges to ensure a fresh sample from G(n, p)
+            let mut builder = SteinerInstance::builder();
+            builder.nodes(num_vertices);
+            if let Terminals::Fixed(terminals) = &terminals {
+                for &terminal in terminals {
+                    builder.terminal(terminal);
+                }
+            }
+
+            // 2. Generate edges (Method A: fresh sample every time)
+            for i in 1..=num_vertices {
+                for j in (i + 1)..=num_vertices {
+                    if allowed(i, j) && rng.random_bool(self.p) {
+                        builder.edge(i, j, self.costs.sample(rng));
                     }
                 }
             }
+            let mut candidate = builder
+                .build()
+                .expect("generated edges and terminals are nodes of the instance");
+            if let Terminals::NonIsolated(count) = terminals {
+                let degrees = candidate.degrees();
+                let mut nodes: Vec<usize> =
+                    (1..=num_vertices).filter(|&v| degrees[v - 1] > 0).collect();
+                if nodes.len() < count {
+                    nodes = (1..=num_vertices).collect();
+                }
+                candidate.terminals = sample(rng, nodes.len(), count)
+                    .into_iter()
+                    .map(|i| nodes[i])
+                    .collect();
+                candidate.recompute_counts();
+            }
+            if self.connectivity == ConnectivityStrategy::Repair {
+                self.repair(&mut candidate, rng, &allowed);
+            }
+
+            // 3. If all terminals are connected, we have a valid G(n, p) instance
+            let groups = terminal_groups(&candidate);
+            if let Some(report) = progress.as_mut() {
+                report(GenerationProgress::Sample {
+                    attempt: attempts,
+                    edges: candidate.edges.len(),
+                    terminals_connected: groups.iter().copied().max().unwrap_or(0),
+                    terminals: candidate.terminals.len(),
+                });
+            }
+            let terminal_components = groups.len();
+            if terminal_components <= 1 {
+                return Ok(candidate);
+            }
+            if attempts >= self.max_attempts {
+                return Err(GenerationError::TerminalsDisconnected {
+                    attempts,
+                    terminal_components,
+                });
+            }
+
+            // If not connected, the loop starts over with an entirely new
+            // configuration.
         }
+    }
 
-        // 3. Build temporary petgraph to check connectivity
-        let pet_edges: Vec<(u32, u32)> = edges
-            .iter()
-            .map(|e| ((e.from - 1) as u32, (e.to - 1) as u32))
-            .collect();
+    /*
+     * Join the components of the terminals in random order, each with one
+     * random allowed edge to the component of the first terminal, or with
+     * two edges through a random intermediate node if no direct edge is
+     * allowed. Components that can't be joined either way stay apart.
+     */
+    fn repair(
+        &self,
+        instance: &mut SteinerInstance,
+        rng: &mut impl Rng,
+        allowed: &impl Fn(usize, usize) -> bool,
+    ) {
+        let allowed = |a: usize, b: usize| a != b && allowed(a.min(b), a.max(b));
+        let num_nodes = instance.num_nodes;
+        let mut sets = DisjointSets::new(num_nodes + 1);
+        for edge in &instance.edges {
+            sets.union(edge.from, edge.to);
+        }
+
+        let mut terminals = instance.terminals.clone();
+        terminals.shuffle(rng);
+        let Some((&first, rest)) = terminals.split_first() else {
+            return;
+        };
+        for &terminal in rest {
+            if sets.find(terminal) == sets.find(first) {
+                continue;
+            }
+            let roots: Vec<usize> = (0..=num_nodes).map(|node| sets.find(node)).collect();
+            let members = |root: usize| -> Vec<usize> {
+                (1..=num_nodes)
+                    .filter(|&node| roots[node] == root)
+                    .collect()
+            };
+            let (main, other) = (members(roots[first]), members(roots[terminal]));
 
-        // Ensure we account for all nodes, even if they have no edges,
-        // otherwise Bfs might panic or g might be under-sized.
-        let mut g = UnGraph::<(), ()>::with_capacity(num_vertices, pet_edges.len());
-        for _ in 0..num_vertices {
-            g.add_node(());
+            let direct: Vec<(usize, usize)> = main
+                .iter()
+                .flat_map(|&u| other.iter().map(move |&v| (u, v)))
+                .filter(|&(u, v)| allowed(u, v))
+                .collect();
+            let joining = match direct.choose(rng) {
+                Some(&edge) => vec![edge],
+                None => {
+                    let reaches = |node: usize, component: &[usize]| {
+                        component.iter().any(|&member| allowed(node, member))
+                    };
+                    let hubs: Vec<usize> = (1..=num_nodes)
+                        .filter(|&node| {
+                            roots[node] != roots[first] && roots[node] != roots[terminal]
+                        })
+                        .filter(|&node| reaches(node, &main) && reaches(node, &other))
+                        .collect();
+                    let Some(&hub) = hubs.choose(rng) else {
+                        continue;
+                    };
+                    let neighbor = |component: &[usize], rng: &mut _| {
+                        let candidates: Vec<usize> = component
+                            .iter()
+                            .copied()
+                            .filter(|&member| allowed(hub, member))
+                            .collect();
+                        *candidates
+                            .choose(rng)
+                            .expect("the hub reaches the component")
+                    };
+                    vec![(neighbor(&main, rng), hub), (hub, neighbor(&other, rng))]
+                }
+            };
+            for (u, v) in joining {
+                sets.union(u, v);
+                instance.edges.push(Edge {
+                    from: u.min(v),
+                    to: u.max(v),
+                    cost: self.costs.sample(rng),
+                });
+            }
         }
-        for (u, v) in pet_edges {
-            g.add_edge(NodeIndex::new(u as usize), NodeIndex::new(v as usize), ());
+        instance.recompute_counts();
+    }
+}
+
+// Number of terminals in each component containing terminals
+fn terminal_groups(instance: &SteinerInstance) -> Vec<usize> {
+    let mut sets = DisjointSets::new(instance.num_nodes + 1);
+    for edge in &instance.edges {
+        sets.union(edge.from, edge.to);
+    }
+    let mut groups: HashMap<usize, usize> = HashMap::new();
+    for &terminal in &instance.terminals {
+        *groups.entry(sets.find(terminal)).or_default() += 1;
+    }
+    groups.into_values().collect()
+}
+
+/*
+* Generate one G(n, p) instance per requested density on the same node set.
+* Every potential edge draws a single uniform value which is compared against
+* each density, so the instances are nested: each denser instance contains all
+* edges of the sparser ones. All instances share the same terminals.
+* The instances are returned sorted by increasing density.
+*/
+pub fn generate_density_series(
+    num_vertices: usize,
+    num_terminals: usize,
+    densities: &[f64],
+    seed: u64,
+) -> Vec<SteinerInstance> {
+    let mut rand_generator = StdRng::seed_from_u64(seed);
+    let terminals: Vec<usize> = sample(&mut rand_generator, num_vertices, num_terminals)
+        .into_iter()
+        .map(|x| x + 1)
+        .collect();
+
+    let mut base = Vec::with_capacity(num_vertices * num_vertices.saturating_sub(1) / 2);
+    for i in 1..=num_vertices {
+        for j in (i + 1)..=num_vertices {
+            base.push((i, j, rand_generator.random::<f64>()));
         }
+    }
+
+    let mut sorted_densities = densities.to_vec();
+    sorted_densities.sort_by(f64::total_cmp);
+
+    sorted_densities
+        .into_iter()
+        .map(|p| {
+            let edges = base
+                .iter()
+                .filter(|(_, _, draw)| *draw < p)
+                .map(|&(from, to, _)| Edge {
+                    from,
+                    to,
+                    cost: 1.0,
+                })
+                .collect();
+            SteinerInstance::new(num_vertices, edges, terminals.clone())
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EuclideanOptions {
+    // Connect every point only to its k nearest neighbours instead of to all
+    // other points. A Euclidean minimum spanning tree is added on top, so the
+    // sparse graph stays connected.
+    pub nearest_neighbors: Option<usize>,
+}
+
+/*
+ * Generate a geometric instance in the style of the SteinLib P4E and X
+ * classes: `num_points` points drawn uniformly from `bounding_box`, all pairs
+ * connected by edges costing their Euclidean distance and a random subset of
+ * `num_terminals` terminals. The points are stored as coordinates of the
+ * nodes, so they are exported in the Coordinates section.
+ */
+pub fn generate_random_euclidean(
+    num_points: usize,
+    num_terminals: usize,
+    bounding_box: Rectangle,
+    rng: &mut impl Rng,
+) -> SteinerInstance {
+    generate_random_euclidean_with_options(
+        num_points,
+        num_terminals,
+        bounding_box,
+        &EuclideanOptions::default(),
+        rng,
+    )
+}
+
+pub fn generate_random_euclidean_with_options(
+    num_points: usize,
+    num_terminals: usize,
+    bounding_box: Rectangle,
+    options: &EuclideanOptions,
+    rng: &mut impl Rng,
+) -> SteinerInstance {
+    let (x_min, x_max) = (
+        bounding_box.x1.min(bounding_box.x2),
+        bounding_box.x1.max(bounding_box.x2),
+    );
+    let (y_min, y_max) = (
+        bounding_box.y1.min(bounding_box.y2),
+        bounding_box.y1.max(bounding_box.y2),
+    );
+    // Point of node v at index v - 1
+    let points: Vec<(f64, f64)> = (0..num_points)
+        .map(|_| {
+            (
+                rng.random_range(x_min..=x_max),
+                rng.random_range(y_min..=y_max),
+            )
+        })
+        .collect();
+    let terminals = generate_vertex_subset_with_rng(num_points, num_terminals, rng);
+    let distance = |i: usize, j: usize| {
+        let (a, b) = (points[i], points[j]);
+        (a.0 - b.0).hypot(a.1 - b.1)
+    };
 
-        // 4. Connectivity Check
-        if terminals.is_empty() {
-            break;
+    // Pairs (i, j) with i < j of 0-based point indices
+    let mut pairs: BTreeSet<(usize, usize)> = BTreeSet::new();
+    match options.nearest_neighbors {
+        None => {
+            for i in 0..num_points {
+                pairs.extend((i + 1..num_points).map(|j| (i, j)));
+            }
+        }
+        Some(k) => {
+            for i in 0..num_points {
+                let mut others: Vec<usize> = (0..num_points).filter(|&j| j != i).collect();
+                others.sort_by(|&a, &b| distance(i, a).total_cmp(&distance(i, b)));
+                pairs.extend(others.into_iter().take(k).map(|j| (i.min(j), i.max(j))));
+            }
+            pairs.extend(euclidean_spanning_tree(num_points, distance));
         }
+    }
 
-        let mut visited_terminals = HashSet::new();
-        let start_node = NodeIndex::new(terminals[0] - 1);
+    let edges = pairs
+        .into_iter()
+        .map(|(i, j)| Edge {
+            from: i + 1,
+            to: j + 1,
+            cost: distance(i, j),
+        })
+        .collect();
+    let mut instance = SteinerInstance::new(num_points, edges, terminals);
+    instance.coordinates = points
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y))| (i + 1, Coordinate::Planar { x, y }))
+        .collect();
+    instance.metadata.creator = Some(format!("steinlib {}", env!("CARGO_PKG_VERSION")));
+    instance.metadata.remarks = vec![format!(
+        "generate_random_euclidean n={} t={} box=({}, {})-({}, {}) nearest_neighbors={:?}",
+        num_points, num_terminals, x_min, y_min, x_max, y_max, options.nearest_neighbors
+    )];
+    instance
+}
 
-        // Safety check: Does the start_node actually exist in the graph?
-        if start_node.index() < g.node_count() {
-            let mut bfs = Bfs::new(&g, start_node);
-            while let Some(nx) = bfs.next(&g) {
-                let actual_val = nx.index() + 1;
-                if terminals.contains(&actual_val) {
-                    visited_terminals.insert(actual_val);
+// Prim's algorithm on the complete graph, as pairs (i, j) with i < j
+fn euclidean_spanning_tree(
+    num_points: usize,
+    distance: impl Fn(usize, usize) -> f64,
+) -> Vec<(usize, usize)> {
+    let mut tree = Vec::with_capacity(num_points.saturating_sub(1));
+    let mut in_tree = vec![false; num_points];
+    // Closest tree point and its distance for every point outside the tree
+    let mut closest = vec![(0, f64::INFINITY); num_points];
+    let mut next = Some(0).filter(|_| num_points > 0);
+    while let Some(point) = next {
+        in_tree[point] = true;
+        if point != 0 {
+            let parent = closest[point].0;
+            tree.push((parent.min(point), parent.max(point)));
+        }
+        next = None;
+        for other in (0..num_points).filter(|&other| !in_tree[other]) {
+            let d = distance(point, other);
+            if d < closest[other].1 {
+                closest[other] = (point, d);
+            }
+            if next.is_none_or(|best: usize| closest[other].1 < closest[best].1) {
+                next = Some(other);
+            }
+        }
+    }
+    tree
+}
+
+// Reason generate_grid couldn't place the terminals
+#[derive(Debug, Clone, PartialEq)]
+pub enum GridError {
+    // The largest connected region of free cells has fewer cells than there
+    // are terminals
+    TooFewConnectedCells {
+        num_terminals: usize,
+        largest_region: usize,
+    },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GridError::TooFewConnectedCells {
+                num_terminals,
+                largest_region,
+            } => write!(
+                f,
+                "the largest connected region of free cells has {} cells, too few for {} terminals; use fewer or smaller obstacles",
+                largest_region, num_terminals
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+/*
+ * Generate the width x height grid graph with unit costs for rectilinear
+ * instances. The cell in column x and row y, both counted from 0, is node
+ * y * width + x + 1, so the nodes are numbered row by row, and has the
+ * coordinates (x, y). Cells inside or on the border of an obstacle keep
+ * their node but lose all their edges, and the obstacles are stored on the
+ * instance. The terminals are sampled from the largest connected region of
+ * free cells, so they are always connected.
+ */
+pub fn generate_grid(
+    width: usize,
+    height: usize,
+    num_terminals: usize,
+    obstacles: &[Rectangle],
+    rng: &mut impl Rng,
+) -> Result<SteinerInstance, GridError> {
+    generate_grid_with_costs(
+        width,
+        height,
+        num_terminals,
+        obstacles,
+        CostModel::Unit,
+        rng,
+    )
+}
+
+// Like generate_grid, but with edge costs drawn from `costs`
+pub fn generate_grid_with_costs(
+    width: usize,
+    height: usize,
+    num_terminals: usize,
+    obstacles: &[Rectangle],
+    costs: CostModel,
+    rng: &mut impl Rng,
+) -> Result<SteinerInstance, GridError> {
+    let node = |x: usize, y: usize| y * width + x + 1;
+    let is_free = |x: usize, y: usize| {
+        let (x, y) = (x as f64, y as f64);
+        !obstacles.iter().any(|rect| {
+            (rect.x1.min(rect.x2)..=rect.x1.max(rect.x2)).contains(&x)
+                && (rect.y1.min(rect.y2)..=rect.y1.max(rect.y2)).contains(&y)
+        })
+    };
+
+    let mut free = vec![false; width * height + 1];
+    let mut edges = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if !is_free(x, y) {
+                continue;
+            }
+            free[node(x, y)] = true;
+            // Edges to the free cells left of and above this one
+            for (other_x, other_y) in [(x.wrapping_sub(1), y), (x, y.wrapping_sub(1))] {
+                if other_x < width && other_y < height && free[node(other_x, other_y)] {
+                    edges.push(Edge {
+                        from: node(other_x, other_y),
+                        to: node(x, y),
+                        cost: costs.sample(rng),
+                    });
                 }
             }
         }
+    }
+
+    let mut instance = SteinerInstance::new(width * height, edges, Vec::new());
+    let largest_region = instance
+        .connected_components()
+        .into_iter()
+        .filter(|component| free[component[0]])
+        .max_by_key(Vec::len)
+        .unwrap_or_default();
+    if largest_region.len() < num_terminals {
+        return Err(GridError::TooFewConnectedCells {
+            num_terminals,
+            largest_region: largest_region.len(),
+        });
+    }
+    instance.terminals = sample(rng, largest_region.len(), num_terminals)
+        .into_iter()
+        .map(|i| largest_region[i])
+        .collect();
+
+    instance.coordinates = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let coordinate = Coordinate::Planar {
+                x: x as f64,
+                y: y as f64,
+            };
+            (node(x, y), coordinate)
+        })
+        .collect();
+    instance.obstacles = obstacles.to_vec();
+    instance.recompute_counts();
+    instance.metadata.creator = Some(format!("steinlib {}", env!("CARGO_PKG_VERSION")));
+    instance.metadata.remarks = vec![format!(
+        "generate_grid width={} height={} t={} obstacles={} costs={:?}",
+        width,
+        height,
+        num_terminals,
+        obstacles.len(),
+        costs
+    )];
+    Ok(instance)
+}
+
+/*
+ * Generate a directed instance with arcs only: a random arborescence rooted
+ * at `root` spanning all nodes is planted first, then every other ordered
+ * pair of distinct nodes gets an arc with probability p. Every node, in
+ * particular every terminal, is thus reachable from the root. The root is
+ * stored as the root of the instance and is one of the num_terminals
+ * terminals, the others are picked at random.
+ */
+pub fn generate_random_directed(
+    num_vertices: usize,
+    num_terminals: usize,
+    root: usize,
+    p: f64,
+    costs: CostModel,
+    rng: &mut impl Rng,
+) -> SteinerInstance {
+    assert!(
+        (1..=num_vertices).contains(&root),
+        "root {} is not one of the {} nodes",
+        root,
+        num_vertices
+    );
+
+    // Each node after the root hangs off a random node before it in a
+    // random order
+    let mut order: Vec<usize> = (1..=num_vertices).filter(|&v| v != root).collect();
+    order.shuffle(rng);
+    order.insert(0, root);
+    let mut planted = HashSet::new();
+    let mut arcs = Vec::new();
+    for (i, &node) in order.iter().enumerate().skip(1) {
+        let parent = order[rng.random_range(0..i)];
+        planted.insert((parent, node));
+        arcs.push(Edge {
+            from: parent,
+            to: node,
+            cost: costs.sample(rng),
+        });
+    }
+    for from in 1..=num_vertices {
+        for to in 1..=num_vertices {
+            if from != to && !planted.contains(&(from, to)) && rng.random_bool(p) {
+                arcs.push(Edge {
+                    from,
+                    to,
+                    cost: costs.sample(rng),
+                });
+            }
+        }
+    }
+
+    // The other terminals among the nodes except the root
+    let mut terminals = Vec::with_capacity(num_terminals);
+    if num_terminals > 0 {
+        terminals.push(root);
+        terminals.extend(
+            sample(rng, num_vertices - 1, num_terminals - 1)
+                .into_iter()
+                .map(|i| if i + 1 < root { i + 1 } else { i + 2 }),
+        );
+    }
+
+    let mut instance = SteinerInstance::new(num_vertices, Vec::new(), terminals);
+    instance.arcs = arcs;
+    instance.root = Some(root);
+    instance.recompute_counts();
+    instance.metadata.creator = Some(format!("steinlib {}", env!("CARGO_PKG_VERSION")));
+    instance.metadata.remarks = vec![format!(
+        "generate_random_directed n={} t={} root={} p={} costs={:?}",
+        num_vertices, num_terminals, root, p, costs
+    )];
+    instance
+}
+
+/*
+ * Generate an instance with a known optimum: a random tree on the terminals
+ * whose edges all cost planted_cost, and noise edges between every other
+ * pair of nodes with probability p_noise, their costs drawn from
+ * noise_cost_range. Every Steiner tree needs at least num_terminals - 1
+ * edges, and no edge is cheaper than planted_cost, so the planted tree is
+ * optimal. The range must lie above planted_cost, which also makes the
+ * planted tree the only optimal solution. Returns the instance and the
+ * planted tree.
+ */
+pub fn generate_planted(
+    num_vertices: usize,
+    num_terminals: usize,
+    p_noise: f64,
+    planted_cost: f64,
+    noise_cost_range: RangeInclusive<f64>,
+    rng: &mut impl Rng,
+) -> (SteinerInstance, SteinerTree) {
+    assert!(
+        *noise_cost_range.start() > planted_cost,
+        "noise costs from {} must exceed the planted cost {}",
+        noise_cost_range.start(),
+        planted_cost
+    );
+    let terminals = generate_vertex_subset_with_rng(num_vertices, num_terminals, rng);
+
+    // Each terminal after the first hangs off a random earlier one
+    let mut planted = Vec::with_capacity(num_terminals.saturating_sub(1));
+    for (i, &terminal) in terminals.iter().enumerate().skip(1) {
+        let parent = terminals[rng.random_range(0..i)];
+        planted.push(Edge {
+            from: parent.min(terminal),
+            to: parent.max(terminal),
+            cost: planted_cost,
+        });
+    }
+    let in_tree: HashSet<Edge> = planted.iter().cloned().collect();
 
-        // 5. If all terminals reached, we have a valid G(n, 1/2) instance
-        if visited_terminals.len() == terminals.len() {
-            break;
+    let mut edges = planted.clone();
+    for i in 1..=num_vertices {
+        for j in (i + 1)..=num_vertices {
+            let edge = Edge {
+                from: i,
+                to: j,
+                cost: 0.0,
+            };
+            if !in_tree.contains(&edge) && rng.random_bool(p_noise) {
+                edges.push(Edge {
+                    cost: rng.random_range(noise_cost_range.clone()),
+                    ..edge
+                });
+            }
         }
+    }
+
+    let mut instance = SteinerInstance::new(num_vertices, edges, terminals);
+    instance.metadata.creator = Some(format!("steinlib {}", env!("CARGO_PKG_VERSION")));
+    instance.metadata.remarks = vec![format!(
+        "generate_planted n={} t={} p_noise={} planted_cost={} noise_costs={:?}",
+        num_vertices, num_terminals, p_noise, planted_cost, noise_cost_range
+    )];
+    (instance, SteinerTree::new(planted))
+}
+
+// How generate_scale_free picks the terminals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TerminalSampling {
+    #[default]
+    Uniform,
+    // Proportional to the degree, so hubs become terminals more often
+    DegreeBiased,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScaleFreeOptions {
+    pub terminals: TerminalSampling,
+}
+
+/*
+ * Generate a scale-free instance by Barabási–Albert preferential attachment:
+ * starting from a clique on m_edges_per_node + 1 nodes, every further node
+ * is joined to m_edges_per_node distinct earlier nodes, each picked with
+ * probability proportional to its degree. The instance is connected and its
+ * degrees are heavy-tailed. A value of 0 for m_edges_per_node counts as 1.
+ */
+pub fn generate_scale_free(
+    num_vertices: usize,
+    num_terminals: usize,
+    m_edges_per_node: usize,
+    costs: CostModel,
+    rng: &mut impl Rng,
+) -> SteinerInstance {
+    generate_scale_free_with_options(
+        num_vertices,
+        num_terminals,
+        m_edges_per_node,
+        costs,
+        &ScaleFreeOptions::default(),
+        rng,
+    )
+}
+
+pub fn generate_scale_free_with_options(
+    num_vertices: usize,
+    num_terminals: usize,
+    m_edges_per_node: usize,
+    costs: CostModel,
+    options: &ScaleFreeOptions,
+    rng: &mut impl Rng,
+) -> SteinerInstance {
+    let m = m_edges_per_node.max(1);
+    let seed_size = num_vertices.min(m + 1);
+    let mut edges = Vec::new();
+    // Every node once per incident edge, so a uniform pick from it is a
+    // pick proportional to the degree
+    let mut endpoints: Vec<usize> = Vec::new();
+    let mut connect = |from: usize, to: usize, endpoints: &mut Vec<usize>, rng: &mut _| {
+        edges.push(Edge {
+            from,
+            to,
+            cost: costs.sample(rng),
+        });
+        endpoints.extend([from, to]);
+    };
 
-        // If not connected, the loop starts over, 'edges' is cleared,
-        // and we try an entirely new configuration.
+    for i in 1..=seed_size {
+        for j in (i + 1)..=seed_size {
+            connect(i, j, &mut endpoints, rng);
+        }
+    }
+    for node in (seed_size + 1)..=num_vertices {
+        let mut targets: Vec<usize> = Vec::with_capacity(m);
+        while targets.len() < m {
+            let target = *endpoints.choose(rng).expect("the seed clique has edges");
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        for target in targets {
+            connect(target, node, &mut endpoints, rng);
+        }
     }
 
-    (SteinerInstance::new(num_vertices, edges, terminals), cover)
+    let terminals = match options.terminals {
+        TerminalSampling::Uniform => {
+            generate_vertex_subset_with_rng(num_vertices, num_terminals, rng)
+        }
+        TerminalSampling::DegreeBiased => {
+            let mut degrees = vec![0_usize; num_vertices];
+            for &node in &endpoints {
+                degrees[node - 1] += 1;
+            }
+            // Without any edges all weights are 0, so fall back to uniform
+            match sample_weighted(rng, num_vertices, |i| degrees[i] as f64, num_terminals) {
+                Ok(indices) => indices.into_iter().map(|i| i + 1).collect(),
+                Err(_) => generate_vertex_subset_with_rng(num_vertices, num_terminals, rng),
+            }
+        }
+    };
+
+    let mut instance = SteinerInstance::new(num_vertices, edges, terminals);
+    instance.metadata.creator = Some(format!("steinlib {}", env!("CARGO_PKG_VERSION")));
+    instance.metadata.remarks = vec![format!(
+        "generate_scale_free n={} t={} m={} costs={:?} terminals={:?}",
+        num_vertices, num_terminals, m, costs, options.terminals
+    )];
+    instance
 }
 
-#[derive(Debug)]
+/*
+ * Relative weights of the four kinds of updates. They need not sum to 1:
+ * each update is of a kind with probability its weight divided by the sum
+ * of all weights, among the kinds that are possible at that point. Weights
+ * must be finite and non-negative, and at least one must be positive.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UpdateProbabilities {
     pub edge_insertion: f32,
     pub edge_deletion: f32,
@@ -112,169 +1179,267 @@ pub struct UpdateProbabilities {
     pub terminal_deactivation: f32,
 }
 
+// Reason UpdateProbabilities::validate rejected the weights
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbError {
+    // Negative, infinite or NaN, named after the field
+    InvalidWeight { update: &'static str, weight: f32 },
+    AllZero,
+    // Every kind of update with a positive weight was impossible after
+    // `generated` updates, e.g. deleting edges when there are none left
+    NoPossibleUpdate { generated: usize },
+}
+
+impl fmt::Display for ProbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbError::InvalidWeight { update, weight } => write!(
+                f,
+                "weight {} of {} is not a finite non-negative number",
+                weight, update
+            ),
+            ProbError::AllZero => write!(f, "all update weights are 0"),
+            ProbError::NoPossibleUpdate { generated } => write!(
+                f,
+                "no update with a positive weight is possible after {} updates",
+                generated
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProbError {}
+
+impl UpdateProbabilities {
+    // Only edge updates, with the given weights
+    pub fn edges_only(insertion: f32, deletion: f32) -> Self {
+        Self {
+            edge_insertion: insertion,
+            edge_deletion: deletion,
+            terminal_activation: 0.0,
+            terminal_deactivation: 0.0,
+        }
+    }
+
+    // Only terminal updates, with the given weights
+    pub fn terminals_only(activation: f32, deactivation: f32) -> Self {
+        Self {
+            edge_insertion: 0.0,
+            edge_deletion: 0.0,
+            terminal_activation: activation,
+            terminal_deactivation: deactivation,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ProbError> {
+        let weights = [
+            ("edge_insertion", self.edge_insertion),
+            ("edge_deletion", self.edge_deletion),
+            ("terminal_activation", self.terminal_activation),
+            ("terminal_deactivation", self.terminal_deactivation),
+        ];
+        for (update, weight) in weights {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(ProbError::InvalidWeight { update, weight });
+            }
+        }
+        if weights.iter().all(|&(_, weight)| weight == 0.0) {
+            return Err(ProbError::AllZero);
+        }
+        Ok(())
+    }
+
+    // The same weights scaled to sum to 1, unchanged if they sum to 0
+    pub fn normalized(&self) -> UpdateProbabilities {
+        let sum = self.edge_insertion
+            + self.edge_deletion
+            + self.terminal_activation
+            + self.terminal_deactivation;
+        if sum == 0.0 || !sum.is_finite() {
+            return *self;
+        }
+        Self {
+            edge_insertion: self.edge_insertion / sum,
+            edge_deletion: self.edge_deletion / sum,
+            terminal_activation: self.terminal_activation / sum,
+            terminal_deactivation: self.terminal_deactivation / sum,
+        }
+    }
+}
+
+/*
+ * With serde, operations are tagged by their `type`, next to the fields of
+ * their content, e.g. `{"type":"EdgeInsertion","from":1,"to":2,"cost":1.0}`
+ * or `{"type":"VertexDeletion","vertex":3}`.
+ */
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type")
+)]
 pub enum UpdateOperation {
     EdgeInsertion(Edge),
     EdgeDeletion(Edge),
     VertexInsertion,
+    #[cfg_attr(feature = "serde", serde(with = "vertex_field"))]
     VertexDeletion(usize),
+    #[cfg_attr(feature = "serde", serde(with = "vertex_field"))]
     TerminalActivation(usize),
+    #[cfg_attr(feature = "serde", serde(with = "vertex_field"))]
     TerminalDeactivation(usize),
-    Query(SteinerInstance),
+    // Boxed since instances are much larger than the other operations
+    Query(Box<SteinerInstance>),
+}
+
+// Internally tagged variants need a map as content, so a bare vertex is
+// wrapped into `{"vertex": v}`
+#[cfg(feature = "serde")]
+mod vertex_field {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Vertex {
+        vertex: usize,
+    }
+
+    pub fn serialize<S: Serializer>(vertex: &usize, serializer: S) -> Result<S::Ok, S::Error> {
+        Vertex { vertex: *vertex }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+        Ok(Vertex::deserialize(deserializer)?.vertex)
+    }
 }
 
-impl ToString for UpdateOperation {
-    fn to_string(&self) -> String {
+impl fmt::Display for UpdateOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            UpdateOperation::Query(steiner_instance) => steiner_instance.to_string(),
-            Self::EdgeInsertion(edge) => format!("E I {} {} {}", edge.from, edge.to, edge.cost),
-            Self::EdgeDeletion(edge) => format!("E D {} {} {}", edge.from, edge.to, edge.cost),
-            Self::VertexInsertion => format!("V I"),
-            Self::VertexDeletion(vertex) => format!("V D {}", vertex),
-            Self::TerminalActivation(vertex) => format!("T A {}", vertex),
-            Self::TerminalDeactivation(vertex) => format!("T D {}", vertex),
+            UpdateOperation::Query(steiner_instance) => write!(f, "{}", steiner_instance),
+            Self::EdgeInsertion(edge) => write!(f, "E I {}", edge),
+            Self::EdgeDeletion(edge) => write!(f, "E D {}", edge),
+            Self::VertexInsertion => write!(f, "V I"),
+            Self::VertexDeletion(vertex) => write!(f, "V D {}", vertex),
+            Self::TerminalActivation(vertex) => write!(f, "T A {}", vertex),
+            Self::TerminalDeactivation(vertex) => write!(f, "T D {}", vertex),
         }
     }
 }
 
+/*
+ * Parse a single line in the format of the Display impl. A query is a `Q`
+ * line, optionally followed by its number, and gets an empty instance.
+ */
 impl std::str::FromStr for UpdateOperation {
     type Err = ParseUpdateError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s
-            .chars()
-            .nth(0)
-            .expect("Tried to parse invalid update line")
-        {
-            'T' => {
-                let action = s
-                    .split(" ")
-                    .nth(1)
-                    .expect("Encountered invalid terminal update");
-                let target = s
-                    .split(" ")
-                    .nth(2)
-                    .expect("Encountered invalid terminal update");
-                let target = target
-                    .parse::<usize>()
-                    .expect("Encountered invalid terminal update");
-                if action == "A" {
-                    Ok(Self::TerminalActivation(target))
-                } else {
-                    Ok(Self::TerminalDeactivation(target))
-                }
-            }
-            'E' => {
-                let components: Vec<&str> = s.split(" ").collect();
-                let action = components[1];
-                let from_vert = components[2]
-                    .parse::<usize>()
-                    .expect("Invalid from vertex in edge update");
-                let to_vert = components[3]
-                    .parse::<usize>()
-                    .expect("Invalid from vertex in edge update");
-                let cost = components[4]
-                    .parse::<f64>()
-                    .expect("Invalid cost in edge update");
-                let target = Edge {
-                    from: from_vert,
-                    to: to_vert,
-                    cost,
-                };
-                if action == "I" {
-                    Ok(Self::EdgeInsertion(target))
-                } else {
-                    Ok(Self::EdgeDeletion(target))
-                }
-            }
-            'V' => {
-                let components: Vec<&str> = s.split(" ").collect();
-                let action = components[1];
-                if action == "I" {
-                    Ok(Self::VertexInsertion)
-                } else {
-                    let vertex = components[2]
-                        .parse::<usize>()
-                        .expect("Invalid vertex identification");
-                    Ok(Self::VertexDeletion(vertex))
-                }
-            }
-            // TODO: I think we don't need the instance
-            'Q' => Ok(Self::Query(SteinerInstance::default())),
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let number = |n: usize| -> Result<usize, ParseUpdateError> {
+            tokens
+                .get(n)
+                .and_then(|token| token.parse().ok())
+                .ok_or(ParseUpdateError)
+        };
+        let edge = || -> Result<Edge, ParseUpdateError> {
+            let cost = tokens
+                .get(4)
+                .and_then(|token| token.parse().ok())
+                .ok_or(ParseUpdateError)?;
+            Ok(Edge {
+                from: number(2)?,
+                to: number(3)?,
+                cost,
+            })
+        };
+
+        match tokens.as_slice() {
+            ["Q", ..] => Ok(Self::Query(Box::default())),
+            ["T", "A", _] => Ok(Self::TerminalActivation(number(2)?)),
+            ["T", "D", _] => Ok(Self::TerminalDeactivation(number(2)?)),
+            ["E", "I", _, _, _] => Ok(Self::EdgeInsertion(edge()?)),
+            ["E", "D", _, _, _] => Ok(Self::EdgeDeletion(edge()?)),
+            ["V", "I"] => Ok(Self::VertexInsertion),
+            ["V", "D", _] => Ok(Self::VertexDeletion(number(2)?)),
             _ => Err(ParseUpdateError),
         }
     }
 }
 
-impl UpdateOperation {
-    fn from_str(s: &str) -> Result<Self, ()> {
-        match s
-            .chars()
-            .nth(0)
-            .expect("Tried to parse invalid update line")
-        {
-            'T' => {
-                let action = s
-                    .split(" ")
-                    .nth(1)
-                    .expect("Encountered invalid terminal update");
-                let target = s
-                    .split(" ")
-                    .nth(2)
-                    .expect("Encountered invalid terminal update");
-                let target = target
-                    .parse::<usize>()
-                    .expect("Encountered invalid terminal update");
-                if action == "A" {
-                    Ok(Self::TerminalActivation(target))
-                } else {
-                    Ok(Self::TerminalDeactivation(target))
-                }
-            }
-            'E' => {
-                let components: Vec<&str> = s.split(" ").collect();
-                let action = components[1];
-                let from_vert = components[2]
-                    .parse::<usize>()
-                    .expect("Invalid from vertex in edge update");
-                let to_vert = components[3]
-                    .parse::<usize>()
-                    .expect("Invalid from vertex in edge update");
-                let cost = components[4]
-                    .parse::<f64>()
-                    .expect("Invalid cost in edge update");
-                let target = Edge {
-                    from: from_vert,
-                    to: to_vert,
-                    cost,
-                };
-                if action == "I" {
-                    Ok(Self::EdgeInsertion(target))
-                } else {
-                    Ok(Self::EdgeDeletion(target))
-                }
-            }
-            'V' => {
-                let components: Vec<&str> = s.split(" ").collect();
-                let action = components[1];
-                if action == "I" {
-                    Ok(Self::VertexInsertion)
-                } else {
-                    let vertex = components[2]
-                        .parse::<usize>()
-                        .expect("Invalid vertex identification");
-                    Ok(Self::VertexDeletion(vertex))
-                }
-            }
-            // TODO: I think we don't need the instance
-            'Q' => Ok(Self::Query(SteinerInstance::default())),
-            _ => Err(()),
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseUpdateError;
+
+impl fmt::Display for ParseUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid update line")
+    }
+}
+
+impl std::error::Error for ParseUpdateError {}
+
+/*
+ * When the update sequence generators insert a query, always right after an
+ * update. Operations are counted from 1.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryPolicy {
+    // After every operation with this probability in 0..=1
+    PerOp(f64),
+    // After operations k, 2k, 3k, ..., never for k = 0
+    EveryK(usize),
+    // After the operations with these numbers
+    AtIndices(Vec<usize>),
+}
+
+impl QueryPolicy {
+    fn query_after(&self, operation: usize, rng: &mut impl Rng) -> bool {
+        match self {
+            QueryPolicy::PerOp(probability) => rng.random_bool(*probability),
+            QueryPolicy::EveryK(k) => *k > 0 && operation.is_multiple_of(*k),
+            QueryPolicy::AtIndices(indices) => indices.contains(&operation),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct ParseUpdateError;
+// Settings of generate_update_sequence_with_rng that have a sensible default
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateSequenceOptions {
+    // No queries by default
+    pub queries: QueryPolicy,
+    // Start from the nodes alone instead of the edges and terminals of the
+    // instance
+    pub start_empty: bool,
+    // Costs of the edges that can be inserted
+    pub costs: CostModel,
+}
+
+impl Default for UpdateSequenceOptions {
+    fn default() -> Self {
+        Self {
+            queries: QueryPolicy::PerOp(0.0),
+            start_empty: false,
+            costs: CostModel::default(),
+        }
+    }
+}
+
+// Kind of the next update, in the order of the weights
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Choice {
+    EdgeInsert,
+    EdgeDelete,
+    TermActivate,
+    TermDeactivate,
+}
+
+impl Choice {
+    const ALL: [Choice; 4] = [
+        Choice::EdgeInsert,
+        Choice::EdgeDelete,
+        Choice::TermActivate,
+        Choice::TermDeactivate,
+    ];
+}
 
 pub fn generate_update_sequence(
     instance: &SteinerInstance,
@@ -283,15 +1448,56 @@ pub fn generate_update_sequence(
     vc: Vec<usize>,
     start_empty: bool,
     total_updates: usize,
-) -> Vec<UpdateOperation> {
+) -> Result<Vec<UpdateOperation>, ProbError> {
+    generate_update_sequence_with_rng(
+        instance,
+        update_probs,
+        vc,
+        total_updates,
+        &UpdateSequenceOptions {
+            queries: QueryPolicy::PerOp(query_prob),
+            start_empty,
+            ..Default::default()
+        },
+        None,
+        &mut rng(),
+    )
+}
+
+/*
+* Like generate_update_sequence, but with queries placed according to
+* `options.queries` and all random choices, including whether to insert a
+* query, drawn from `rng`. Every candidate edge draws its cost from
+* `options.costs` once, so an edge inserted again comes back with the same
+* cost.
+* Every generated update is reported to `progress`. Fails if the weights
+* in `update_probs` don't pass UpdateProbabilities::validate, or if no
+* update with a positive weight is possible at some point.
+*/
+pub fn generate_update_sequence_with_rng(
+    instance: &SteinerInstance,
+    update_probs: UpdateProbabilities,
+    vc: Vec<usize>,
+    total_updates: usize,
+    options: &UpdateSequenceOptions,
+    mut progress: Option<&mut dyn FnMut(GenerationProgress)>,
+    rng: &mut impl Rng,
+) -> Result<Vec<UpdateOperation>, ProbError> {
+    let UpdateSequenceOptions {
+        queries,
+        start_empty,
+        costs,
+    } = options;
+    update_probs.validate()?;
     let mut updates = Vec::new();
-    let mut rng = rng();
 
     let mut current_edges: Vec<Edge> = Vec::new();
     let mut current_terminals: Vec<usize> = Vec::new();
 
+    // Edges are kept normalized, so e.g. an edge given as (2, 1) by the
+    // instance is found when (1, 2) is picked for deletion
     if !start_empty {
-        current_edges = instance.edges.clone();
+        current_edges = instance.edges.iter().map(Edge::normalized).collect();
         current_terminals = instance.terminals.clone();
     }
 
@@ -304,132 +1510,180 @@ pub fn generate_update_sequence(
         update_probs.terminal_deactivation,
     ];
 
-    let dist = WeightedIndex::new(&weights).expect("Invalid probabilities");
-
     let mut all_edges: Vec<Edge> = Vec::with_capacity(vc.len() * vc.len());
     for i in 1..vc.len() + 1 {
         for j in i + 1..vc.len() + 1 {
             all_edges.push(Edge {
                 from: i,
                 to: j,
-                cost: 1.0,
+                cost: costs.sample(rng),
             });
         }
     }
 
-    for _ in 0..total_updates {
-        let mut update_generated = false;
-        while !update_generated {
-            // 1. Choose either terminal or edge update
-            // 2. choose between insert/activate or delete/deactivate
-            // 3. choose legal target of operation (edge or vertex)
-
-            // 1 = terminal update, 0 = edge update
-            let choice = dist.sample(&mut rng);
-            if choice == 1 && current_edges.len() == 0
-                || choice == 3 && current_terminals.len() == 0
-            {
-                // Can't delete objects if none to sample from exist
-                continue;
+    for generated in 1..=total_updates {
+        // Legal targets of every kind of update, a kind without any gets no
+        // weight for this update
+        let (present, absent): (Vec<&Edge>, Vec<&Edge>) = all_edges
+            .iter()
+            .partition(|edge| current_edges_map.contains(edge));
+        let (active, inactive): (Vec<usize>, Vec<usize>) = instance
+            .terminals
+            .iter()
+            .partition(|terminal| current_terminals.contains(terminal));
+        let available = |choice: Choice| match choice {
+            Choice::EdgeInsert => !absent.is_empty(),
+            Choice::EdgeDelete => !present.is_empty(),
+            Choice::TermActivate => !inactive.is_empty(),
+            Choice::TermDeactivate => !active.is_empty(),
+        };
+        let possible = Choice::ALL.map(|choice| {
+            if available(choice) {
+                weights[choice as usize]
+            } else {
+                0.0
             }
+        });
+        let Ok(dist) = WeightedIndex::new(possible) else {
+            return Err(ProbError::NoPossibleUpdate {
+                generated: generated - 1,
+            });
+        };
 
-            // terminal update
-            if choice == 2 || choice == 3 {
-                let is_activation = choice == 2;
-                let available_vertices: Vec<usize> = instance
-                    .terminals
-                    .clone()
-                    .into_iter()
-                    .filter(|i| is_activation ^ current_terminals.contains(i))
-                    .collect();
-                if available_vertices.len() == 0 {
-                    continue;
-                }
-                let target = *available_vertices.choose(&mut rng).unwrap();
-                if is_activation {
-                    updates.push(UpdateOperation::TerminalActivation(target));
-                    current_terminals.push(target);
-                } else {
-                    updates.push(UpdateOperation::TerminalDeactivation(target));
-                    current_terminals.retain(|&x| x != target);
-                }
-                update_generated = true;
+        match Choice::ALL[dist.sample(rng)] {
+            Choice::EdgeInsert => {
+                let target = (*absent.choose(rng).expect("available")).clone();
+                updates.push(UpdateOperation::EdgeInsertion(target.clone()));
+                current_edges.push(target.clone());
+                current_edges_map.insert(target);
             }
-
-            // edge update
-            if choice == 0 || choice == 1 {
-                let is_insertion = choice == 0;
-                let available_edges: Vec<Edge> = all_edges
-                    .clone()
-                    .into_iter()
-                    .filter(|i| is_insertion ^ current_edges_map.contains(i))
-                    .collect();
-                if available_edges.len() == 0 {
-                    continue;
-                }
-                let target = available_edges.choose(&mut rng).unwrap().clone();
-                if is_insertion {
-                    updates.push(UpdateOperation::EdgeInsertion(target.clone()));
-                    current_edges.push(target.clone());
-                    current_edges_map.insert(target.clone());
-                } else {
-                    updates.push(UpdateOperation::EdgeDeletion(target.clone()));
-                    current_edges.retain(|x| *x != target);
-                    current_edges_map.remove(&target);
-                }
-                update_generated = true;
+            Choice::EdgeDelete => {
+                let target = (*present.choose(rng).expect("available")).clone();
+                updates.push(UpdateOperation::EdgeDeletion(target.clone()));
+                current_edges.retain(|x| *x != target);
+                current_edges_map.remove(&target);
             }
-
-            let do_query = random_bool(query_prob);
-            if do_query {
-                updates.push(UpdateOperation::Query(SteinerInstance::new(
-                    instance.num_nodes,
-                    current_edges.clone(),
-                    current_terminals.clone(),
-                )));
+            Choice::TermActivate => {
+                let target = *inactive.choose(rng).expect("available");
+                updates.push(UpdateOperation::TerminalActivation(target));
+                current_terminals.push(target);
+            }
+            Choice::TermDeactivate => {
+                let target = *active.choose(rng).expect("available");
+                updates.push(UpdateOperation::TerminalDeactivation(target));
+                current_terminals.retain(|&x| x != target);
             }
         }
+
+        if queries.query_after(generated, rng) {
+            updates.push(UpdateOperation::Query(Box::new(SteinerInstance::new(
+                instance.num_nodes,
+                current_edges.clone(),
+                current_terminals.clone(),
+            ))));
+        }
+        if let Some(report) = progress.as_mut() {
+            report(GenerationProgress::Updates {
+                generated,
+                total: total_updates,
+            });
+        }
     }
 
     // Ensure that any full sequence ends with a query
-    if !matches!(updates.last().unwrap(), UpdateOperation::Query(_)) {
-        updates.push(UpdateOperation::Query(SteinerInstance::new(
+    if !matches!(updates.last(), Some(UpdateOperation::Query(_))) {
+        updates.push(UpdateOperation::Query(Box::new(SteinerInstance::new(
             instance.num_nodes,
             current_edges.clone(),
             current_terminals.clone(),
-        )));
+        ))));
     }
 
-    return updates;
+    Ok(updates)
 }
 
 pub fn generate_vertex_subset(num_vertices: usize, size: usize) -> Vec<usize> {
-    sample(&mut rng(), num_vertices, size)
+    generate_vertex_subset_with_rng(num_vertices, size, &mut rng())
+}
+
+pub fn generate_vertex_subset_with_rng(
+    num_vertices: usize,
+    size: usize,
+    rng: &mut impl Rng,
+) -> Vec<usize> {
+    sample(rng, num_vertices, size)
         .into_iter()
         .map(|x| x + 1) // Shift range from 0..n to 1..=n
         .collect()
 }
 
-pub fn export_update_sequence(updates: Vec<UpdateOperation>) -> (String, Vec<String>) {
-    let mut main_output = String::new();
-    let mut query_instances = Vec::new();
-    let _ = writeln!(main_output, "SECTION UPDATES");
+/*
+ * Render an update sequence as the contents of a `.dus` file.
+ * Queries are replaced by inline `Q n` markers, numbered from 1 in order of
+ * appearance. The query instances themselves are not part of the output.
+ */
+pub fn updates_to_dus(updates: &[UpdateOperation]) -> String {
+    let mut output = Vec::new();
+    write_dus(updates, &mut output).expect("writing to a Vec never fails");
+    String::from_utf8(output).expect("updates are rendered as UTF-8")
+}
+
+// Writer based counterpart of updates_to_dus
+pub fn write_dus<W: io::Write>(updates: &[UpdateOperation], mut w: W) -> io::Result<()> {
+    writeln!(w, "SECTION UPDATES")?;
     let mut query_no = 1;
 
     for update in updates {
         match update {
             UpdateOperation::Query(_) => {
-                let _ = writeln!(main_output, "Q {}", query_no);
-                let _ = query_instances.push(update.to_string());
+                writeln!(w, "Q {}", query_no)?;
                 query_no += 1;
             }
-            _ => {
-                let _ = writeln!(main_output, "{}", update.to_string());
-            }
+            _ => writeln!(w, "{}", update)?,
         }
     }
 
-    return (main_output, query_instances);
+    Ok(())
+}
+
+pub fn export_update_sequence(updates: Vec<UpdateOperation>) -> (String, Vec<String>) {
+    let main_output = updates_to_dus(&updates);
+    let query_instances = updates
+        .iter()
+        .filter(|update| matches!(update, UpdateOperation::Query(_)))
+        .map(|update| update.to_string())
+        .collect();
+
+    (main_output, query_instances)
+}
+
+/*
+ * Writer based variant of export_update_sequence. The `.dus` file is streamed
+ * to `main`, and every query instance to the writer `query_writer` returns
+ * for its number, counting from 1. Nothing is kept in memory.
+ */
+pub fn write_update_sequence<W, Q, F>(
+    updates: &[UpdateOperation],
+    main: W,
+    mut query_writer: F,
+) -> io::Result<()>
+where
+    W: io::Write,
+    Q: io::Write,
+    F: FnMut(usize) -> io::Result<Q>,
+{
+    let mut main = BufWriter::new(main);
+    write_dus(updates, &mut main)?;
+    main.flush()?;
+    let queries = updates.iter().filter_map(|update| match update {
+        UpdateOperation::Query(instance) => Some(instance),
+        _ => None,
+    });
+    for (query_no, instance) in (1..).zip(queries) {
+        instance.write_stp(query_writer(query_no)?)?;
+    }
+
+    Ok(())
 }
 
 pub fn output_update_sequence(
@@ -456,81 +1710,203 @@ pub fn output_update_sequence(
     }
 
     // 2. Original logic: Export and write files
-    let (main_output, query_instances) = export_update_sequence(updates);
-
-    let mut main_path = path.clone();
-    main_path.push("updates.dus");
-    fs::write(main_path, main_output)?;
-
-    let mut query_no = 1;
-    for query_instance in query_instances {
-        let mut query_path = path.clone();
-        query_path.push(format!("instance_{}.gr", query_no));
-        fs::write(query_path, query_instance)?;
-        query_no += 1;
-    }
-
-    Ok(())
+    let main_output = File::create(path.join("updates.dus"))?;
+    write_update_sequence(&updates, main_output, |query_no| {
+        File::create(path.join(format!("instance_{}.gr", query_no)))
+    })
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DynamicInstance {
     pub num_vertices: usize,
     pub target_value: usize,
     pub update_sequence: Vec<UpdateOperation>,
     performed_steps: usize,
+    // Degree and neighbors of vertex v at index v - 1, maintained while
+    // replaying
+    degrees: Vec<usize>,
+    neighbors: Vec<BTreeSet<usize>>,
+}
+
+/*
+ * Problem found by DynamicInstance::from_str, with the 1-based line number
+ * of the update or the 1-based number of the query.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicInstanceError {
+    InvalidUpdate { line_number: usize, line: String },
+    // A vertex id above MAX_PREALLOCATED, which would need a huge degree table
+    VertexOutOfRange { line_number: usize, vertex: usize },
+    MissingQuery { query: usize },
+    InvalidQuery { query: usize, error: ParseError },
 }
 
+impl fmt::Display for DynamicInstanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DynamicInstanceError::InvalidUpdate { line_number, line } => {
+                write!(f, "invalid update on line {}: {}", line_number, line)
+            }
+            DynamicInstanceError::VertexOutOfRange {
+                line_number,
+                vertex,
+            } => write!(
+                f,
+                "vertex {} on line {} is above the limit of {}",
+                vertex, line_number, MAX_PREALLOCATED
+            ),
+            DynamicInstanceError::MissingQuery { query } => {
+                write!(f, "query {} has no instance", query)
+            }
+            DynamicInstanceError::InvalidQuery { query, error } => {
+                write!(f, "instance of query {} is invalid: {}", query, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DynamicInstanceError {}
+
 impl DynamicInstance {
+    /*
+     * Read an update sequence in the `.dus` format of write_dus, with the
+     * instance of the i-th query at index i - 1 of query_instance_specs.
+     * Blank lines are skipped.
+     */
     pub fn from_str(
         update_specs: String,
         target_value: usize,
-        query_instance_specs: &Vec<String>,
-    ) -> Self {
+        query_instance_specs: &[String],
+    ) -> Result<Self, DynamicInstanceError> {
         let mut update_sequence = Vec::new();
         let mut num_queries = 0;
-        for line in update_specs.lines() {
-            if line.starts_with("SECTION UPDATES") {
+        for (index, line) in update_specs.lines().enumerate() {
+            let line_number = index + 1;
+            if line.starts_with("SECTION UPDATES") || line.trim().is_empty() {
                 continue;
             }
-            let mut next_update =
-                UpdateOperation::from_str(line).expect("Passed invalid update specs.");
+            let mut next_update: UpdateOperation =
+                line.parse()
+                    .map_err(|_| DynamicInstanceError::InvalidUpdate {
+                        line_number,
+                        line: line.to_string(),
+                    })?;
+            let vertex = Self::_helper_max_vertex(&next_update);
+            if vertex > MAX_PREALLOCATED {
+                return Err(DynamicInstanceError::VertexOutOfRange {
+                    line_number,
+                    vertex,
+                });
+            }
             if matches!(next_update, UpdateOperation::Query(_)) {
                 // Fill the update with the actual query instance
-                let query_instance =
-                    Parser::default().parse_stp(&query_instance_specs[num_queries]);
                 num_queries += 1;
-                next_update = UpdateOperation::Query(query_instance);
+                let specs = query_instance_specs
+                    .get(num_queries - 1)
+                    .ok_or(DynamicInstanceError::MissingQuery { query: num_queries })?;
+                let query_instance = Parser::default().parse_stp(specs).map_err(|error| {
+                    DynamicInstanceError::InvalidQuery {
+                        query: num_queries,
+                        error,
+                    }
+                })?;
+                next_update = UpdateOperation::Query(Box::new(query_instance));
             }
             update_sequence.push(next_update);
         }
-        return Self {
-            num_vertices: Self::vertices_from_updates(&update_sequence),
+        let num_vertices = Self::vertices_from_updates(&update_sequence);
+        Ok(Self {
+            num_vertices,
             target_value,
             update_sequence,
             performed_steps: 0,
-        };
+            degrees: vec![0; num_vertices],
+            neighbors: vec![BTreeSet::new(); num_vertices],
+        })
     }
 
     pub fn reset(&mut self) {
         self.performed_steps = 0;
+        self.degrees = vec![0; self.num_vertices];
+        self.neighbors = vec![BTreeSet::new(); self.num_vertices];
     }
 
     pub fn get_next(&mut self) -> Option<UpdateOperation> {
         if self.performed_steps < self.update_sequence.len() {
-            let result = Some(self.update_sequence[self.performed_steps].clone());
+            let result = self.update_sequence[self.performed_steps].clone();
             self.performed_steps += 1;
-            return result;
+            self.update_degrees(&result);
+            return Some(result);
         }
-        return None;
+        None
     }
 
-    fn vertices_from_updates(update_sequence: &Vec<UpdateOperation>) -> usize {
-        Self::_helper_max_vertex(
-            update_sequence
-                .iter()
-                .max_by_key(|x| Self::_helper_max_vertex(x))
-                .unwrap(),
-        )
+    /*
+     * Degrees of all vertices after the updates replayed so far, where index
+     * v - 1 holds the degree of vertex v. The replay starts from the empty
+     * graph, so only edges inserted by the sequence itself are counted.
+     * Inserting a present edge or deleting an absent one changes nothing,
+     * deleting a vertex removes its edges from its neighbors as well.
+     */
+    pub fn current_degrees(&self) -> &[usize] {
+        &self.degrees
+    }
+
+    fn update_degrees(&mut self, op: &UpdateOperation) {
+        match op {
+            // Vertex ids are 1-based, ignore anything referring to vertex 0
+            UpdateOperation::EdgeInsertion(e) | UpdateOperation::EdgeDeletion(e)
+                if e.from == 0 || e.to == 0 => {}
+            UpdateOperation::VertexDeletion(0) => {}
+            UpdateOperation::EdgeInsertion(e) => {
+                let needed = e.from.max(e.to);
+                if self.degrees.len() < needed {
+                    self.degrees.resize(needed, 0);
+                    self.neighbors.resize(needed, BTreeSet::new());
+                }
+                if self.neighbors[e.from - 1].insert(e.to) {
+                    self.neighbors[e.to - 1].insert(e.from);
+                    self.degrees[e.from - 1] += 1;
+                    self.degrees[e.to - 1] += 1;
+                }
+            }
+            UpdateOperation::EdgeDeletion(e) => {
+                let present = self
+                    .neighbors
+                    .get_mut(e.from - 1)
+                    .is_some_and(|neighbors| neighbors.remove(&e.to));
+                if present {
+                    self.neighbors[e.to - 1].remove(&e.from);
+                    self.degrees[e.from - 1] -= 1;
+                    self.degrees[e.to - 1] -= 1;
+                }
+            }
+            UpdateOperation::VertexInsertion => {
+                self.degrees.push(0);
+                self.neighbors.push(BTreeSet::new());
+            }
+            UpdateOperation::VertexDeletion(v) => {
+                // Deleting a vertex removes all of its incident edges
+                if let Some(neighbors) = self.neighbors.get_mut(v - 1) {
+                    for u in std::mem::take(neighbors) {
+                        if u != *v {
+                            self.neighbors[u - 1].remove(v);
+                            self.degrees[u - 1] -= 1;
+                        }
+                    }
+                    self.degrees[v - 1] = 0;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn vertices_from_updates(update_sequence: &[UpdateOperation]) -> usize {
+        update_sequence
+            .iter()
+            .map(Self::_helper_max_vertex)
+            .max()
+            .unwrap_or(0)
     }
 
     fn _helper_max_vertex(op: &UpdateOperation) -> usize {
@@ -0,0 +1,161 @@
+// Reading and writing the `.gr` format of the PACE 2018 challenge.
+// PACE files use the STP sections without the STP header, optionally followed
+// by a tree decomposition. The DIMACS flavour with a `p` problem line, `e`
+// edge lines and `t` terminal lines is accepted as well. In both flavours
+// lines starting with `c` are comments.
+
+use crate::events::StpEvent;
+use crate::export::{FileOptions, write_file};
+use crate::{IndexBase, Line, ParseError, Parser, Section, SteinerInstance};
+use std::io::{Error, ErrorKind, Write};
+use std::path::Path;
+
+/*
+ * PACE only allows integral edge costs. Other costs are either refused or
+ * multiplied by a factor and rounded to the nearest integer.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GrCosts {
+    #[default]
+    RequireIntegral,
+    Scale(f64),
+}
+
+impl Parser {
+    /*
+     * Parse a `.gr` file, like the query instances written by
+     * output_update_sequence. The tree decomposition of PACE track 2 files is
+     * skipped.
+     */
+    pub fn parse_gr(&mut self, gr: &str) -> Result<SteinerInstance, ParseError> {
+        let mut parsed_result = SteinerInstance::default();
+        self.reset();
+        // Neither flavour has the STP header
+        self.header_seen = true;
+        let mut dimacs = false;
+        let mut in_decomposition = false;
+
+        for text in gr.lines() {
+            let line = Line::new(text.trim());
+            let keyword = line.keyword();
+            let is_decomposition_start = line.is_section()
+                && line
+                    .tokens
+                    .get(1)
+                    .is_some_and(|name| name.eq_ignore_ascii_case("Tree"));
+
+            if keyword == "c" || in_decomposition || is_decomposition_start {
+                self.line_number += 1;
+                in_decomposition = (in_decomposition || is_decomposition_start)
+                    && !keyword.eq_ignore_ascii_case("END");
+            } else if keyword == "p" {
+                self.line_number += 1;
+                self.process_problem_line(&line, &mut parsed_result)?;
+                dimacs = true;
+            } else if keyword == "t" && dimacs {
+                self.line_number += 1;
+                if let Some(event) = self.process_terminals_line(&line)? {
+                    parsed_result.apply_event(event);
+                }
+            } else {
+                self.parse_stp_line(text, &mut parsed_result)?;
+                if self.finished {
+                    break;
+                }
+            }
+        }
+
+        // DIMACS files don't declare the number of terminals
+        if dimacs {
+            parsed_result.num_terminals = parsed_result.terminals.len();
+        }
+        self.finish(&mut parsed_result)?;
+        Ok(parsed_result)
+    }
+
+    /*
+     * `p <kind> <nodes> <edges>` opens the graph of a DIMACS style file.
+     */
+    fn process_problem_line(
+        &mut self,
+        line: &Line,
+        instance: &mut SteinerInstance,
+    ) -> Result<(), ParseError> {
+        self.num_nodes = self.nth_arg(line, 2, "node count")?;
+        let num_edges = self.nth_arg(line, 3, "edge count")?;
+        instance.apply_event(StpEvent::NodeCount(self.num_nodes));
+        instance.apply_event(StpEvent::EdgeCount(num_edges));
+        self.current_section = Section::Graph;
+        Ok(())
+    }
+}
+
+impl SteinerInstance {
+    /*
+     * Write the instance in the PACE 2018 `.gr` format, i.e. the Graph and
+     * Terminals sections of STP without the header. The counts are taken from
+     * the actual edges and terminals, since the PACE verifier checks them.
+     * Arcs can't be expressed and are refused, as are costs that aren't
+     * integral after applying `costs`.
+     */
+    pub fn write_gr<W: Write>(&self, writer: &mut W, costs: GrCosts) -> std::io::Result<()> {
+        if self.index_base == IndexBase::Zero {
+            return self.to_one_indexed().write_gr(writer, costs);
+        }
+        if !self.arcs.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "the .gr format has no arcs",
+            ));
+        }
+
+        writeln!(writer, "SECTION Graph")?;
+        writeln!(writer, "Nodes {}", self.num_nodes)?;
+        writeln!(writer, "Edges {}", self.edges.len())?;
+        for edge in &self.edges {
+            let cost = match costs {
+                GrCosts::RequireIntegral => edge.cost,
+                GrCosts::Scale(factor) => (edge.cost * factor).round(),
+            };
+            if !cost.is_finite() || cost.fract() != 0.0 {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "edge {} {} has the non-integral cost {}",
+                        edge.from, edge.to, cost
+                    ),
+                ));
+            }
+            // Integral floats are printed without a fractional part
+            writeln!(writer, "E {} {} {}", edge.from, edge.to, cost)?;
+        }
+        writeln!(writer, "END")?;
+        writeln!(writer)?;
+
+        writeln!(writer, "SECTION Terminals")?;
+        writeln!(writer, "Terminals {}", self.terminals.len())?;
+        for terminal in &self.terminals {
+            writeln!(writer, "T {}", terminal)?;
+        }
+        writeln!(writer, "END")?;
+        writeln!(writer)?;
+        writeln!(writer, "EOF")
+    }
+
+    pub fn to_gr_string(&self, costs: GrCosts) -> std::io::Result<String> {
+        let mut output = Vec::new();
+        self.write_gr(&mut output, costs)?;
+        Ok(String::from_utf8(output).expect("the .gr output is ASCII"))
+    }
+
+    pub fn write_gr_to_file(
+        &self,
+        path: impl AsRef<Path>,
+        costs: GrCosts,
+        options: FileOptions,
+    ) -> std::io::Result<()> {
+        write_file(path.as_ref(), options, |writer| {
+            self.write_gr(writer, costs)
+        })
+    }
+}
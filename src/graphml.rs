@@ -0,0 +1,153 @@
+// Export to GraphML, to open instances in tools like Gephi or yEd.
+// Node `nV` is STP node V. Nodes carry a boolean `terminal` attribute and,
+// when the instance has coordinates, `x` and `y` (and `z` for spatial ones).
+// Edges carry their `cost`. Elements are written in a fixed order, nodes by
+// id and edges as stored, so generated files can be diffed.
+
+use crate::{Coordinate, IndexBase, SteinerInstance};
+use std::collections::HashSet;
+use std::io::{self, Error, ErrorKind, Write};
+
+// Escape a value for use in XML text and double quoted attributes
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl SteinerInstance {
+    /*
+     * Write the instance as a GraphML document. Arcs become edges with
+     * `directed="true"` in the otherwise undirected graph, and the instance
+     * name, if any, becomes the description of the graph. Edges and arcs
+     * with an endpoint outside of 1..=num_nodes would refer to undeclared
+     * nodes and are refused before anything is written, as are costs and
+     * coordinates that aren't finite, which GraphML doubles can't express.
+     */
+    pub fn write_graphml<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        if self.index_base == IndexBase::Zero {
+            return self.to_one_indexed().write_graphml(writer);
+        }
+        let in_range = |node: usize| (1..=self.num_nodes).contains(&node);
+        for edge in self.edges.iter().chain(&self.arcs) {
+            if !in_range(edge.from) || !in_range(edge.to) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "edge {} {} has an endpoint outside of the {} nodes",
+                        edge.from, edge.to, self.num_nodes
+                    ),
+                ));
+            }
+            if !edge.cost.is_finite() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "edge {} {} has the cost {}, GraphML needs finite numbers",
+                        edge.from, edge.to, edge.cost
+                    ),
+                ));
+            }
+        }
+        for (node, coordinate) in self.coordinates.range(1..=self.num_nodes) {
+            let finite = match *coordinate {
+                Coordinate::Planar { x, y } => x.is_finite() && y.is_finite(),
+                Coordinate::Spatial { x, y, z } => x.is_finite() && y.is_finite() && z.is_finite(),
+            };
+            if !finite {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("node {} has coordinates that aren't finite", node),
+                ));
+            }
+        }
+        let has_coordinates = !self.coordinates.is_empty();
+        let is_spatial = self
+            .coordinates
+            .values()
+            .any(|coordinate| matches!(coordinate, Coordinate::Spatial { .. }));
+
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="terminal" for="node" attr.name="terminal" attr.type="boolean"><default>false</default></key>"#
+        )?;
+        let mut axes = Vec::new();
+        if has_coordinates {
+            axes.extend(["x", "y"]);
+        }
+        if is_spatial {
+            axes.push("z");
+        }
+        for axis in &axes {
+            writeln!(
+                writer,
+                r#"  <key id="{0}" for="node" attr.name="{0}" attr.type="double"/>"#,
+                axis
+            )?;
+        }
+        writeln!(
+            writer,
+            r#"  <key id="cost" for="edge" attr.name="cost" attr.type="double"/>"#
+        )?;
+
+        writeln!(writer, r#"  <graph id="G" edgedefault="undirected">"#)?;
+        if let Some(name) = &self.metadata.name {
+            writeln!(writer, "    <desc>{}</desc>", escape(name))?;
+        }
+
+        let terminals: HashSet<usize> = self.terminals.iter().copied().collect();
+        for node in 1..=self.num_nodes {
+            write!(writer, r#"    <node id="n{}">"#, node)?;
+            if terminals.contains(&node) {
+                write!(writer, r#"<data key="terminal">true</data>"#)?;
+            }
+            if let Some(coordinate) = self.coordinates.get(&node) {
+                let values = match *coordinate {
+                    Coordinate::Planar { x, y } => vec![x, y],
+                    Coordinate::Spatial { x, y, z } => vec![x, y, z],
+                };
+                for (axis, value) in axes.iter().zip(values) {
+                    write!(writer, r#"<data key="{}">{}</data>"#, axis, value)?;
+                }
+            }
+            writeln!(writer, "</node>")?;
+        }
+
+        let edges = self.edges.iter().map(|edge| (edge, false));
+        let arcs = self.arcs.iter().map(|arc| (arc, true));
+        for (index, (edge, directed)) in edges.chain(arcs).enumerate() {
+            let direction = if directed { r#" directed="true""# } else { "" };
+            writeln!(
+                writer,
+                r#"    <edge id="e{}" source="n{}" target="n{}"{}><data key="cost">{}</data></edge>"#,
+                index + 1,
+                edge.from,
+                edge.to,
+                direction,
+                edge.cost
+            )?;
+        }
+        writeln!(writer, "  </graph>")?;
+        writeln!(writer, "</graphml>")
+    }
+
+    pub fn to_graphml(&self) -> io::Result<String> {
+        let mut output = Vec::new();
+        self.write_graphml(&mut output)?;
+        Ok(String::from_utf8(output).expect("the GraphML output is UTF-8"))
+    }
+}
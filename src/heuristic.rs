@@ -0,0 +1,189 @@
+// Mehlhorn's variant of the distance network heuristic, a 2-approximation
+// for the Steiner tree problem in O(m + n log n).
+// Instead of the full metric closure it only connects terminals whose
+// Voronoi regions touch, which gives an MST of the same cost.
+
+use crate::components::DisjointSets;
+use crate::shortest_paths::Entry;
+use crate::solution::{SolveError, SteinerTree};
+use crate::{Edge, SteinerInstance};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry as MapEntry;
+use std::collections::{BinaryHeap, HashMap};
+
+impl SteinerInstance {
+    /*
+     * A Steiner tree of at most twice the optimal cost. A single terminal
+     * gives the empty tree, terminals in different components give
+     * SolveError::DisconnectedTerminals.
+     */
+    pub fn approximate_steiner_tree(&self) -> Result<SteinerTree, SolveError> {
+        let terminals = self.solvable_terminals()?;
+        if terminals.len() <= 1 {
+            return Ok(SteinerTree::default());
+        }
+        let n = self.num_nodes;
+        let adjacency = self.adjacency();
+
+        // 1. Voronoi regions: the nearest terminal of every node, its
+        // distance and the first edge of the path towards it. Node 0 marks
+        // nodes that no terminal reaches.
+        let mut distance = vec![f64::INFINITY; n + 1];
+        let mut base = vec![0; n + 1];
+        let mut towards = vec![None; n + 1];
+        let mut heap = BinaryHeap::new();
+        for &terminal in &terminals {
+            distance[terminal] = 0.0;
+            base[terminal] = terminal;
+            heap.push(Reverse(Entry(0.0, terminal)));
+        }
+        while let Some(Reverse(Entry(d, node))) = heap.pop() {
+            if d > distance[node] {
+                continue;
+            }
+            for &(neighbor, index) in adjacency.entries(node) {
+                let candidate = d + self.edges[index].cost;
+                if candidate < distance[neighbor] {
+                    distance[neighbor] = candidate;
+                    base[neighbor] = base[node];
+                    towards[neighbor] = Some(index);
+                    heap.push(Reverse(Entry(candidate, neighbor)));
+                }
+            }
+        }
+
+        // 2. The cheapest edge between each pair of neighboring regions,
+        // with the length of the terminal path through it
+        let mut bridges = HashMap::new();
+        for (index, edge) in self.edges.iter().enumerate() {
+            let (a, b) = (base[edge.from], base[edge.to]);
+            if a == b || a == 0 || b == 0 {
+                continue;
+            }
+            let length = distance[edge.from] + edge.cost + distance[edge.to];
+            match bridges.entry((a.min(b), a.max(b))) {
+                MapEntry::Vacant(entry) => {
+                    entry.insert((length, index));
+                }
+                MapEntry::Occupied(mut entry) => {
+                    if length < entry.get().0 {
+                        entry.insert((length, index));
+                    }
+                }
+            }
+        }
+
+        // 3. MST of the terminals over the bridges, each chosen bridge
+        // expanded to its path in the instance
+        let mut bridges: Vec<(f64, usize)> = bridges.into_values().collect();
+        bridges.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let mut regions = DisjointSets::new(n + 1);
+        let mut selected = vec![false; self.edges.len()];
+        for (_, index) in bridges {
+            let edge = &self.edges[index];
+            if !regions.union(base[edge.from], base[edge.to]) {
+                continue;
+            }
+            selected[index] = true;
+            for mut node in [edge.from, edge.to] {
+                // The rest of the path is selected once one edge of it is
+                while let Some(index) = towards[node]
+                    && !selected[index]
+                {
+                    selected[index] = true;
+                    node = other_endpoint(&self.edges[index], node);
+                }
+            }
+        }
+
+        let first = terminals[0];
+        if let Some(&terminal) = terminals
+            .iter()
+            .find(|&&terminal| regions.find(terminal) != regions.find(first))
+        {
+            return Err(SolveError::DisconnectedTerminals {
+                from: first,
+                to: terminal,
+            });
+        }
+
+        // 4. MST of the subgraph induced by the selected nodes, without the
+        // leaves that aren't terminals
+        let mut in_tree = vec![false; n + 1];
+        for (edge, _) in self.edges.iter().zip(&selected).filter(|(_, s)| **s) {
+            in_tree[edge.from] = true;
+            in_tree[edge.to] = true;
+        }
+        let mut is_terminal = vec![false; n + 1];
+        for &terminal in &terminals {
+            is_terminal[terminal] = true;
+        }
+        Ok(self.spanning_tree_of(&in_tree, &is_terminal))
+    }
+
+    /*
+     * MST of the subgraph induced by the nodes v with in_tree[v], without the
+     * leaves that aren't terminals. The edges are sorted by their index in
+     * the instance. Both slices must cover all nodes.
+     */
+    pub(crate) fn spanning_tree_of(&self, in_tree: &[bool], is_terminal: &[bool]) -> SteinerTree {
+        let mut candidates: Vec<usize> = (0..self.edges.len())
+            .filter(|&i| in_tree[self.edges[i].from] && in_tree[self.edges[i].to])
+            .collect();
+        candidates.sort_by(|&a, &b| self.edges[a].cost.total_cmp(&self.edges[b].cost));
+        let mut components = DisjointSets::new(in_tree.len());
+        let tree: Vec<usize> = candidates
+            .into_iter()
+            .filter(|&i| components.union(self.edges[i].from, self.edges[i].to))
+            .collect();
+
+        let mut kept = self.prune_leaves(&tree, is_terminal);
+        kept.sort_unstable();
+        SteinerTree::new(kept.into_iter().map(|i| self.edges[i].clone()).collect())
+    }
+
+    // The edges of the forest `tree` that remain after repeatedly removing
+    // leaves that aren't terminals. is_terminal must cover all endpoints.
+    pub(crate) fn prune_leaves(&self, tree: &[usize], is_terminal: &[bool]) -> Vec<usize> {
+        let mut degree = vec![0; is_terminal.len()];
+        let mut incident = vec![Vec::new(); is_terminal.len()];
+        for (position, &index) in tree.iter().enumerate() {
+            let edge = &self.edges[index];
+            for node in [edge.from, edge.to] {
+                degree[node] += 1;
+                incident[node].push(position);
+            }
+        }
+
+        let mut removed = vec![false; tree.len()];
+        let mut leaves: Vec<usize> = (1..is_terminal.len())
+            .filter(|&node| degree[node] == 1 && !is_terminal[node])
+            .collect();
+        while let Some(leaf) = leaves.pop() {
+            let Some(&position) = incident[leaf].iter().find(|&&p| !removed[p]) else {
+                continue;
+            };
+            removed[position] = true;
+            degree[leaf] -= 1;
+            let other = other_endpoint(&self.edges[tree[position]], leaf);
+            degree[other] -= 1;
+            if degree[other] == 1 && !is_terminal[other] {
+                leaves.push(other);
+            }
+        }
+
+        tree.iter()
+            .zip(removed)
+            .filter(|(_, removed)| !removed)
+            .map(|(&index, _)| index)
+            .collect()
+    }
+}
+
+pub(crate) fn other_endpoint(edge: &Edge, node: usize) -> usize {
+    if edge.from == node {
+        edge.to
+    } else {
+        edge.from
+    }
+}
@@ -0,0 +1,95 @@
+// Switching an instance between 1-based and 0-based node numbering.
+// The base is tracked in SteinerInstance::index_base, so converting twice
+// in the same direction leaves the instance as it is instead of shifting
+// the nodes again.
+
+use crate::conversion::ConversionError;
+use crate::{Edge, IndexBase, SteinerInstance};
+use std::ops::Range;
+
+impl SteinerInstance {
+    // The node ids, 1..num_nodes + 1 or 0..num_nodes depending on the base
+    pub fn node_ids(&self) -> Range<usize> {
+        let first = self.first_node();
+        first..first + self.num_nodes
+    }
+
+    pub(crate) fn first_node(&self) -> usize {
+        match self.index_base {
+            IndexBase::Zero => 0,
+            IndexBase::One => 1,
+        }
+    }
+
+    /*
+     * A copy with nodes numbered 0..num_nodes, every node id lowered by one.
+     * Values indexed by v - 1, like max_degrees, are already 0-based and stay
+     * as they are. A 0-based instance is returned unchanged. Fails if
+     * anything refers to node 0, which the lenient parser accepts.
+     */
+    pub fn to_zero_indexed(&self) -> Result<SteinerInstance, ConversionError> {
+        let mut converted = self.clone();
+        if self.index_base == IndexBase::One {
+            if converted.node_references().any(|node| node == 0) {
+                return Err(ConversionError::NodeZero);
+            }
+            converted.shift_nodes(|node| node - 1);
+            converted.index_base = IndexBase::Zero;
+        }
+        Ok(converted)
+    }
+
+    // A copy with nodes numbered 1..=num_nodes, the inverse of to_zero_indexed
+    pub fn to_one_indexed(&self) -> SteinerInstance {
+        let mut converted = self.clone();
+        if self.index_base == IndexBase::Zero {
+            converted.shift_nodes(|node| node + 1);
+            converted.index_base = IndexBase::One;
+        }
+        converted
+    }
+
+    // Every node id the instance refers to, with repetitions
+    fn node_references(&self) -> impl Iterator<Item = usize> + '_ {
+        let presolve = self
+            .presolve
+            .iter()
+            .flat_map(|presolve| presolve.ea_edges.iter().chain(&presolve.ec_edges));
+        self.edges
+            .iter()
+            .chain(&self.arcs)
+            .chain(presolve)
+            .flat_map(|edge| [edge.from, edge.to])
+            .chain(self.terminals.iter().copied())
+            .chain(self.terminal_prizes.iter().map(|&(node, _)| node))
+            .chain(self.groups.iter().flatten().copied())
+            .chain(self.root)
+            .chain(self.coordinates.keys().copied())
+    }
+
+    fn shift_nodes(&mut self, shift: impl Fn(usize) -> usize) {
+        let shift_edge = |edge: &mut Edge| {
+            edge.from = shift(edge.from);
+            edge.to = shift(edge.to);
+        };
+        self.edges.iter_mut().for_each(shift_edge);
+        self.arcs.iter_mut().for_each(shift_edge);
+        if let Some(presolve) = &mut self.presolve {
+            presolve.ea_edges.iter_mut().for_each(shift_edge);
+            presolve.ec_edges.iter_mut().for_each(shift_edge);
+        }
+        for node in self
+            .terminals
+            .iter_mut()
+            .chain(self.terminal_prizes.iter_mut().map(|(node, _)| node))
+            .chain(self.groups.iter_mut().flatten())
+            .chain(&mut self.root)
+        {
+            *node = shift(*node);
+        }
+        self.coordinates = std::mem::take(&mut self.coordinates)
+            .into_iter()
+            .map(|(node, coordinate)| (shift(node), coordinate))
+            .collect();
+    }
+}
@@ -0,0 +1,227 @@
+// JSON import and export for web tooling.
+// The schema is fixed independently of the internal data structures, so it
+// stays stable when SteinerInstance changes:
+//
+//     {
+//       "nodes": 3,
+//       "edges": [{"from": 1, "to": 2, "cost": 1.0}],
+//       "arcs": [{"from": 2, "to": 3, "cost": 2.0}],
+//       "terminals": [1, 3],
+//       "coordinates": [{"node": 1, "x": 0.0, "y": 1.0}],
+//       "metadata": {"name": "...", "creator": "...", "date": "...",
+//                    "problem": "...", "remarks": ["..."]}
+//     }
+//
+// `arcs`, `coordinates` and `metadata` are optional and omitted when empty,
+// as are missing metadata fields. Coordinates carry a `z` for spatial nodes.
+// Nodes are 1-based as in STP. Other sections are not part of the schema.
+
+use crate::{Coordinate, Edge, IndexBase, Metadata, ParseError, ParseErrorKind, SteinerInstance};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonInstance {
+    nodes: usize,
+    edges: Vec<JsonEdge>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    arcs: Vec<JsonEdge>,
+    terminals: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    coordinates: Vec<JsonCoordinate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<JsonMetadata>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonEdge {
+    from: usize,
+    to: usize,
+    cost: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonCoordinate {
+    node: usize,
+    x: f64,
+    y: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    z: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    creator: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    problem: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    remarks: Vec<String>,
+}
+
+impl From<&Edge> for JsonEdge {
+    fn from(edge: &Edge) -> Self {
+        Self {
+            from: edge.from,
+            to: edge.to,
+            cost: edge.cost,
+        }
+    }
+}
+
+impl From<JsonEdge> for Edge {
+    fn from(edge: JsonEdge) -> Self {
+        Self {
+            from: edge.from,
+            to: edge.to,
+            cost: edge.cost,
+        }
+    }
+}
+
+/*
+ * Error for a problem at the given place of the JSON document, e.g.
+ * `edges[2]`. JSON validation is not tied to a line, so the place takes the
+ * role of the offending line.
+ */
+fn error(place: String, kind: ParseErrorKind) -> ParseError {
+    ParseError {
+        line_number: 0,
+        line: place,
+        kind,
+    }
+}
+
+fn check_node(
+    node: usize,
+    num_nodes: usize,
+    place: impl FnOnce() -> String,
+) -> Result<(), ParseError> {
+    if node == 0 || node > num_nodes {
+        return Err(error(
+            place(),
+            ParseErrorKind::NodeOutOfRange { node, num_nodes },
+        ));
+    }
+    Ok(())
+}
+
+/*
+ * Check the invariants the strict STP parser enforces: every node lies in
+ * 1..=nodes and costs are finite and non-negative.
+ */
+fn validate(json: &JsonInstance) -> Result<(), ParseError> {
+    let num_nodes = json.nodes;
+    for (key, edges) in [("edges", &json.edges), ("arcs", &json.arcs)] {
+        for (index, edge) in edges.iter().enumerate() {
+            let place = || format!("{}[{}]", key, index);
+            check_node(edge.from, num_nodes, place)?;
+            check_node(edge.to, num_nodes, place)?;
+            if !edge.cost.is_finite() || edge.cost < 0.0 {
+                return Err(error(place(), ParseErrorKind::InvalidCost(edge.cost)));
+            }
+        }
+    }
+    for (index, &terminal) in json.terminals.iter().enumerate() {
+        check_node(terminal, num_nodes, || format!("terminals[{}]", index))?;
+    }
+    for (index, coordinate) in json.coordinates.iter().enumerate() {
+        check_node(coordinate.node, num_nodes, || {
+            format!("coordinates[{}]", index)
+        })?;
+    }
+    Ok(())
+}
+
+impl SteinerInstance {
+    // Export the instance in the JSON schema described at the top of this file
+    pub fn to_json(&self) -> String {
+        if self.index_base == IndexBase::Zero {
+            return self.to_one_indexed().to_json();
+        }
+        let metadata = &self.metadata;
+        let json = JsonInstance {
+            nodes: self.num_nodes,
+            edges: self.edges.iter().map(JsonEdge::from).collect(),
+            arcs: self.arcs.iter().map(JsonEdge::from).collect(),
+            terminals: self.terminals.clone(),
+            coordinates: self
+                .coordinates
+                .iter()
+                .map(|(&node, coordinate)| match *coordinate {
+                    Coordinate::Planar { x, y } => JsonCoordinate {
+                        node,
+                        x,
+                        y,
+                        z: None,
+                    },
+                    Coordinate::Spatial { x, y, z } => JsonCoordinate {
+                        node,
+                        x,
+                        y,
+                        z: Some(z),
+                    },
+                })
+                .collect(),
+            metadata: (*metadata != Metadata::default()).then(|| JsonMetadata {
+                name: metadata.name.clone(),
+                creator: metadata.creator.clone(),
+                date: metadata.date.clone(),
+                problem: metadata.problem.clone(),
+                remarks: metadata.remarks.clone(),
+            }),
+        };
+        serde_json::to_string(&json).expect("the JSON schema only contains serializable values")
+    }
+
+    /*
+     * Read an instance in the JSON schema described at the top of this file.
+     * Malformed documents are reported as InvalidJson with the line of the
+     * problem, violated invariants with the place in the document.
+     */
+    pub fn from_json(json: &str) -> Result<SteinerInstance, ParseError> {
+        let parsed: JsonInstance = serde_json::from_str(json).map_err(|err| ParseError {
+            line_number: err.line(),
+            line: String::new(),
+            kind: ParseErrorKind::InvalidJson(err.to_string()),
+        })?;
+        validate(&parsed)?;
+
+        let mut instance = SteinerInstance::new(
+            parsed.nodes,
+            parsed.edges.into_iter().map(Edge::from).collect(),
+            parsed.terminals,
+        );
+        instance.num_arcs = parsed.arcs.len();
+        instance.arcs = parsed.arcs.into_iter().map(Edge::from).collect();
+        instance.coordinates = parsed
+            .coordinates
+            .into_iter()
+            .map(|coordinate| {
+                let JsonCoordinate { node, x, y, z } = coordinate;
+                let position = match z {
+                    Some(z) => Coordinate::Spatial { x, y, z },
+                    None => Coordinate::Planar { x, y },
+                };
+                (node, position)
+            })
+            .collect();
+        if let Some(metadata) = parsed.metadata {
+            instance.metadata = Metadata {
+                name: metadata.name,
+                creator: metadata.creator,
+                date: metadata.date,
+                problem: metadata.problem,
+                remarks: metadata.remarks,
+            };
+        }
+        Ok(instance)
+    }
+}
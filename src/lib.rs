@@ -1,11 +1,56 @@
+pub mod adjacency;
+pub mod bounds;
+pub mod builder;
+pub mod checksum;
+pub mod combine;
+pub mod components;
+pub mod conversion;
+pub mod csv;
+pub mod degree;
+pub mod directory;
+pub mod dot;
+pub mod events;
+pub mod exact;
 pub mod export;
 pub mod generate_random;
+pub mod gr;
+pub mod graphml;
+pub mod heuristic;
+pub mod indexing;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod metric_closure;
+pub mod orientation;
+pub mod planarity;
+pub mod reductions;
+pub mod relabel;
+pub mod scaling;
+pub mod shortest_paths;
+pub mod solution;
+pub mod solution_file;
+pub mod stats;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+pub mod treewidth;
+pub mod validate;
+pub mod verify;
 
+use events::{MAX_PREALLOCATED, PresolveEntry, StpEvent, StpEvents};
+use smallvec::SmallVec;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::BufRead;
+use std::path::Path;
 use std::str::FromStr;
 
 use std::hash::{Hash, Hasher};
 
+/*
+ * Edges compare and hash by their endpoints only, so a set of edges holds at
+ * most one copy of each edge regardless of its cost. Compare `cost`
+ * explicitly where it matters.
+ */
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Edge {
     pub from: usize,
     pub to: usize,
@@ -30,7 +75,118 @@ impl Hash for Edge {
     }
 }
 
-#[derive(Debug, Clone)]
+// Edges sort by their endpoints, consistent with their equality
+impl PartialOrd for Edge {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Edge {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.from, self.to).cmp(&(other.from, other.to))
+    }
+}
+
+impl Edge {
+    /*
+     * The same undirected edge with the smaller endpoint first. Sets of
+     * normalized edges find an edge regardless of the orientation it was
+     * given in. Arcs must not be normalized.
+     */
+    pub fn normalized(&self) -> Edge {
+        Edge {
+            from: self.from.min(self.to),
+            to: self.from.max(self.to),
+            cost: self.cost,
+        }
+    }
+}
+
+// Edges print as `from to cost`, the columns of the E and A lines
+impl std::fmt::Display for Edge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.from, self.to, self.cost)
+    }
+}
+
+/*
+ * Position of a node as given by the `DD` (planar) and `DDD` (spatial) lines
+ * of the Coordinates section.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Coordinate {
+    Planar { x: f64, y: f64 },
+    Spatial { x: f64, y: f64, z: f64 },
+}
+
+/*
+ * Axis-parallel rectangle from an `RR` line of the Obstacles section, given by
+ * two opposite corners. Degenerate rectangles with zero width or height are
+ * allowed.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rectangle {
+    pub x1: f64,
+    pub y1: f64,
+    pub x2: f64,
+    pub y2: f64,
+}
+
+/*
+ * Numbering of the nodes held in memory. Parsing always yields One, the
+ * numbering of the file formats. Zero is meant for handing an instance to
+ * 0-based code, see SteinerInstance::to_zero_indexed. Exporters convert back
+ * to One first. Views of the graph like adjacency, degrees, components,
+ * validation and the petgraph conversion follow the base, as do relabeling,
+ * compaction and the reductions. The solvers and shortest paths refuse Zero
+ * instances.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IndexBase {
+    Zero,
+    #[default]
+    One,
+}
+
+/*
+ * Information left by a presolver in the Presolve section. The bounds refer to
+ * the original instance and can be used to warm-start a solver.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Presolve {
+    // Cost of the edges already fixed into the solution
+    pub fixed: Option<f64>,
+    pub lower: Option<f64>,
+    pub upper: Option<f64>,
+    pub time: Option<f64>,
+    pub original_nodes: Option<usize>,
+    pub original_edges: Option<usize>,
+    // Edges given by `EA` and `EC` lines
+    pub ea_edges: Vec<Edge>,
+    pub ec_edges: Vec<Edge>,
+}
+
+/*
+ * Descriptive information from the Comment section.
+ * Values are stored without their surrounding quotes.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub creator: Option<String>,
+    pub date: Option<String>,
+    pub problem: Option<String>,
+    pub remarks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SteinerInstance {
     pub num_nodes: usize,
     pub num_edges: usize,
@@ -40,6 +196,78 @@ pub struct SteinerInstance {
     pub edges: Vec<Edge>,
     pub arcs: Vec<Edge>,
     pub terminals: Vec<usize>,
+    // Prizes of prize-collecting terminals given by `TP` lines. These
+    // terminals are also listed in `terminals`.
+    pub terminal_prizes: Vec<(usize, f64)>,
+    // Terminal groups of group Steiner instances given by `TG <node> <group>`
+    // lines, group g at index g - 1. Empty for all other variants.
+    pub groups: Vec<Vec<usize>>,
+    // Root of rooted and directed variants, given by `Root` or `RootP`
+    pub root: Option<usize>,
+    // Node coordinates keyed by node id, empty if the instance has none
+    pub coordinates: BTreeMap<usize, Coordinate>,
+    // Maximum degree of node v at index v - 1, empty if unconstrained
+    pub max_degrees: Vec<usize>,
+    // Weight of node v at index v - 1 for node-weighted variants
+    pub node_weights: Option<Vec<f64>>,
+    pub obstacles: Vec<Rectangle>,
+    pub presolve: Option<Presolve>,
+    pub metadata: Metadata,
+    // Version declared in the STP header, e.g. "1.0"
+    pub format_version: Option<String>,
+    // Nodes are numbered 1..=num_nodes, or 0..num_nodes with IndexBase::Zero
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub index_base: IndexBase,
+}
+
+// Every edge whose key already occurred earlier in the list
+fn surplus_copies(edges: &[Edge], key: impl Fn(&Edge) -> (usize, usize)) -> Vec<Edge> {
+    let mut seen = HashSet::new();
+    edges
+        .iter()
+        .filter(|edge| !seen.insert(key(edge)))
+        .cloned()
+        .collect()
+}
+
+// Merge edges with equal keys into the first copy with the minimum cost
+fn keep_cheapest(edges: &mut Vec<Edge>, key: impl Fn(&Edge) -> (usize, usize)) -> usize {
+    let original_len = edges.len();
+    let mut first_index: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut kept: Vec<Edge> = Vec::with_capacity(original_len);
+    for edge in edges.drain(..) {
+        match first_index.get(&key(&edge)) {
+            Some(&index) => kept[index].cost = kept[index].cost.min(edge.cost),
+            None => {
+                first_index.insert(key(&edge), kept.len());
+                kept.push(edge);
+            }
+        }
+    }
+    let removed = original_len - kept.len();
+    *edges = kept;
+    removed
+}
+
+/*
+ * A declared count, e.g. from an `Edges` line, that disagrees with the number
+ * of entries actually present.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountMismatch {
+    pub what: &'static str,
+    pub declared: usize,
+    pub found: usize,
+}
+
+impl std::fmt::Display for CountMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expected {} {}, found {}",
+            self.declared, self.what, self.found
+        )
+    }
 }
 
 impl SteinerInstance {
@@ -53,42 +281,276 @@ impl SteinerInstance {
             edges,
             arcs: Vec::default(),
             terminals,
+            terminal_prizes: Vec::new(),
+            groups: Vec::new(),
+            root: None,
+            coordinates: BTreeMap::new(),
+            max_degrees: Vec::new(),
+            node_weights: None,
+            obstacles: Vec::new(),
+            presolve: None,
+            metadata: Metadata::default(),
+            format_version: None,
+            index_base: IndexBase::One,
         }
     }
-}
 
-impl Default for SteinerInstance {
-    fn default() -> Self {
-        Self {
-            num_nodes: 0,
-            num_edges: 0,
-            num_arcs: 0,
-            num_obstacles: 0,
-            num_terminals: 0,
-            edges: Vec::new(),
-            arcs: Vec::new(),
-            terminals: Vec::new(),
+    /*
+     * Set the Comment section remark `<key>: <value>`, replacing the first
+     * remark that starts with `<key>: ` or appending a new one. This keeps
+     * key-value remarks like `vertex_cover: 3 7 12` unique.
+     */
+    pub fn set_comment_remark(&mut self, key: &str, value: &str) {
+        let prefix = format!("{}: ", key);
+        let remark = format!("{}{}", prefix, value);
+        let remarks = &mut self.metadata.remarks;
+        match remarks
+            .iter_mut()
+            .find(|existing| existing.starts_with(&prefix))
+        {
+            Some(existing) => *existing = remark,
+            None => remarks.push(remark),
+        }
+    }
+
+    /*
+     * Check whether any edge or arc carries a negative cost.
+     * Shortest-path based algorithms assume non-negative costs and refuse
+     * such instances, with PathError::InvalidCost for shortest paths and the
+     * metric closure and SolveError::InvalidCost for the solvers and bounds.
+     */
+    pub fn has_negative_costs(&self) -> bool {
+        self.edges
+            .iter()
+            .chain(self.arcs.iter())
+            .any(|edge| edge.cost < 0.0)
+    }
+
+    /*
+     * Remove all edges and arcs from a node to itself and return them.
+     * The declared counts are reduced accordingly.
+     */
+    pub fn remove_self_loops(&mut self) -> Vec<Edge> {
+        let (loops, edges): (Vec<Edge>, Vec<Edge>) =
+            self.edges.drain(..).partition(|edge| edge.from == edge.to);
+        let (arc_loops, arcs): (Vec<Edge>, Vec<Edge>) =
+            self.arcs.drain(..).partition(|arc| arc.from == arc.to);
+        self.edges = edges;
+        self.arcs = arcs;
+        self.num_edges = self.num_edges.saturating_sub(loops.len());
+        self.num_arcs = self.num_arcs.saturating_sub(arc_loops.len());
+        loops.into_iter().chain(arc_loops).collect()
+    }
+
+    /*
+     * Handle repeated edges and arcs according to the policy, see
+     * DuplicateEdgePolicy. Merged copies keep the position of the first one
+     * and the declared counts are reduced accordingly. Returns the number of
+     * removed copies, or all surplus copies if the policy forbids duplicates.
+     */
+    pub fn dedup_edges(&mut self, policy: DuplicateEdgePolicy) -> Result<usize, Vec<Edge>> {
+        let undirected = |edge: &Edge| (edge.from.min(edge.to), edge.from.max(edge.to));
+        let directed = |arc: &Edge| (arc.from, arc.to);
+        match policy {
+            DuplicateEdgePolicy::KeepAll => Ok(0),
+            DuplicateEdgePolicy::Error => {
+                let duplicates: Vec<Edge> = surplus_copies(&self.edges, undirected)
+                    .into_iter()
+                    .chain(surplus_copies(&self.arcs, directed))
+                    .collect();
+                if duplicates.is_empty() {
+                    Ok(0)
+                } else {
+                    Err(duplicates)
+                }
+            }
+            DuplicateEdgePolicy::KeepMinimum => {
+                let removed_edges = keep_cheapest(&mut self.edges, undirected);
+                let removed_arcs = keep_cheapest(&mut self.arcs, directed);
+                self.num_edges = self.num_edges.saturating_sub(removed_edges);
+                self.num_arcs = self.num_arcs.saturating_sub(removed_arcs);
+                Ok(removed_edges + removed_arcs)
+            }
+        }
+    }
+
+    /*
+     * Compare the declared counts against the parsed content, in both
+     * directions. Group Steiner instances without plain terminals declare the
+     * number of group members as their terminal count. Maximum degrees and
+     * node weights are only checked if present.
+     */
+    pub fn check_counts(&self) -> Vec<CountMismatch> {
+        let mut counts = vec![
+            ("edges", self.num_edges, self.edges.len()),
+            ("arcs", self.num_arcs, self.arcs.len()),
+            ("terminals", self.num_terminals, self.terminal_count()),
+            ("obstacles", self.num_obstacles, self.obstacles.len()),
+        ];
+        if !self.max_degrees.is_empty() {
+            counts.push(("maximum degrees", self.num_nodes, self.max_degrees.len()));
+        }
+        if let Some(weights) = &self.node_weights {
+            counts.push(("node weights", self.num_nodes, weights.len()));
+        }
+
+        counts
+            .into_iter()
+            .filter(|(_, declared, found)| declared != found)
+            .map(|(what, declared, found)| CountMismatch {
+                what,
+                declared,
+                found,
+            })
+            .collect()
+    }
+
+    /*
+     * Bring the instance into a canonical form: edges are oriented with
+     * `from <= to` and sorted by endpoints and cost, arcs are sorted the same
+     * way without changing their direction, terminals, prizes and group
+     * members are sorted and deduplicated, and all counts are recomputed.
+     * Instances that only differ in the order of their lines export to the
+     * same text afterwards.
+     */
+    pub fn normalize(&mut self) {
+        let by_key = |a: &Edge, b: &Edge| a.cmp(b).then(a.cost.total_cmp(&b.cost));
+        self.edges = self.edges.iter().map(Edge::normalized).collect();
+        self.edges.sort_by(by_key);
+        self.arcs.sort_by(by_key);
+
+        self.terminals.sort_unstable();
+        self.terminals.dedup();
+        self.terminal_prizes
+            .sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        self.terminal_prizes.dedup_by_key(|(node, _)| *node);
+        for group in &mut self.groups {
+            group.sort_unstable();
+            group.dedup();
+        }
+
+        self.recompute_counts();
+    }
+
+    // Set the count fields, except num_nodes, from the data
+    pub(crate) fn recompute_counts(&mut self) {
+        self.num_edges = self.edges.len();
+        self.num_arcs = self.arcs.len();
+        self.num_obstacles = self.obstacles.len();
+        self.num_terminals = self.terminal_count();
+    }
+
+    // Number of terminals as declared in a file, group members count for
+    // group Steiner instances without plain terminals
+    pub(crate) fn terminal_count(&self) -> usize {
+        if self.terminals.is_empty() {
+            self.groups.iter().map(Vec::len).sum()
+        } else {
+            self.terminals.len()
         }
     }
 }
 
-#[derive(PartialEq)]
-enum Section {
+// Undirected edges as sorted keys with their orientation normalized
+fn edge_keys(edges: &[Edge], directed: bool) -> Vec<(usize, usize, u64)> {
+    let mut keys: Vec<_> = edges
+        .iter()
+        .map(|edge| {
+            let (from, to) = if directed {
+                (edge.from, edge.to)
+            } else {
+                (edge.from.min(edge.to), edge.from.max(edge.to))
+            };
+            (from, to, checksum::canonical_cost_bits(edge.cost))
+        })
+        .collect();
+    keys.sort_unstable();
+    keys
+}
+
+fn sorted<T: Ord + Clone>(values: &[T]) -> Vec<T> {
+    let mut values = values.to_vec();
+    values.sort_unstable();
+    values
+}
+
+/*
+ * Semantic equality: edges and arcs are compared as multisets, with edges
+ * regardless of their orientation, and terminals as sets, so the order of
+ * the lines in a file doesn't matter. Costs are compared exactly, only 0.0
+ * and -0.0 are the same cost. The declared format version is ignored.
+ */
+impl PartialEq for SteinerInstance {
+    fn eq(&self, other: &Self) -> bool {
+        let terminal_set = |instance: &SteinerInstance| -> HashSet<usize> {
+            instance.terminals.iter().copied().collect()
+        };
+        let prizes = |instance: &SteinerInstance| {
+            let mut prizes: Vec<(usize, u64)> = instance
+                .terminal_prizes
+                .iter()
+                .map(|&(node, prize)| (node, checksum::canonical_cost_bits(prize)))
+                .collect();
+            prizes.sort_unstable();
+            prizes
+        };
+
+        self.num_nodes == other.num_nodes
+            && self.num_edges == other.num_edges
+            && self.num_arcs == other.num_arcs
+            && self.num_obstacles == other.num_obstacles
+            && self.num_terminals == other.num_terminals
+            && edge_keys(&self.edges, false) == edge_keys(&other.edges, false)
+            && edge_keys(&self.arcs, true) == edge_keys(&other.arcs, true)
+            && terminal_set(self) == terminal_set(other)
+            && prizes(self) == prizes(other)
+            && self.groups.len() == other.groups.len()
+            && self
+                .groups
+                .iter()
+                .zip(&other.groups)
+                .all(|(a, b)| sorted(a) == sorted(b))
+            && self.root == other.root
+            && self.coordinates == other.coordinates
+            && self.max_degrees == other.max_degrees
+            && self.node_weights == other.node_weights
+            && self.obstacles == other.obstacles
+            && self.presolve == other.presolve
+            && self.metadata == other.metadata
+            && self.index_base == other.index_base
+    }
+}
+
+// Part of the file the parser is currently in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    // Before the first SECTION line
     Start,
     Comment,
     Graph,
     Terminals,
+    MaximumDegrees,
     Coordinates,
+    Obstacles,
+    Presolve,
+    NodeWeights,
+    // Between an END line and the next SECTION line
+    Outside,
 }
 
-impl ToString for Section {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Section::Start => "Start".to_string(),
-            Section::Comment => "Comment".to_string(),
-            Section::Graph => "Graph".to_string(),
-            Section::Terminals => "Terminals".to_string(),
-            Section::Coordinates => "Coordinates".to_string(),
+            Section::Start => write!(f, "Start"),
+            Section::Comment => write!(f, "Comment"),
+            Section::Graph => write!(f, "Graph"),
+            Section::Terminals => write!(f, "Terminals"),
+            Section::MaximumDegrees => write!(f, "MaximumDegrees"),
+            Section::Coordinates => write!(f, "Coordinates"),
+            Section::Obstacles => write!(f, "Obstacles"),
+            Section::Presolve => write!(f, "Presolve"),
+            Section::NodeWeights => write!(f, "NodeWeights"),
+            Section::Outside => write!(f, "Outside"),
         }
     }
 }
@@ -97,182 +559,987 @@ impl FromStr for Section {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Start" => Ok(Section::Start),
-            "Comment" => Ok(Section::Comment),
-            "Graph" => Ok(Section::Graph),
-            "Terminals" => Ok(Section::Terminals),
-            "Coordinates" => Ok(Section::Coordinates),
+        // Section names are matched case-insensitively
+        match s.to_ascii_lowercase().as_str() {
+            "start" => Ok(Section::Start),
+            "comment" => Ok(Section::Comment),
+            "graph" => Ok(Section::Graph),
+            "terminals" => Ok(Section::Terminals),
+            "maximumdegrees" => Ok(Section::MaximumDegrees),
+            "coordinates" => Ok(Section::Coordinates),
+            "obstacles" => Ok(Section::Obstacles),
+            "presolve" => Ok(Section::Presolve),
+            "nodeweights" => Ok(Section::NodeWeights),
             _ => Err(()),
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnknownKeyword(String),
+    UnknownSection(String),
+    MissingArgument {
+        expected: &'static str,
+    },
+    InvalidArgument {
+        expected: &'static str,
+        found: String,
+    },
+    CountMismatch {
+        what: &'static str,
+        declared: usize,
+        found: usize,
+    },
+    NodeOutOfRange {
+        node: usize,
+        num_nodes: usize,
+    },
+    MixedTerminals,
+    SelfLoop {
+        node: usize,
+    },
+    DuplicateEdge {
+        from: usize,
+        to: usize,
+    },
+    InvalidCost(f64),
+    InvalidHeader,
+    UnsupportedVersion(String),
+    OutsideSection,
+    // Malformed JSON document, with serde's description of the problem
+    InvalidJson(String),
+    // Edge of a solution file that the instance doesn't have
+    UnknownEdge {
+        from: usize,
+        to: usize,
+    },
+    // VALUE line of a solution file that differs from the cost of its edges
+    ValueMismatch {
+        stated: f64,
+        computed: f64,
+    },
+}
+
+impl std::fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::UnknownKeyword(keyword) => write!(f, "unknown keyword '{}'", keyword),
+            ParseErrorKind::UnknownSection(section) => write!(f, "unknown section '{}'", section),
+            ParseErrorKind::MissingArgument { expected } => write!(f, "missing {}", expected),
+            ParseErrorKind::InvalidArgument { expected, found } => {
+                write!(f, "expected {}, got '{}'", expected, found)
+            }
+            ParseErrorKind::CountMismatch {
+                what,
+                declared,
+                found,
+            } => write!(f, "expected {} {}, found {}", declared, what, found),
+            ParseErrorKind::NodeOutOfRange { node, num_nodes } => {
+                write!(f, "node {} is outside of 1..={}", node, num_nodes)
+            }
+            ParseErrorKind::MixedTerminals => {
+                write!(f, "terminals are given by both T and TP lines")
+            }
+            ParseErrorKind::SelfLoop { node } => write!(f, "self-loop at node {}", node),
+            ParseErrorKind::DuplicateEdge { from, to } => {
+                write!(f, "duplicate edge between {} and {}", from, to)
+            }
+            ParseErrorKind::InvalidCost(cost) if cost.is_nan() => write!(f, "cost is NaN"),
+            ParseErrorKind::InvalidCost(cost) if cost.is_infinite() => {
+                write!(f, "cost is infinite")
+            }
+            ParseErrorKind::InvalidCost(cost) => write!(f, "negative cost {}", cost),
+            ParseErrorKind::InvalidHeader => write!(f, "expected STP header '{}'", STP_HEADER),
+            ParseErrorKind::UnsupportedVersion(version) => {
+                write!(f, "unsupported STP format version '{}'", version)
+            }
+            ParseErrorKind::OutsideSection => write!(f, "data line outside of any section"),
+            ParseErrorKind::InvalidJson(message) => write!(f, "invalid JSON: {}", message),
+            ParseErrorKind::UnknownEdge { from, to } => {
+                write!(f, "edge between {} and {} is not in the instance", from, to)
+            }
+            ParseErrorKind::ValueMismatch { stated, computed } => write!(
+                f,
+                "stated value {} differs from the cost {} of the edges",
+                stated, computed
+            ),
+        }
+    }
+}
+
+/*
+ * Error produced when a line of an STP file cannot be interpreted.
+ * Line numbers are 1-based and count every line of the input, including
+ * blank ones.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line_number: usize,
+    pub line: String,
+    pub kind: ParseErrorKind,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Problems found after the whole input was read are not tied to a line
+        if self.line.is_empty() {
+            return write!(f, "line {}: {}", self.line_number, self.kind);
+        }
+        write!(
+            f,
+            "line {}: {} in '{}'",
+            self.line_number, self.kind, self.line
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/*
+ * Problem that is tolerated in lenient mode but would have been an error in
+ * strict mode. Collected by the parser, see Parser::warnings.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line_number: usize,
+    pub line: String,
+    pub kind: ParseErrorKind,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}: {} in '{}'",
+            self.line_number, self.kind, self.line
+        )
+    }
+}
+
+/*
+ * Error of the file and reader based entry points, which can fail either
+ * while reading the input or while interpreting it.
+ */
+#[derive(Debug)]
+pub enum StpError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+
+impl std::fmt::Display for StpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StpError::Io(err) => write!(f, "failed to read STP input: {}", err),
+            StpError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for StpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StpError::Io(err) => Some(err),
+            StpError::Parse(err) => Some(err),
+        }
+    }
+}
+
+impl From<std::io::Error> for StpError {
+    fn from(err: std::io::Error) -> Self {
+        StpError::Io(err)
+    }
+}
+
+impl From<ParseError> for StpError {
+    fn from(err: ParseError) -> Self {
+        StpError::Parse(err)
+    }
+}
+
+/*
+ * Strip the surrounding double quotes of a comment value and resolve the
+ * escaped quotes and backslashes inside. Unquoted values are returned as-is.
+ */
+fn unquote(value: &str) -> String {
+    let inner = match value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        Some(inner) => inner,
+        None => return value.to_string(),
+    };
+
+    let mut result = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                result.push(escaped);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+// Magic number opening every STP file, followed by the format version
+pub const STP_MAGIC: &str = "33D32945";
+pub const STP_HEADER: &str = "33D32945 STP File, STP Format Version 1.0";
+const SUPPORTED_MAJOR_VERSION: &str = "1";
+
+#[derive(Debug, Clone, Default)]
+pub struct ParserOptions {
+    // Turn every tolerated problem into an error instead of a warning, e.g. a
+    // missing STP header, data lines outside of any section or edges without
+    // a cost
+    pub strict: bool,
+    // Cost of `E`/`A` lines without a cost column. Without it, such lines
+    // default to 1.0 with a warning in lenient mode and are refused in strict
+    // mode.
+    pub default_edge_cost: Option<f64>,
+    // Check that edge, arc and terminal node ids lie in 1..=Nodes. Follows
+    // `strict` unless set explicitly.
+    pub check_node_bounds: Option<bool>,
+    pub self_loops: SelfLoopPolicy,
+    pub duplicate_edges: DuplicateEdgePolicy,
+    // Accept negative edge costs without a warning, for variants that use them
+    pub allow_negative_costs: bool,
+}
+
+/*
+ * What to do with repeated edges between the same pair of nodes. Undirected
+ * edges are duplicates regardless of their orientation, arcs only if they
+ * point in the same direction.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateEdgePolicy {
+    #[default]
+    KeepAll,
+    // Only the cheapest copy can be part of an optimal Steiner tree
+    KeepMinimum,
+    Error,
+}
+
+/*
+ * What to do with edges and arcs from a node to itself, which never take
+ * part in a Steiner tree.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelfLoopPolicy {
+    Drop,
+    // Drop and warn in lenient mode, refuse in strict mode
+    #[default]
+    Warn,
+    Error,
+}
+
+/*
+ * A trimmed line together with its whitespace separated tokens. The tokens
+ * are split once per line and shared by all processing steps.
+ */
+struct Line<'a> {
+    text: &'a str,
+    tokens: SmallVec<[&'a str; 8]>,
+}
+
+impl<'a> Line<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            tokens: text.split_whitespace().collect(),
+        }
+    }
+
+    fn keyword(&self) -> &'a str {
+        self.tokens.first().copied().unwrap_or_default()
+    }
+
+    fn is_section(&self) -> bool {
+        self.keyword().eq_ignore_ascii_case("SECTION")
+    }
+}
+
 pub struct Parser {
     current_section: Section,
+    line_number: usize,
+    header_seen: bool,
+    finished: bool,
+    // What has been read so far, for the checks spanning several lines
+    num_nodes: usize,
+    plain_terminals: bool,
+    prize_terminals: bool,
+    dropped_edges: usize,
+    dropped_arcs: usize,
+    // Only filled for DuplicateEdgePolicy::Error
+    seen_edges: HashSet<(usize, usize)>,
+    seen_arcs: HashSet<(usize, usize)>,
+    warnings: Vec<Warning>,
+    options: ParserOptions,
 }
 
 impl Default for Parser {
     fn default() -> Self {
+        Self::new(ParserOptions::default())
+    }
+}
+
+impl Parser {
+    pub fn new(options: ParserOptions) -> Self {
         Self {
             current_section: Section::Start,
+            line_number: 0,
+            header_seen: false,
+            finished: false,
+            num_nodes: 0,
+            plain_terminals: false,
+            prize_terminals: false,
+            dropped_edges: 0,
+            dropped_arcs: 0,
+            seen_edges: HashSet::new(),
+            seen_arcs: HashSet::new(),
+            warnings: Vec::new(),
+            options,
         }
     }
-}
 
-// TODO: implement maximum degrees
-impl Parser {
-    pub fn parse_stp(&mut self, stp: &str) -> SteinerInstance {
+    fn reset(&mut self) {
+        self.current_section = Section::Start;
+        self.line_number = 0;
+        self.header_seen = false;
+        self.finished = false;
+        self.num_nodes = 0;
+        self.plain_terminals = false;
+        self.prize_terminals = false;
+        self.dropped_edges = 0;
+        self.dropped_arcs = 0;
+        self.seen_edges.clear();
+        self.seen_arcs.clear();
+        self.warnings.clear();
+    }
+
+    /*
+     * Warnings collected during the last parse in lenient mode.
+     */
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /*
+     * Parse a complete STP file. All state of a previous parse is reset first,
+     * so one parser can be reused for any number of files.
+     */
+    pub fn parse_stp(&mut self, stp: &str) -> Result<SteinerInstance, ParseError> {
         let mut parsed_result = SteinerInstance::default();
+        self.reset();
 
         for line in stp.lines() {
-            self.parse_stp_line(line.trim(), &mut parsed_result);
+            self.parse_stp_line(line, &mut parsed_result)?;
+            if self.finished {
+                break;
+            }
+        }
+
+        self.finish(&mut parsed_result)?;
+        Ok(parsed_result)
+    }
+
+    /*
+     * Checks that need the complete instance. Dropped self-loops are taken
+     * out of the declared counts and duplicate edges are merged if requested.
+     * In strict mode the declared counts have to match the content, the first
+     * mismatch is reported at the last line read.
+     */
+    fn finish(&self, instance: &mut SteinerInstance) -> Result<(), ParseError> {
+        instance.num_edges = instance.num_edges.saturating_sub(self.dropped_edges);
+        instance.num_arcs = instance.num_arcs.saturating_sub(self.dropped_arcs);
+        if self.options.duplicate_edges == DuplicateEdgePolicy::KeepMinimum {
+            let _ = instance.dedup_edges(DuplicateEdgePolicy::KeepMinimum);
+        }
+        if !self.options.strict {
+            return Ok(());
+        }
+        match instance.check_counts().into_iter().next() {
+            Some(CountMismatch {
+                what,
+                declared,
+                found,
+            }) => Err(self.error(
+                &Line::new(""),
+                ParseErrorKind::CountMismatch {
+                    what,
+                    declared,
+                    found,
+                },
+            )),
+            None => Ok(()),
         }
+    }
+
+    /*
+     * Parse an STP file from disk, see parse_stp_reader.
+     */
+    pub fn parse_stp_file(&mut self, path: impl AsRef<Path>) -> Result<SteinerInstance, StpError> {
+        let file = std::fs::File::open(path)?;
+        self.parse_stp_reader(std::io::BufReader::new(file))
+    }
 
-        return parsed_result;
+    /*
+     * Parse a gzip compressed STP file, as distributed in the SteinLib archive.
+     */
+    #[cfg(feature = "flate2")]
+    pub fn parse_stp_gz_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<SteinerInstance, StpError> {
+        let file = std::fs::File::open(path)?;
+        self.parse_stp_gz_reader(std::io::BufReader::new(file))
+    }
+
+    /*
+     * Decompress a gzip stream on the fly and parse it like parse_stp_reader.
+     * The input has to start with the gzip magic bytes, so plain text passed
+     * here by accident is refused instead of being decoded into garbage.
+     */
+    #[cfg(feature = "flate2")]
+    pub fn parse_stp_gz_reader<R: BufRead>(
+        &mut self,
+        mut reader: R,
+    ) -> Result<SteinerInstance, StpError> {
+        const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+        if !reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            return Err(StpError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "input is not gzip compressed",
+            )));
+        }
+        let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+        self.parse_stp_reader(std::io::BufReader::new(decoder))
+    }
+
+    /*
+     * Parse an STP instance from a buffered reader. Lines are read one at a
+     * time into a reused buffer, so the input never has to fit into memory
+     * as a whole. Reading stops at EOF.
+     */
+    pub fn parse_stp_reader<R: BufRead>(&mut self, reader: R) -> Result<SteinerInstance, StpError> {
+        let mut parsed_result = SteinerInstance::default();
+        for event in self.events(reader) {
+            parsed_result.apply_event(event?);
+        }
+        self.finish(&mut parsed_result)?;
+        Ok(parsed_result)
+    }
+
+    /*
+     * Stream the contents of an STP input as events instead of collecting
+     * them into a SteinerInstance. Memory use stays constant, which allows
+     * scanning huge instances for e.g. their terminals only.
+     */
+    pub fn events<R: BufRead>(&mut self, reader: R) -> StpEvents<'_, R> {
+        self.reset();
+        StpEvents::new(self, reader)
     }
 
     /*
      * Parse the current line and modify the resulting SteinerInstance in place.
+     * See line_event for the handling of the individual lines.
      */
-    pub fn parse_stp_line(&mut self, line: &str, current_result: &mut SteinerInstance) {
-        match self.current_section {
-            Section::Start => self.process_start_line(line, current_result),
-            Section::Comment => self.process_comment_line(line, current_result),
-            Section::Graph => self.process_graph_line(line, current_result),
-            Section::Terminals => self.process_terminals_line(line, current_result),
-            Section::Coordinates => self.process_coordinates_line(line, current_result),
+    pub fn parse_stp_line(
+        &mut self,
+        line: &str,
+        current_result: &mut SteinerInstance,
+    ) -> Result<(), ParseError> {
+        if let Some(event) = self.line_event(line)? {
+            current_result.apply_event(event);
         }
-        self.move_section(line);
+        Ok(())
     }
 
-    pub fn move_section(&mut self, line: &str) {
-        if !line.starts_with("SECTION") {
-            return;
+    /*
+     * Translate the current line into an event, if it carries any information.
+     * Blank lines and lines starting with '#' are skipped. An END line closes
+     * the current section and everything after EOF is ignored.
+     * Surrounding whitespace including a trailing '\r' is removed here, so raw
+     * lines from CRLF files can be passed in directly, as well as a UTF-8 BOM
+     * on the first line.
+     */
+    fn line_event(&mut self, text: &str) -> Result<Option<StpEvent>, ParseError> {
+        self.line_number += 1;
+        let mut text = text.trim();
+        if self.line_number == 1 {
+            text = text.trim_start_matches('\u{feff}').trim_start();
+        }
+        if self.finished || text.is_empty() || text.starts_with('#') {
+            return Ok(None);
+        }
+        let line = &Line::new(text);
+        if line.is_section() {
+            self.enter_section(line)?;
+            return Ok(Some(StpEvent::SectionStart(self.current_section)));
         }
 
-        let section_str = line.split(" ").nth(1);
-        if section_str.is_none() {
-            return;
+        let keyword = line.keyword();
+        if keyword.eq_ignore_ascii_case("EOF") {
+            self.finished = true;
+            return Ok(Some(StpEvent::Eof));
         }
 
-        match Section::from_str(section_str.unwrap()) {
-            Ok(section) => self.current_section = section,
-            Err(_) => return,
+        let event = match self.current_section {
+            Section::Start => self.process_start_line(line)?,
+            Section::Comment => self.process_comment_line(line)?,
+            Section::Graph => self.process_graph_line(line)?,
+            Section::Terminals => self.process_terminals_line(line)?,
+            Section::MaximumDegrees => self.process_maximum_degrees_line(line)?,
+            Section::Coordinates => self.process_coordinates_line(line)?,
+            Section::Obstacles => self.process_obstacles_line(line)?,
+            Section::Presolve => self.process_presolve_line(line)?,
+            Section::NodeWeights => self.process_node_weights_line(line)?,
+            Section::Outside => self.process_outside_line(line)?,
+        };
+
+        if keyword.eq_ignore_ascii_case("END") {
+            self.current_section = Section::Outside;
+            return Ok(Some(StpEvent::End));
         }
+        Ok(event)
     }
 
-    pub fn process_start_line(&mut self, line: &str, current_result: &mut SteinerInstance) {
-        // TODO: Do something with the information eventually. Skipped for now.
+    /*
+     * Lines between sections carry no information. They are skipped, unless
+     * the parser is strict.
+     */
+    fn process_outside_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        self.report(line, ParseErrorKind::OutsideSection)?;
+        Ok(None)
     }
 
-    pub fn process_comment_line(&mut self, line: &str, current_result: &mut SteinerInstance) {
-        // TODO: Do something with the information eventually. Skipped for now.
+    pub fn move_section(&mut self, line: &str) -> Result<(), ParseError> {
+        let line = Line::new(line.trim());
+        if !line.is_section() {
+            return Ok(());
+        }
+        self.enter_section(&line)
     }
 
-    fn nth_arg<T: FromStr>(&self, line: &str, n: usize) -> Option<T> {
-        let mut s = line.split(" ");
-        let target = s.nth(n);
-        if target.is_none() {
-            return None;
+    fn enter_section(&mut self, line: &Line) -> Result<(), ParseError> {
+        // Only reported once, at the first section
+        if !self.header_seen && self.current_section == Section::Start {
+            self.report(line, ParseErrorKind::InvalidHeader)?;
         }
-        let res = target.unwrap().parse::<T>();
-        match res {
-            Ok(parsed) => Some(parsed),
-            Err(_) => None,
+
+        let section_str = line.tokens.get(1).ok_or_else(|| {
+            self.error(
+                line,
+                ParseErrorKind::MissingArgument {
+                    expected: "section name",
+                },
+            )
+        })?;
+
+        match Section::from_str(section_str) {
+            Ok(section) => {
+                self.current_section = section;
+                Ok(())
+            }
+            Err(_) => Err(self.error(
+                line,
+                ParseErrorKind::UnknownSection(section_str.to_string()),
+            )),
         }
     }
 
-    fn parse_edge(&self, line: &str) -> Option<Edge> {
-        let from = match self.nth_arg(line, 1) {
-            Some(u) => u,
-            None => return None,
-        };
-        let to = match self.nth_arg(line, 2) {
-            Some(v) => v,
-            None => return None,
+    /*
+     * Recognize the `33D32945 STP File, STP Format Version 1.0` header and
+     * record the declared version. Other lines before the first section are
+     * ignored, unless the parser is strict.
+     */
+    fn process_start_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        let is_header = line.keyword().eq_ignore_ascii_case(STP_MAGIC);
+        if !is_header {
+            self.report(line, ParseErrorKind::InvalidHeader)?;
+            return Ok(None);
+        }
+
+        let version = line.tokens.last().copied().unwrap_or_default();
+        let major = version.split('.').next().unwrap_or_default();
+        if major != SUPPORTED_MAJOR_VERSION {
+            self.report(
+                line,
+                ParseErrorKind::UnsupportedVersion(version.to_string()),
+            )?;
+        }
+        self.header_seen = true;
+        Ok(Some(StpEvent::Header {
+            version: version.to_string(),
+        }))
+    }
+
+    /*
+     * The Comment section is free-form, so every line is passed on as a key
+     * with its unquoted value. Which keys are kept is decided when the event
+     * is applied.
+     */
+    fn process_comment_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        let keyword = line.keyword();
+        let value = unquote(line.text[keyword.len()..].trim());
+        Ok(Some(StpEvent::Comment {
+            key: keyword.to_string(),
+            value,
+        }))
+    }
+
+    fn error(&self, line: &Line, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line_number: self.line_number,
+            line: line.text.to_string(),
+            kind,
+        }
+    }
+
+    /*
+     * Fail on a problem in strict mode, otherwise record it as a warning and
+     * carry on.
+     */
+    fn report(&mut self, line: &Line, kind: ParseErrorKind) -> Result<(), ParseError> {
+        let error = self.error(line, kind);
+        if self.options.strict {
+            return Err(error);
+        }
+        self.warnings.push(Warning {
+            line_number: error.line_number,
+            line: error.line,
+            kind: error.kind,
+        });
+        Ok(())
+    }
+
+    /*
+     * Refuse node ids outside of 1..=num_nodes, if bounds checking is enabled.
+     * Relies on the `Nodes` line preceding the lines referring to nodes.
+     */
+    fn check_node_bounds(&self, line: &Line, nodes: &[usize]) -> Result<(), ParseError> {
+        if !self
+            .options
+            .check_node_bounds
+            .unwrap_or(self.options.strict)
+        {
+            return Ok(());
+        }
+        match nodes
+            .iter()
+            .find(|&&node| node == 0 || node > self.num_nodes)
+        {
+            Some(&node) => Err(self.error(
+                line,
+                ParseErrorKind::NodeOutOfRange {
+                    node,
+                    num_nodes: self.num_nodes,
+                },
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /*
+     * Apply the self-loop policy to an edge or arc from node to itself.
+     * Returns whether the loop should be kept.
+     */
+    fn keep_self_loop(&mut self, line: &Line, node: usize) -> Result<bool, ParseError> {
+        match self.options.self_loops {
+            SelfLoopPolicy::Drop => (),
+            SelfLoopPolicy::Warn => self.report(line, ParseErrorKind::SelfLoop { node })?,
+            SelfLoopPolicy::Error => {
+                return Err(self.error(line, ParseErrorKind::SelfLoop { node }));
+            }
+        }
+        Ok(false)
+    }
+
+    fn duplicate_edge(&self, line: &Line, edge: &Edge) -> ParseError {
+        self.error(
+            line,
+            ParseErrorKind::DuplicateEdge {
+                from: edge.from,
+                to: edge.to,
+            },
+        )
+    }
+
+    fn nth_arg<T: FromStr>(
+        &self,
+        line: &Line,
+        n: usize,
+        expected: &'static str,
+    ) -> Result<T, ParseError> {
+        let target = line
+            .tokens
+            .get(n)
+            .ok_or_else(|| self.error(line, ParseErrorKind::MissingArgument { expected }))?;
+        target.parse::<T>().map_err(|_| {
+            self.error(
+                line,
+                ParseErrorKind::InvalidArgument {
+                    expected,
+                    found: target.to_string(),
+                },
+            )
+        })
+    }
+
+    fn parse_edge(&mut self, line: &Line) -> Result<Edge, ParseError> {
+        let from = self.nth_arg(line, 1, "integer node id")?;
+        let to = self.nth_arg(line, 2, "integer node id")?;
+        // NOTE: Omitting the cost is not part of the official specification, but
+        // used by many in practice.
+        let cost = match (line.tokens.get(3), self.options.default_edge_cost) {
+            (Some(_), _) => self.nth_arg(line, 3, "numeric edge cost")?,
+            (None, Some(cost)) => cost,
+            (None, None) => {
+                self.report(
+                    line,
+                    ParseErrorKind::MissingArgument {
+                        expected: "numeric edge cost",
+                    },
+                )?;
+                1.0
+            }
         };
-        // NOTE: This is not part of the official specification, but used by many in
-        // practice.
-        let cost = match self.nth_arg(line, 3) {
-            Some(w) => w,
-            None => 1.0,
+        self.check_cost(line, cost)?;
+        Ok(Edge { from, to, cost })
+    }
+
+    /*
+     * Costs may be written as integers, decimals or in scientific notation,
+     * e.g. `1`, `1.5` or `1e3`. NaN, infinite and (unless allowed) negative
+     * costs are accepted syntactically but reported, since they break the
+     * assumptions of every solver.
+     */
+    fn check_cost(&mut self, line: &Line, cost: f64) -> Result<(), ParseError> {
+        if cost.is_nan() || cost.is_infinite() || (cost < 0.0 && !self.options.allow_negative_costs)
+        {
+            self.report(line, ParseErrorKind::InvalidCost(cost))?;
+        }
+        Ok(())
+    }
+
+    fn process_graph_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        let keyword = line.keyword();
+        let event = match keyword.to_ascii_lowercase().as_str() {
+            "obstacles" => StpEvent::ObstacleCount(self.nth_arg(line, 1, "obstacle count")?),
+            "nodes" => {
+                self.num_nodes = self.nth_arg(line, 1, "node count")?;
+                StpEvent::NodeCount(self.num_nodes)
+            }
+            "edges" => StpEvent::EdgeCount(self.nth_arg(line, 1, "edge count")?),
+            "arcs" => StpEvent::ArcCount(self.nth_arg(line, 1, "arc count")?),
+            "e" => {
+                let edge = self.parse_edge(line)?;
+                self.check_node_bounds(line, &[edge.from, edge.to])?;
+                if edge.from == edge.to && !self.keep_self_loop(line, edge.from)? {
+                    self.dropped_edges += 1;
+                    return Ok(None);
+                }
+                if self.options.duplicate_edges == DuplicateEdgePolicy::Error
+                    && !self
+                        .seen_edges
+                        .insert((edge.from.min(edge.to), edge.from.max(edge.to)))
+                {
+                    return Err(self.duplicate_edge(line, &edge));
+                }
+                StpEvent::Edge(edge)
+            }
+            "a" => {
+                let arc = self.parse_edge(line)?;
+                self.check_node_bounds(line, &[arc.from, arc.to])?;
+                if arc.from == arc.to && !self.keep_self_loop(line, arc.from)? {
+                    self.dropped_arcs += 1;
+                    return Ok(None);
+                }
+                if self.options.duplicate_edges == DuplicateEdgePolicy::Error
+                    && !self.seen_arcs.insert((arc.from, arc.to))
+                {
+                    return Err(self.duplicate_edge(line, &arc));
+                }
+                StpEvent::Arc(arc)
+            }
+            "end" | "eof" => return Ok(None),
+            _ => {
+                return Err(self.error(line, ParseErrorKind::UnknownKeyword(keyword.to_string())));
+            }
         };
-        Some(Edge { from, to, cost })
+        Ok(Some(event))
     }
 
-    pub fn process_graph_line(&mut self, line: &str, current_result: &mut SteinerInstance) {
-        let mut s = line.split(" ");
-        match s.nth(0) {
-            Some("Obstacles") => {
-                let num: Option<usize> = self.nth_arg(line, 1);
-                if num.is_none() {
-                    return;
+    // TODO: There are many more options in the specification, which are not all implemented
+    fn process_terminals_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        let keyword = line.keyword();
+        let event = match keyword.to_ascii_lowercase().as_str() {
+            "terminals" => StpEvent::TerminalCount(self.nth_arg(line, 1, "terminal count")?),
+            "t" => {
+                let terminal: usize = self.nth_arg(line, 1, "integer node id")?;
+                self.check_node_bounds(line, &[terminal])?;
+                if self.prize_terminals {
+                    return Err(self.error(line, ParseErrorKind::MixedTerminals));
+                }
+                self.plain_terminals = true;
+                if line.tokens.len() > 2 {
+                    let x = self.nth_arg(line, 2, "numeric x coordinate")?;
+                    let y = self.nth_arg(line, 3, "numeric y coordinate")?;
+                    StpEvent::PlacedTerminal {
+                        node: terminal,
+                        x,
+                        y,
+                    }
+                } else {
+                    StpEvent::Terminal(terminal)
                 }
-                current_result.num_obstacles = num.unwrap();
             }
-            Some("Nodes") => {
-                let num: Option<usize> = self.nth_arg(line, 1);
-                if num.is_none() {
-                    return;
+            "tp" => {
+                let node: usize = self.nth_arg(line, 1, "integer node id")?;
+                let prize: f64 = self.nth_arg(line, 2, "numeric prize")?;
+                self.check_node_bounds(line, &[node])?;
+                if self.plain_terminals {
+                    return Err(self.error(line, ParseErrorKind::MixedTerminals));
                 }
-                current_result.num_nodes = num.unwrap();
+                self.prize_terminals = true;
+                StpEvent::PrizeTerminal { node, prize }
             }
-            Some("Edges") => {
-                let num: Option<usize> = self.nth_arg(line, 1);
-                if num.is_none() {
-                    return;
+            "tg" => {
+                let node: usize = self.nth_arg(line, 1, "integer node id")?;
+                let group: usize = self.nth_arg(line, 2, "integer group id")?;
+                self.check_node_bounds(line, &[node])?;
+                // Group g is stored at index g - 1, so huge ids are refused
+                // instead of allocating all groups up to them
+                if group == 0 || group > MAX_PREALLOCATED {
+                    return Err(self.error(
+                        line,
+                        ParseErrorKind::InvalidArgument {
+                            expected: "integer group id",
+                            found: group.to_string(),
+                        },
+                    ));
                 }
-                current_result.num_edges = num.unwrap();
+                StpEvent::GroupTerminal { node, group }
             }
-            Some("Arcs") => {
-                let num: Option<usize> = self.nth_arg(line, 1);
-                if num.is_none() {
-                    return;
+            "root" | "rootp" => {
+                let root: usize = self.nth_arg(line, 1, "integer node id")?;
+                if root == 0 || root > self.num_nodes {
+                    return Err(self.error(
+                        line,
+                        ParseErrorKind::NodeOutOfRange {
+                            node: root,
+                            num_nodes: self.num_nodes,
+                        },
+                    ));
                 }
-                current_result.num_arcs = num.unwrap();
+                StpEvent::Root(root)
             }
-            Some("E") => {
-                let edge = match self.parse_edge(line) {
-                    Some(e) => e,
-                    None => return,
-                };
-                current_result.edges.push(edge);
+            "end" | "eof" => return Ok(None),
+            _ => {
+                return Err(self.error(line, ParseErrorKind::UnknownKeyword(keyword.to_string())));
+            }
+        };
+        Ok(Some(event))
+    }
+
+    /*
+     * `MD` lines give the maximum degree of the nodes in order. The number of
+     * entries is compared with the node count in strict mode, see finish.
+     */
+    fn process_maximum_degrees_line(
+        &mut self,
+        line: &Line,
+    ) -> Result<Option<StpEvent>, ParseError> {
+        let keyword = line.keyword();
+        match keyword.to_ascii_lowercase().as_str() {
+            "md" => {
+                let degree = self.nth_arg(line, 1, "maximum degree")?;
+                Ok(Some(StpEvent::MaxDegree(degree)))
             }
-            Some("A") => {
-                let arc = match self.parse_edge(line) {
-                    Some(a) => a,
-                    None => return,
-                };
-                current_result.arcs.push(arc);
+            "end" | "eof" => Ok(None),
+            _ => Err(self.error(line, ParseErrorKind::UnknownKeyword(keyword.to_string()))),
+        }
+    }
+
+    /*
+     * `NW` lines give the weights of the nodes in order, as used for the
+     * node-weighted variants. Terminals may have any weight, including zero.
+     * The number of weights is compared with the node count in strict mode,
+     * see finish.
+     */
+    fn process_node_weights_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        let keyword = line.keyword();
+        match keyword.to_ascii_lowercase().as_str() {
+            "nw" => {
+                let weight = self.nth_arg(line, 1, "numeric node weight")?;
+                Ok(Some(StpEvent::NodeWeight(weight)))
             }
-            Some(_) | None => return,
+            "end" | "eof" => Ok(None),
+            _ => Err(self.error(line, ParseErrorKind::UnknownKeyword(keyword.to_string()))),
         }
     }
 
-    // TODO: There are many more options in the specification, which are not all implemented
-    pub fn process_terminals_line(&mut self, line: &str, current_result: &mut SteinerInstance) {
-        let mut s = line.split(" ");
-        match s.nth(0) {
-            Some("Terminals") => {
-                let num: Option<usize> = self.nth_arg(line, 1);
-                if num.is_none() {
-                    return;
-                }
-                current_result.num_terminals = num.unwrap();
+    /*
+     * Coordinates are passed on even if the node id exceeds the declared
+     * number of nodes, and files may mix planar and spatial coordinates.
+     */
+    fn process_coordinates_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        let keyword = line.keyword();
+        let event = match keyword.to_ascii_lowercase().as_str() {
+            "dd" => {
+                let node = self.nth_arg(line, 1, "integer node id")?;
+                let x = self.nth_arg(line, 2, "numeric x coordinate")?;
+                let y = self.nth_arg(line, 3, "numeric y coordinate")?;
+                StpEvent::Coordinate(node, Coordinate::Planar { x, y })
+            }
+            "ddd" => {
+                let node = self.nth_arg(line, 1, "integer node id")?;
+                let x = self.nth_arg(line, 2, "numeric x coordinate")?;
+                let y = self.nth_arg(line, 3, "numeric y coordinate")?;
+                let z = self.nth_arg(line, 4, "numeric z coordinate")?;
+                StpEvent::Coordinate(node, Coordinate::Spatial { x, y, z })
             }
-            Some("T") => {
-                let terminal: usize = match self.nth_arg(line, 1) {
-                    Some(t) => t,
-                    None => return,
-                };
-                current_result.terminals.push(terminal);
+            "end" | "eof" => return Ok(None),
+            _ => {
+                return Err(self.error(line, ParseErrorKind::UnknownKeyword(keyword.to_string())));
             }
-            Some(_) | None => return,
+        };
+        Ok(Some(event))
+    }
+
+    fn process_obstacles_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        let keyword = line.keyword();
+        match keyword.to_ascii_lowercase().as_str() {
+            "rr" => {
+                let x1 = self.nth_arg(line, 1, "numeric x coordinate")?;
+                let y1 = self.nth_arg(line, 2, "numeric y coordinate")?;
+                let x2 = self.nth_arg(line, 3, "numeric x coordinate")?;
+                let y2 = self.nth_arg(line, 4, "numeric y coordinate")?;
+                Ok(Some(StpEvent::Obstacle(Rectangle { x1, y1, x2, y2 })))
+            }
+            "end" | "eof" => Ok(None),
+            _ => Err(self.error(line, ParseErrorKind::UnknownKeyword(keyword.to_string()))),
         }
     }
 
-    pub fn process_coordinates_line(&mut self, line: &str, current_result: &mut SteinerInstance) {
-        todo!()
+    fn process_presolve_line(&mut self, line: &Line) -> Result<Option<StpEvent>, ParseError> {
+        let keyword = line.keyword();
+        let entry = match keyword.to_ascii_lowercase().as_str() {
+            "fixed" => PresolveEntry::Fixed(self.nth_arg(line, 1, "numeric cost")?),
+            "lower" => PresolveEntry::Lower(self.nth_arg(line, 1, "numeric bound")?),
+            "upper" => PresolveEntry::Upper(self.nth_arg(line, 1, "numeric bound")?),
+            "time" => PresolveEntry::Time(self.nth_arg(line, 1, "time in seconds")?),
+            "orgnodes" => PresolveEntry::OriginalNodes(self.nth_arg(line, 1, "node count")?),
+            "orgedges" => PresolveEntry::OriginalEdges(self.nth_arg(line, 1, "edge count")?),
+            "ea" => PresolveEntry::EaEdge(self.parse_edge(line)?),
+            "ec" => PresolveEntry::EcEdge(self.parse_edge(line)?),
+            "end" | "eof" => return Ok(None),
+            _ => {
+                return Err(self.error(line, ParseErrorKind::UnknownKeyword(keyword.to_string())));
+            }
+        };
+        Ok(Some(StpEvent::Presolve(entry)))
     }
 }
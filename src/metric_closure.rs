@@ -0,0 +1,79 @@
+// Shortest-path distances between the terminals of an instance and the
+// metric closure built from them, e.g. for distance network heuristics and
+// lower bounds.
+
+use crate::shortest_paths::PathError;
+use crate::{Edge, IndexBase, SteinerInstance};
+
+impl SteinerInstance {
+    /*
+     * Distances between all pairs of terminals, with one Dijkstra run per
+     * terminal. Entry [i][j] is the distance from terminals[i] to
+     * terminals[j], f64::INFINITY if there is no path. With arcs the matrix
+     * need not be symmetric.
+     */
+    pub fn terminal_distance_matrix(&self) -> Result<Vec<Vec<f64>>, PathError> {
+        self.distance_matrix(&self.terminals)
+    }
+
+    // Distances between all pairs of `nodes`, see terminal_distance_matrix.
+    // Every node is checked before the first search, as it is looked up in
+    // the distances of the others.
+    pub(crate) fn distance_matrix(&self, nodes: &[usize]) -> Result<Vec<Vec<f64>>, PathError> {
+        if self.index_base == IndexBase::Zero {
+            return Err(PathError::ZeroIndexed);
+        }
+        if let Some(&source) = nodes
+            .iter()
+            .find(|&&node| !(1..=self.num_nodes).contains(&node))
+        {
+            return Err(PathError::SourceOutOfRange {
+                source,
+                num_nodes: self.num_nodes,
+            });
+        }
+        nodes
+            .iter()
+            .map(|&node| {
+                let distances = self.shortest_paths(node)?;
+                Ok(nodes
+                    .iter()
+                    .map(|&other| distances.get(other - 1).copied().unwrap_or(f64::INFINITY))
+                    .collect())
+            })
+            .collect()
+    }
+
+    /*
+     * The complete graph on the terminals with their distances as costs.
+     * Terminal terminals[i] becomes node i + 1 of the closure, the returned
+     * vector maps the closure nodes back, i.e. its entry i is the original id
+     * of node i + 1. Fails with PathError::Unreachable if two terminals are
+     * not connected. Meant for undirected instances, with arcs the distance
+     * from the terminal listed first is used.
+     */
+    pub fn metric_closure(&self) -> Result<(SteinerInstance, Vec<usize>), PathError> {
+        let distances = self.terminal_distance_matrix()?;
+        let k = self.terminals.len();
+
+        let mut edges = Vec::with_capacity(k * k.saturating_sub(1) / 2);
+        for (i, row) in distances.iter().enumerate() {
+            for (j, &cost) in row.iter().enumerate().skip(i + 1) {
+                if !cost.is_finite() {
+                    return Err(PathError::Unreachable {
+                        from: self.terminals[i],
+                        to: self.terminals[j],
+                    });
+                }
+                edges.push(Edge {
+                    from: i + 1,
+                    to: j + 1,
+                    cost,
+                });
+            }
+        }
+
+        let closure = SteinerInstance::new(k, edges, (1..=k).collect());
+        Ok((closure, self.terminals.clone()))
+    }
+}
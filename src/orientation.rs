@@ -0,0 +1,74 @@
+// Converting between undirected edges and arcs, for solvers that only accept
+// one of the two.
+
+use crate::{Edge, SteinerInstance};
+use std::collections::{HashMap, VecDeque};
+
+impl SteinerInstance {
+    /*
+     * The instance with every arc turned into an undirected edge. An arc and
+     * a reverse arc form a single edge with the smaller cost of the two, an
+     * edge that already connects the same nodes keeps the cheaper cost
+     * instead of getting a parallel edge. Everything else is unchanged.
+     */
+    pub fn symmetrized(&self) -> SteinerInstance {
+        // Edges made from arcs, and the edges still waiting for their
+        // reverse arc by direction
+        let mut from_arcs: Vec<Edge> = Vec::new();
+        let mut waiting: HashMap<(usize, usize), VecDeque<usize>> = HashMap::new();
+        for arc in &self.arcs {
+            match waiting
+                .get_mut(&(arc.to, arc.from))
+                .and_then(VecDeque::pop_front)
+            {
+                Some(index) => from_arcs[index].cost = from_arcs[index].cost.min(arc.cost),
+                None => {
+                    waiting
+                        .entry((arc.from, arc.to))
+                        .or_default()
+                        .push_back(from_arcs.len());
+                    from_arcs.push(arc.clone());
+                }
+            }
+        }
+
+        let key = |edge: &Edge| (edge.from.min(edge.to), edge.from.max(edge.to));
+        let mut symmetrized = self.clone();
+        let mut existing: HashMap<(usize, usize), usize> = HashMap::new();
+        for (index, edge) in self.edges.iter().enumerate() {
+            existing.entry(key(edge)).or_insert(index);
+        }
+        for edge in from_arcs {
+            match existing.get(&key(&edge)) {
+                Some(&index) => {
+                    let cost = &mut symmetrized.edges[index].cost;
+                    *cost = cost.min(edge.cost);
+                }
+                None => symmetrized.edges.push(edge),
+            }
+        }
+        symmetrized.arcs.clear();
+        symmetrized.recompute_counts();
+        symmetrized
+    }
+
+    /*
+     * The instance with every undirected edge replaced by two opposite arcs
+     * of the same cost, following the existing arcs. Everything else is
+     * unchanged.
+     */
+    pub fn bidirected(&self) -> SteinerInstance {
+        let mut bidirected = self.clone();
+        for edge in &self.edges {
+            bidirected.arcs.push(edge.clone());
+            bidirected.arcs.push(Edge {
+                from: edge.to,
+                to: edge.from,
+                cost: edge.cost,
+            });
+        }
+        bidirected.edges.clear();
+        bidirected.recompute_counts();
+        bidirected
+    }
+}
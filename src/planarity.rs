@@ -0,0 +1,390 @@
+// Planarity testing for Steiner instances.
+// The test follows the left-right planarity criterion of de Fraysseix and
+// Rosenstiehl in the formulation by Brandes ("The Left-Right Planarity Test").
+// Only the decision is computed, no embedding is constructed.
+
+use crate::SteinerInstance;
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, Default)]
+struct Interval {
+    low: Option<usize>,
+    high: Option<usize>,
+}
+
+impl Interval {
+    fn is_empty(&self) -> bool {
+        self.low.is_none() && self.high.is_none()
+    }
+
+    fn conflicting(&self, edge: usize, lowpt: &[usize]) -> bool {
+        match self.high {
+            Some(high) => lowpt[high] > lowpt[edge],
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct ConflictPair {
+    left: Interval,
+    right: Interval,
+}
+
+impl ConflictPair {
+    fn swap(&mut self) {
+        std::mem::swap(&mut self.left, &mut self.right);
+    }
+
+    fn lowest(&self, lowpt: &[usize]) -> usize {
+        match (self.left.low, self.right.low) {
+            (None, Some(r)) => lowpt[r],
+            (Some(l), None) => lowpt[l],
+            (Some(l), Some(r)) => lowpt[l].min(lowpt[r]),
+            (None, None) => usize::MAX,
+        }
+    }
+}
+
+struct LrState {
+    // Undirected adjacency, storing (neighbor, edge id)
+    adjacency: Vec<Vec<(usize, usize)>>,
+    // Endpoints of every edge after it has been oriented by the DFS
+    source: Vec<usize>,
+    target: Vec<usize>,
+    oriented: Vec<bool>,
+    height: Vec<Option<usize>>,
+    parent_edge: Vec<Option<usize>>,
+    lowpt: Vec<usize>,
+    lowpt2: Vec<usize>,
+    nesting_depth: Vec<usize>,
+    // Outgoing oriented edges sorted by nesting depth
+    ordered_adjacency: Vec<Vec<usize>>,
+    reference: Vec<Option<usize>>,
+    lowpt_edge: Vec<usize>,
+    stack_bottom: Vec<usize>,
+    stack: Vec<ConflictPair>,
+}
+
+impl LrState {
+    fn new(num_nodes: usize, edges: &[(usize, usize)]) -> Self {
+        let mut adjacency = vec![Vec::new(); num_nodes];
+        for (id, &(u, v)) in edges.iter().enumerate() {
+            adjacency[u].push((v, id));
+            adjacency[v].push((u, id));
+        }
+        let m = edges.len();
+        Self {
+            adjacency,
+            source: vec![0; m],
+            target: vec![0; m],
+            oriented: vec![false; m],
+            height: vec![None; num_nodes],
+            parent_edge: vec![None; num_nodes],
+            lowpt: vec![0; m],
+            lowpt2: vec![0; m],
+            nesting_depth: vec![0; m],
+            ordered_adjacency: vec![Vec::new(); num_nodes],
+            reference: vec![None; m],
+            lowpt_edge: vec![0; m],
+            stack_bottom: vec![0; m],
+            stack: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> bool {
+        let num_nodes = self.adjacency.len();
+        let mut roots = Vec::new();
+        for v in 0..num_nodes {
+            if self.height[v].is_none() {
+                self.height[v] = Some(0);
+                roots.push(v);
+                self.dfs_orientation(v);
+            }
+        }
+
+        for v in 0..num_nodes {
+            let mut outgoing: Vec<usize> = self.adjacency[v]
+                .iter()
+                .map(|&(_, id)| id)
+                .filter(|&id| self.source[id] == v)
+                .collect();
+            outgoing.sort_by_key(|&id| self.nesting_depth[id]);
+            self.ordered_adjacency[v] = outgoing;
+        }
+
+        roots.into_iter().all(|root| self.dfs_testing(root))
+    }
+
+    fn height_of(&self, v: usize) -> usize {
+        self.height[v].expect("height is assigned during orientation")
+    }
+
+    /*
+     * First DFS phase: orient all edges, compute the lowpoints of every edge
+     * and derive the nesting depth used to order the adjacency lists.
+     * Implemented iteratively so large instances don't overflow the stack.
+     */
+    fn dfs_orientation(&mut self, root: usize) {
+        let mut dfs_stack = vec![root];
+        let mut next_index = vec![0; self.adjacency.len()];
+        let mut descended = vec![false; self.source.len()];
+
+        while let Some(v) = dfs_stack.pop() {
+            let parent = self.parent_edge[v];
+            while next_index[v] < self.adjacency[v].len() {
+                let (w, vw) = self.adjacency[v][next_index[v]];
+                if !descended[vw] {
+                    if self.oriented[vw] {
+                        next_index[v] += 1;
+                        continue;
+                    }
+                    self.oriented[vw] = true;
+                    self.source[vw] = v;
+                    self.target[vw] = w;
+                    self.lowpt[vw] = self.height_of(v);
+                    self.lowpt2[vw] = self.height_of(v);
+                    match self.height[w] {
+                        None => {
+                            // Tree edge, revisit v once w is finished
+                            self.parent_edge[w] = Some(vw);
+                            self.height[w] = Some(self.height_of(v) + 1);
+                            descended[vw] = true;
+                            dfs_stack.push(v);
+                            dfs_stack.push(w);
+                            break;
+                        }
+                        Some(height_w) => {
+                            // Back edge
+                            self.lowpt[vw] = height_w;
+                        }
+                    }
+                }
+
+                self.nesting_depth[vw] = 2 * self.lowpt[vw];
+                if self.lowpt2[vw] < self.height_of(v) {
+                    // chordal edge
+                    self.nesting_depth[vw] += 1;
+                }
+
+                if let Some(e) = parent {
+                    if self.lowpt[vw] < self.lowpt[e] {
+                        self.lowpt2[e] = self.lowpt[e].min(self.lowpt2[vw]);
+                        self.lowpt[e] = self.lowpt[vw];
+                    } else if self.lowpt[vw] > self.lowpt[e] {
+                        self.lowpt2[e] = self.lowpt2[e].min(self.lowpt[vw]);
+                    } else {
+                        self.lowpt2[e] = self.lowpt2[e].min(self.lowpt2[vw]);
+                    }
+                }
+                next_index[v] += 1;
+            }
+        }
+    }
+
+    /*
+     * Second DFS phase: process the edges in nesting order and maintain the
+     * stack of conflict pairs. Returns false as soon as two return edges are
+     * forced onto the same side, which certifies non-planarity.
+     */
+    fn dfs_testing(&mut self, root: usize) -> bool {
+        let mut dfs_stack = vec![root];
+        let mut next_index = vec![0; self.adjacency.len()];
+        let mut descended = vec![false; self.source.len()];
+
+        while let Some(v) = dfs_stack.pop() {
+            let parent = self.parent_edge[v];
+            let mut finished = true;
+            while next_index[v] < self.ordered_adjacency[v].len() {
+                let ei = self.ordered_adjacency[v][next_index[v]];
+                let w = self.target[ei];
+                if !descended[ei] {
+                    self.stack_bottom[ei] = self.stack.len();
+                    if self.parent_edge[w] == Some(ei) {
+                        // Tree edge, come back to v once w is finished
+                        descended[ei] = true;
+                        finished = false;
+                        dfs_stack.push(v);
+                        dfs_stack.push(w);
+                        break;
+                    }
+                    // Back edge
+                    self.lowpt_edge[ei] = ei;
+                    self.stack.push(ConflictPair {
+                        left: Interval::default(),
+                        right: Interval {
+                            low: Some(ei),
+                            high: Some(ei),
+                        },
+                    });
+                }
+
+                // Integrate the new return edges
+                if self.lowpt[ei] < self.height_of(v) {
+                    let e = parent.expect("only non-root vertices have return edges");
+                    if next_index[v] == 0 {
+                        self.lowpt_edge[e] = self.lowpt_edge[ei];
+                    } else if !self.add_constraints(ei, e) {
+                        return false;
+                    }
+                }
+                next_index[v] += 1;
+            }
+
+            if finished && let Some(e) = parent {
+                self.remove_back_edges(e);
+            }
+        }
+        true
+    }
+
+    fn add_constraints(&mut self, ei: usize, e: usize) -> bool {
+        let mut pair = ConflictPair::default();
+
+        // Merge the return edges of ei into the right interval
+        while let Some(mut q) = self.stack.pop() {
+            if !q.left.is_empty() {
+                q.swap();
+            }
+            if !q.left.is_empty() {
+                return false;
+            }
+            let q_low = q.right.low.expect("non-empty interval has a low edge");
+            if self.lowpt[q_low] > self.lowpt[e] {
+                if pair.right.is_empty() {
+                    pair.right = q.right;
+                } else if let Some(low) = pair.right.low {
+                    self.reference[low] = q.right.high;
+                }
+                pair.right.low = q.right.low;
+            } else {
+                self.reference[q_low] = Some(self.lowpt_edge[e]);
+            }
+            if self.stack.len() == self.stack_bottom[ei] {
+                break;
+            }
+        }
+
+        // Merge the conflicting return edges of earlier siblings into the left interval
+        while let Some(top) = self.stack.last() {
+            if !top.left.conflicting(ei, &self.lowpt) && !top.right.conflicting(ei, &self.lowpt) {
+                break;
+            }
+            let mut q = self.stack.pop().expect("stack is not empty");
+            if q.right.conflicting(ei, &self.lowpt) {
+                q.swap();
+            }
+            if q.right.conflicting(ei, &self.lowpt) {
+                return false;
+            }
+            if let Some(low) = pair.right.low {
+                self.reference[low] = q.right.high;
+            }
+            if q.right.low.is_some() {
+                pair.right.low = q.right.low;
+            }
+
+            if pair.left.is_empty() {
+                pair.left = q.left;
+            } else if let Some(low) = pair.left.low {
+                self.reference[low] = q.left.high;
+            }
+            pair.left.low = q.left.low;
+        }
+
+        if !(pair.left.is_empty() && pair.right.is_empty()) {
+            self.stack.push(pair);
+        }
+        true
+    }
+
+    fn remove_back_edges(&mut self, e: usize) {
+        let u = self.source[e];
+        let height_u = self.height_of(u);
+
+        // Drop entire conflict pairs returning to u
+        while let Some(top) = self.stack.last() {
+            if top.lowest(&self.lowpt) != height_u {
+                break;
+            }
+            self.stack.pop();
+        }
+
+        if let Some(mut pair) = self.stack.pop() {
+            // Trim the left interval
+            while let Some(high) = pair.left.high {
+                if self.target[high] != u {
+                    break;
+                }
+                pair.left.high = self.reference[high];
+            }
+            if pair.left.high.is_none()
+                && let Some(low) = pair.left.low
+            {
+                self.reference[low] = pair.right.low;
+                pair.left.low = None;
+            }
+            // Trim the right interval
+            while let Some(high) = pair.right.high {
+                if self.target[high] != u {
+                    break;
+                }
+                pair.right.high = self.reference[high];
+            }
+            if pair.right.high.is_none()
+                && let Some(low) = pair.right.low
+            {
+                self.reference[low] = pair.left.low;
+                pair.right.low = None;
+            }
+            self.stack.push(pair);
+        }
+
+        // The side of e is the side of a highest return edge
+        if self.lowpt[e] < height_u
+            && let Some(top) = self.stack.last()
+        {
+            let hl = top.left.high;
+            let hr = top.right.high;
+            self.reference[e] = match (hl, hr) {
+                (Some(l), Some(r)) if self.lowpt[l] > self.lowpt[r] => hl,
+                (Some(_), None) => hl,
+                _ => hr,
+            };
+        }
+    }
+}
+
+impl SteinerInstance {
+    /*
+     * Check whether the underlying undirected graph of the instance is planar.
+     * Arcs are treated as undirected edges, self-loops and parallel edges are
+     * ignored since they never affect planarity.
+     *
+     * Uses the left-right planarity test which runs in O(n + m) after the
+     * edges have been deduplicated. Graphs with more than 3n - 6 edges are
+     * rejected immediately by Euler's formula.
+     */
+    pub fn is_planar(&self) -> bool {
+        let mut seen = HashSet::new();
+        let mut simple_edges = Vec::new();
+        let mut num_nodes = self.num_nodes;
+        for edge in self.edges.iter().chain(self.arcs.iter()) {
+            if edge.from == edge.to {
+                continue;
+            }
+            let key = (edge.from.min(edge.to), edge.from.max(edge.to));
+            if seen.insert(key) {
+                num_nodes = num_nodes.max(key.1);
+                simple_edges.push(key);
+            }
+        }
+
+        if num_nodes > 2 && simple_edges.len() > 3 * num_nodes - 6 {
+            return false;
+        }
+
+        // Node ids are 1-based, slot 0 stays an isolated vertex
+        LrState::new(num_nodes + 1, &simple_edges).run()
+    }
+}
@@ -0,0 +1,468 @@
+// Reductions that shrink an instance without changing the cost of its
+// optimal Steiner trees, as applied by solvers before the actual search.
+// Nodes that have to stay are the terminals, the root and all endpoints of
+// arcs, as the reductions only reason about undirected edges.
+
+use crate::heuristic::other_endpoint;
+use crate::shortest_paths::Entry;
+use crate::solution::SteinerTree;
+use crate::{Edge, IndexBase, SteinerInstance};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+#[derive(Debug, Clone, Default)]
+pub struct ReductionOptions {
+    // Renumber the remaining nodes to 1..=k afterwards, or 0..k if the
+    // instance is 0-based, dropping removed
+    // nodes and all other isolated nodes that aren't terminals. Otherwise
+    // node ids stay stable and removed nodes remain as isolated nodes.
+    pub compact: bool,
+    // Let reduce_long_edges also remove edges exactly as long as another
+    // path between their endpoints
+    pub drop_ties: bool,
+}
+
+/*
+ * A node of degree 2 bypassed by reduce_degree2. The replaced edges are the
+ * ones at the node when it was contracted, which may be shortcuts of earlier
+ * contractions themselves.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contraction {
+    pub node: usize,
+    pub replaced: [Edge; 2],
+    // None if a parallel edge was at least as cheap, or both edges led to
+    // the same neighbor
+    pub shortcut: Option<Edge>,
+}
+
+/*
+ * What a reduction removed from an instance. Node ids are the ones before
+ * compacting. Removed edges are edges of the instance before the reduction,
+ * shortcuts that were added and removed again are only in the contractions.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReductionLog {
+    pub removed_nodes: Vec<usize>,
+    pub removed_edges: Vec<Edge>,
+    pub contractions: Vec<Contraction>,
+    // Old id of node i + 1 at index i, if the instance was compacted. Old id
+    // of node i if it is 0-based, see SteinerInstance::compact.
+    pub relabeling: Option<Vec<usize>>,
+    pub index_base: IndexBase,
+}
+
+impl ReductionLog {
+    /*
+     * Translate a solution of the reduced instance into one of the instance
+     * before the reduction, undoing the compaction and replacing shortcuts
+     * by the edges they bypass. The cost stays the same. Logs of several
+     * reductions have to be applied in reverse order.
+     */
+    pub fn lift_solution(&self, tree: &SteinerTree) -> SteinerTree {
+        let old_id = |node: usize| match &self.relabeling {
+            Some(relabeling) => node
+                .checked_sub(match self.index_base {
+                    IndexBase::Zero => 0,
+                    IndexBase::One => 1,
+                })
+                .and_then(|i| relabeling.get(i))
+                .copied()
+                .unwrap_or(node),
+            None => node,
+        };
+        let key = |edge: &Edge| (edge.from.min(edge.to), edge.from.max(edge.to));
+
+        // A shortcut is the only edge between its endpoints while it exists,
+        // so an edge is the latest shortcut with its endpoints before the
+        // contraction that replaced it, if there is any
+        let mut by_key: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (index, contraction) in self.contractions.iter().enumerate() {
+            if let Some(shortcut) = &contraction.shortcut {
+                by_key.entry(key(shortcut)).or_default().push(index);
+            }
+        }
+
+        let mut edges = Vec::with_capacity(tree.edges.len());
+        let mut pending: Vec<(Edge, usize)> = tree
+            .edges
+            .iter()
+            .rev()
+            .map(|edge| {
+                let edge = Edge {
+                    from: old_id(edge.from),
+                    to: old_id(edge.to),
+                    cost: edge.cost,
+                };
+                (edge, self.contractions.len())
+            })
+            .collect();
+        while let Some((edge, before)) = pending.pop() {
+            let shortcut = by_key.get(&key(&edge)).and_then(|indices| {
+                let earlier = indices.partition_point(|&index| index < before);
+                earlier.checked_sub(1).map(|i| indices[i])
+            });
+            match shortcut {
+                Some(index) => {
+                    let [first, second] = &self.contractions[index].replaced;
+                    pending.push((second.clone(), index));
+                    pending.push((first.clone(), index));
+                }
+                None => edges.push(edge),
+            }
+        }
+        SteinerTree::new(edges)
+    }
+}
+
+impl SteinerInstance {
+    // Nodes no reduction may remove, indexed by node
+    fn protected_nodes(&self) -> Vec<bool> {
+        let mut protected = vec![false; self.num_nodes + 1];
+        let nodes = self
+            .terminals
+            .iter()
+            .chain(self.terminal_prizes.iter().map(|(node, _)| node))
+            .chain(self.groups.iter().flatten())
+            .chain(&self.root)
+            .chain(self.arcs.iter().flat_map(|arc| [&arc.from, &arc.to]));
+        for &node in nodes {
+            if let Some(protected) = protected.get_mut(node) {
+                *protected = true;
+            }
+        }
+        protected
+    }
+
+    // Compact if requested, dropping `removed` and isolated unprotected nodes
+    fn finish_reduction(&mut self, options: &ReductionOptions, log: &mut ReductionLog) {
+        log.index_base = self.index_base;
+        if !options.compact {
+            return;
+        }
+        let keep = self.used_nodes();
+        let removed: HashSet<usize> = log.removed_nodes.iter().copied().collect();
+        log.removed_nodes.extend(
+            self.node_ids()
+                .filter(|&node| !keep[node] && !removed.contains(&node)),
+        );
+        log.relabeling = Some(self.retain_nodes(&keep));
+    }
+
+    // Remove non-terminal leaves with the default options
+    pub fn reduce_degree1(&mut self) -> ReductionLog {
+        self.reduce_degree1_with(&ReductionOptions::default())
+    }
+
+    /*
+     * Repeatedly remove nodes of degree 1 that aren't terminals, together
+     * with their edge. No optimal Steiner tree uses them, and removing one
+     * leaf may turn its neighbor into the next. The edge count is reduced
+     * accordingly.
+     */
+    pub fn reduce_degree1_with(&mut self, options: &ReductionOptions) -> ReductionLog {
+        let protected = self.protected_nodes();
+        let removable = |node: usize| !protected.get(node).copied().unwrap_or(true);
+
+        let mut degree = vec![0; self.num_nodes + 1];
+        let mut incident = vec![Vec::new(); self.num_nodes + 1];
+        for (index, edge) in self.edges.iter().enumerate() {
+            for node in [edge.from, edge.to] {
+                if let Some(degree) = degree.get_mut(node) {
+                    *degree += 1;
+                    incident[node].push(index);
+                }
+            }
+        }
+
+        let mut log = ReductionLog::default();
+        let mut removed_edge = vec![false; self.edges.len()];
+        let mut leaves: Vec<usize> = self
+            .node_ids()
+            .filter(|&node| degree[node] == 1 && removable(node))
+            .collect();
+        while let Some(leaf) = leaves.pop() {
+            let Some(&index) = incident[leaf].iter().find(|&&i| !removed_edge[i]) else {
+                continue;
+            };
+            removed_edge[index] = true;
+            degree[leaf] -= 1;
+            log.removed_nodes.push(leaf);
+
+            let other = other_endpoint(&self.edges[index], leaf);
+            if let Some(degree) = degree.get_mut(other) {
+                *degree -= 1;
+                if *degree == 1 && removable(other) {
+                    leaves.push(other);
+                }
+            }
+        }
+
+        let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.edges)
+            .into_iter()
+            .zip(removed_edge)
+            .partition(|(_, removed)| *removed);
+        self.edges = kept.into_iter().map(|(edge, _)| edge).collect();
+        log.removed_edges = removed.into_iter().map(|(edge, _)| edge).collect();
+        self.num_edges = self.num_edges.saturating_sub(log.removed_edges.len());
+
+        self.finish_reduction(options, &mut log);
+        log
+    }
+
+    // Bypass non-terminals of degree 2 with the default options
+    pub fn reduce_degree2(&mut self) -> ReductionLog {
+        self.reduce_degree2_with(&ReductionOptions::default())
+    }
+
+    /*
+     * Repeatedly replace a node of degree 2 that isn't a terminal and its
+     * edges of cost c1 and c2 by a shortcut edge of cost c1 + c2 between its
+     * neighbors, so chains of such nodes collapse into a single edge. Of the
+     * shortcut and existing parallel edges only the cheapest is kept, an
+     * existing edge if there is a tie. Shortcuts are appended to the edges,
+     * see ReductionLog::lift_solution for mapping solutions back.
+     */
+    pub fn reduce_degree2_with(&mut self, options: &ReductionOptions) -> ReductionLog {
+        let protected = self.protected_nodes();
+        let removable = |node: usize| !protected.get(node).copied().unwrap_or(true);
+        let mut graph = EdgeSlots::new(self.num_nodes, std::mem::take(&mut self.edges));
+        let mut log = ReductionLog::default();
+
+        let mut candidates: Vec<usize> = self
+            .node_ids()
+            .filter(|&node| graph.degree[node] == 2 && removable(node))
+            .collect();
+        while let Some(node) = candidates.pop() {
+            // A self-loop is listed twice
+            let [first, second] = graph.live_slots(node)[..] else {
+                continue;
+            };
+            if first == second {
+                continue;
+            }
+
+            let first = graph.remove(first);
+            let second = graph.remove(second);
+            log.removed_nodes.push(node);
+            let (u, w) = (other_endpoint(&first, node), other_endpoint(&second, node));
+
+            let shortcut = Edge {
+                from: u,
+                to: w,
+                cost: first.cost + second.cost,
+            };
+            let parallel = graph.live_parallel(u, w);
+            let cheapest = parallel
+                .iter()
+                .map(|&slot| graph.edge(slot).cost)
+                .min_by(f64::total_cmp);
+            let shortcut =
+                (u != w && cheapest.is_none_or(|cost| shortcut.cost < cost)).then_some(shortcut);
+            if let Some(shortcut) = &shortcut {
+                for slot in parallel {
+                    graph.remove(slot);
+                }
+                graph.add(shortcut.clone());
+            }
+
+            log.contractions.push(Contraction {
+                node,
+                replaced: [first, second],
+                shortcut,
+            });
+            for neighbor in [u, w] {
+                if graph.degree.get(neighbor) == Some(&2) && removable(neighbor) {
+                    candidates.push(neighbor);
+                }
+            }
+        }
+
+        let added = graph.num_shortcuts();
+        log.removed_edges = std::mem::take(&mut graph.removed);
+        self.edges = graph.into_edges();
+        self.num_edges = (self.num_edges + added).saturating_sub(log.removed_edges.len());
+
+        self.finish_reduction(options, &mut log);
+        log
+    }
+}
+
+impl SteinerInstance {
+    // Remove edges longer than a path between their endpoints, keeping ties
+    pub fn reduce_long_edges(&mut self) -> ReductionLog {
+        self.reduce_long_edges_with(&ReductionOptions::default())
+    }
+
+    /*
+     * Remove every edge that is more expensive than another path between its
+     * endpoints, as an optimal Steiner tree would take the path instead.
+     * Edges are checked from the most expensive one down, each with a
+     * Dijkstra run from one endpoint that stops at the cost of the edge, so
+     * dense instances with short paths are fast to reduce. The number of
+     * removed edges is the length of removed_edges.
+     */
+    pub fn reduce_long_edges_with(&mut self, options: &ReductionOptions) -> ReductionLog {
+        let mut graph = EdgeSlots::new(self.num_nodes, std::mem::take(&mut self.edges));
+        let mut order: Vec<usize> = (0..graph.slots.len()).collect();
+        order.sort_by(|&a, &b| graph.edge(b).cost.total_cmp(&graph.edge(a).cost));
+        for slot in order {
+            if graph.has_bypass(slot, options.drop_ties) {
+                graph.remove(slot);
+            }
+        }
+
+        let mut log = ReductionLog {
+            removed_edges: std::mem::take(&mut graph.removed),
+            ..ReductionLog::default()
+        };
+        self.edges = graph.into_edges();
+        self.num_edges = self.num_edges.saturating_sub(log.removed_edges.len());
+
+        self.finish_reduction(options, &mut log);
+        log
+    }
+}
+
+/*
+ * Edges that can be removed and added while keeping track of the degrees,
+ * the edges at each node and the edges between each pair of nodes. Removed
+ * edges leave an empty slot behind.
+ */
+struct EdgeSlots {
+    // Edges with whether they belong to the original instance
+    slots: Vec<Option<(Edge, bool)>>,
+    degree: Vec<usize>,
+    incident: Vec<Vec<usize>>,
+    between: HashMap<(usize, usize), Vec<usize>>,
+    // Removed edges of the original instance
+    removed: Vec<Edge>,
+}
+
+impl EdgeSlots {
+    fn new(num_nodes: usize, edges: Vec<Edge>) -> Self {
+        let mut graph = EdgeSlots {
+            slots: Vec::with_capacity(edges.len()),
+            degree: vec![0; num_nodes + 1],
+            incident: vec![Vec::new(); num_nodes + 1],
+            between: HashMap::new(),
+            removed: Vec::new(),
+        };
+        for edge in edges {
+            graph.insert(edge, true);
+        }
+        graph
+    }
+
+    fn insert(&mut self, edge: Edge, original: bool) {
+        let slot = self.slots.len();
+        for node in [edge.from, edge.to] {
+            if let Some(degree) = self.degree.get_mut(node) {
+                *degree += 1;
+                self.incident[node].push(slot);
+            }
+        }
+        let key = (edge.from.min(edge.to), edge.from.max(edge.to));
+        self.between.entry(key).or_default().push(slot);
+        self.slots.push(Some((edge, original)));
+    }
+
+    fn add(&mut self, edge: Edge) {
+        self.insert(edge, false);
+    }
+
+    fn edge(&self, slot: usize) -> &Edge {
+        &self.slots[slot].as_ref().expect("slot holds an edge").0
+    }
+
+    fn remove(&mut self, slot: usize) -> Edge {
+        let (edge, original) = self.slots[slot].take().expect("edge is removed once");
+        for node in [edge.from, edge.to] {
+            if let Some(degree) = self.degree.get_mut(node) {
+                *degree -= 1;
+            }
+        }
+        if original {
+            self.removed.push(edge.clone());
+        }
+        edge
+    }
+
+    fn live_slots(&self, node: usize) -> Vec<usize> {
+        self.incident[node]
+            .iter()
+            .copied()
+            .filter(|&slot| self.slots[slot].is_some())
+            .collect()
+    }
+
+    fn live_parallel(&mut self, u: usize, w: usize) -> Vec<usize> {
+        let slots = &self.slots;
+        let parallel = self.between.entry((u.min(w), u.max(w))).or_default();
+        parallel.retain(|&slot| slots[slot].is_some());
+        parallel.clone()
+    }
+
+    /*
+     * Whether another path connects the endpoints of the edge in `slot` that
+     * is shorter than the edge, or as short if `ties` is set. Edges to nodes
+     * outside of the instance are never bypassed.
+     */
+    fn has_bypass(&self, slot: usize, ties: bool) -> bool {
+        let edge = self.edge(slot);
+        let (source, target, bound) = (edge.from, edge.to, edge.cost);
+        if source >= self.degree.len() || target >= self.degree.len() {
+            return false;
+        }
+        let within = |distance: f64| {
+            if ties {
+                distance <= bound
+            } else {
+                distance < bound
+            }
+        };
+
+        // Only nodes within the bound are visited, so a map is enough
+        let mut distances = HashMap::from([(source, 0.0)]);
+        let mut heap = BinaryHeap::from([Reverse(Entry(0.0, source))]);
+        while let Some(Reverse(Entry(distance, node))) = heap.pop() {
+            if node == target {
+                return within(distance);
+            }
+            if distance > distances[&node] {
+                continue;
+            }
+            for &other in &self.incident[node] {
+                let Some((edge, _)) = self.slots[other].as_ref().filter(|_| other != slot) else {
+                    continue;
+                };
+                let next = other_endpoint(edge, node);
+                let candidate = distance + edge.cost;
+                if next < self.degree.len()
+                    && within(candidate)
+                    && distances.get(&next).is_none_or(|&known| candidate < known)
+                {
+                    distances.insert(next, candidate);
+                    heap.push(Reverse(Entry(candidate, next)));
+                }
+            }
+        }
+        false
+    }
+
+    fn num_shortcuts(&self) -> usize {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|(_, original)| !original)
+            .count()
+    }
+
+    fn into_edges(self) -> Vec<Edge> {
+        self.slots
+            .into_iter()
+            .flatten()
+            .map(|(edge, _)| edge)
+            .collect()
+    }
+}
@@ -0,0 +1,266 @@
+// Renumbering the nodes of an instance, either compacting the ids after
+// nodes were deleted or applying an explicit permutation.
+
+use crate::{Edge, SteinerInstance};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use std::fmt;
+use std::ops::Range;
+
+/*
+ * Reason SteinerInstance::relabel rejected a mapping. The mapping has the new
+ * id of node v at index v - 1 and must be a permutation of 1..=num_nodes, or
+ * at index v and of 0..num_nodes for 0-based instances.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelabelError {
+    LengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+    OutOfRange {
+        node: usize,
+        new_id: usize,
+        nodes: Range<usize>,
+    },
+    // Both nodes would get the same new id
+    NotInjective {
+        first: usize,
+        second: usize,
+        new_id: usize,
+    },
+}
+
+impl fmt::Display for RelabelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelabelError::LengthMismatch { expected, found } => write!(
+                f,
+                "mapping has {} entries but the instance has {} nodes",
+                found, expected
+            ),
+            RelabelError::OutOfRange {
+                node,
+                new_id,
+                nodes,
+            } => write!(
+                f,
+                "node {} is mapped to {}, which is not in {}..{}",
+                node, new_id, nodes.start, nodes.end
+            ),
+            RelabelError::NotInjective {
+                first,
+                second,
+                new_id,
+            } => write!(
+                f,
+                "nodes {} and {} are both mapped to {}",
+                first, second, new_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RelabelError {}
+
+impl SteinerInstance {
+    /*
+     * Nodes referred to by an edge, arc, terminal, prize, group or the root,
+     * indexed by node. Coordinates and node data alone don't count.
+     */
+    pub(crate) fn used_nodes(&self) -> Vec<bool> {
+        let mut used = vec![false; self.num_nodes + 1];
+        let nodes = self
+            .terminals
+            .iter()
+            .chain(self.terminal_prizes.iter().map(|(node, _)| node))
+            .chain(self.groups.iter().flatten())
+            .chain(&self.root)
+            .chain(
+                self.edges
+                    .iter()
+                    .chain(&self.arcs)
+                    .flat_map(|edge| [&edge.from, &edge.to]),
+            );
+        for &node in nodes {
+            if let Some(used) = used.get_mut(node) {
+                *used = true;
+            }
+        }
+        used
+    }
+
+    /*
+     * Renumber the used nodes to 1..=k in their current order, dropping all
+     * others, e.g. after deleting nodes. Returns the old id of every new
+     * node, i.e. entry i is the old id of node i + 1, to translate solutions
+     * back. 0-based instances are renumbered to 0..k, entry i is then the
+     * old id of node i.
+     */
+    pub fn compact(&mut self) -> Vec<usize> {
+        let used = self.used_nodes();
+        self.retain_nodes(&used)
+    }
+
+    /*
+     * Give node v the new id mapping[v - 1], or mapping[v] in a 0-based
+     * instance. The mapping must be a permutation of the node ids, otherwise
+     * the instance is left unchanged. References to other nodes are kept as
+     * they are.
+     */
+    pub fn relabel(&mut self, mapping: &[usize]) -> Result<(), RelabelError> {
+        let nodes = self.node_ids();
+        if mapping.len() != nodes.len() {
+            return Err(RelabelError::LengthMismatch {
+                expected: nodes.len(),
+                found: mapping.len(),
+            });
+        }
+        // The old id of every new id, by position
+        let mut old_id = vec![None; nodes.len()];
+        for (node, &new_id) in nodes.clone().zip(mapping) {
+            if !nodes.contains(&new_id) {
+                return Err(RelabelError::OutOfRange {
+                    node,
+                    new_id,
+                    nodes,
+                });
+            }
+            if let Some(first) = old_id[new_id - nodes.start] {
+                return Err(RelabelError::NotInjective {
+                    first,
+                    second: node,
+                    new_id,
+                });
+            }
+            old_id[new_id - nodes.start] = Some(node);
+        }
+        let old_id: Vec<usize> = old_id.into_iter().flatten().collect();
+
+        let map = |node: usize| match node.checked_sub(nodes.start).and_then(|i| mapping.get(i)) {
+            Some(&new_id) => new_id,
+            None => node,
+        };
+        for edge in self.edges.iter_mut().chain(&mut self.arcs) {
+            edge.from = map(edge.from);
+            edge.to = map(edge.to);
+        }
+        for node in self
+            .terminals
+            .iter_mut()
+            .chain(self.terminal_prizes.iter_mut().map(|(node, _)| node))
+            .chain(self.groups.iter_mut().flatten())
+            .chain(&mut self.root)
+        {
+            *node = map(*node);
+        }
+        self.coordinates = std::mem::take(&mut self.coordinates)
+            .into_iter()
+            .map(|(node, coordinate)| (map(node), coordinate))
+            .collect();
+
+        permute(&mut self.max_degrees, &old_id, nodes.start);
+        if let Some(weights) = &mut self.node_weights {
+            permute(weights, &old_id, nodes.start);
+        }
+        Ok(())
+    }
+
+    /*
+     * A copy with the node ids shuffled by a uniformly random permutation,
+     * e.g. to anonymize generated instances. Returns the permutation as
+     * well, in the form relabel takes.
+     */
+    pub fn permuted(&self, rng: &mut impl Rng) -> (SteinerInstance, Vec<usize>) {
+        let mut permutation: Vec<usize> = self.node_ids().collect();
+        permutation.shuffle(rng);
+        let permuted = self
+            .permuted_by(&permutation)
+            .expect("a shuffled range is a permutation");
+        (permuted, permutation)
+    }
+
+    // A relabeled copy, see relabel for the form of `permutation`
+    pub fn permuted_by(&self, permutation: &[usize]) -> Result<SteinerInstance, RelabelError> {
+        let mut permuted = self.clone();
+        permuted.relabel(permutation)?;
+        Ok(permuted)
+    }
+
+    /*
+     * Keep the nodes v with keep[v] and renumber them in their current order,
+     * to 1..=k or 0..k depending on the base. Edges, arcs, terminals and all
+     * other data referring to dropped nodes are dropped as well. Returns the
+     * old id of every new node, see compact.
+     */
+    pub(crate) fn retain_nodes(&mut self, keep: &[bool]) -> Vec<usize> {
+        let first = self.first_node();
+        let mut new_id = vec![None; self.num_nodes + 1];
+        let mut old_id = Vec::new();
+        for node in self.node_ids() {
+            if keep.get(node).copied().unwrap_or(false) {
+                new_id[node] = Some(first + old_id.len());
+                old_id.push(node);
+            }
+        }
+        let map = |node: usize| new_id.get(node).copied().flatten();
+
+        let map_edges = |edges: &mut Vec<Edge>| {
+            let original_len = edges.len();
+            edges.retain_mut(|edge| match (map(edge.from), map(edge.to)) {
+                (Some(from), Some(to)) => {
+                    edge.from = from;
+                    edge.to = to;
+                    true
+                }
+                _ => false,
+            });
+            original_len - edges.len()
+        };
+        let removed_edges = map_edges(&mut self.edges);
+        let removed_arcs = map_edges(&mut self.arcs);
+        self.num_edges = self.num_edges.saturating_sub(removed_edges);
+        self.num_arcs = self.num_arcs.saturating_sub(removed_arcs);
+
+        self.terminals = self.terminals.iter().filter_map(|&t| map(t)).collect();
+        self.terminal_prizes = self
+            .terminal_prizes
+            .iter()
+            .filter_map(|&(node, prize)| Some((map(node)?, prize)))
+            .collect();
+        for group in &mut self.groups {
+            *group = group.iter().filter_map(|&node| map(node)).collect();
+        }
+        self.num_terminals = self.terminal_count();
+        self.root = self.root.and_then(map);
+        self.coordinates = std::mem::take(&mut self.coordinates)
+            .into_iter()
+            .filter_map(|(node, coordinate)| Some((map(node)?, coordinate)))
+            .collect();
+
+        // Indexed by v - 1, or by v if 0-based
+        if !self.max_degrees.is_empty() {
+            self.max_degrees = old_id
+                .iter()
+                .filter_map(|&node| self.max_degrees.get(node - first).copied())
+                .collect();
+        }
+        if let Some(weights) = &mut self.node_weights {
+            *weights = old_id
+                .iter()
+                .filter_map(|&node| weights.get(node - first).copied())
+                .collect();
+        }
+
+        self.num_nodes = old_id.len();
+        old_id
+    }
+}
+
+// Reorder values indexed by v - first so the node at position i gets the
+// value of old_id[i], if there is one value per node
+fn permute<T: Copy>(values: &mut Vec<T>, old_id: &[usize], first: usize) {
+    if values.len() == old_id.len() {
+        *values = old_id.iter().map(|&node| values[node - first]).collect();
+    }
+}
@@ -0,0 +1,134 @@
+// Scaling costs to integers for solvers that only accept integral costs.
+// The factor is the least common multiple of the smallest denominators of
+// all costs, each found from the continued fraction of the cost.
+
+use crate::SteinerInstance;
+use std::fmt;
+
+// Relative tolerance for a scaled cost to count as an integer, for the
+// rounding errors of decimal fractions like 0.1 in binary
+const INTEGRAL_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalingError {
+    // The cost needs a factor above max_denominator, alone or together with
+    // the costs before it
+    NotIntegral {
+        from: usize,
+        to: usize,
+        cost: f64,
+        max_denominator: u64,
+    },
+}
+
+impl fmt::Display for ScalingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalingError::NotIntegral {
+                from,
+                to,
+                cost,
+                max_denominator,
+            } => write!(
+                f,
+                "edge ({}, {}) has the cost {}, which no factor up to {} makes integral together with the other costs",
+                from, to, cost, max_denominator
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScalingError {}
+
+impl SteinerInstance {
+    /*
+     * Multiply all edge and arc costs by a factor of at most max_denominator
+     * that makes them integral and return the factor, so an objective value
+     * v of the scaled instance is v / factor in the original one. If there
+     * is no such factor the factor is max_denominator and the costs are
+     * rounded to the nearest integer afterwards, which changes every cost by
+     * at most 1 / (2 * factor) in original units. Costs that aren't finite
+     * are left as they are.
+     */
+    pub fn integerize_costs(&mut self, max_denominator: u64) -> f64 {
+        let factor = self
+            .integral_factor(max_denominator)
+            .unwrap_or(max_denominator.max(1));
+        self.scale_costs(factor as f64);
+        factor as f64
+    }
+
+    // Like integerize_costs, but fail instead of rounding
+    pub fn integerize_costs_exact(&mut self, max_denominator: u64) -> Result<f64, ScalingError> {
+        let factor = self.integral_factor(max_denominator)?;
+        self.scale_costs(factor as f64);
+        Ok(factor as f64)
+    }
+
+    // The smallest factor making all finite costs integral
+    fn integral_factor(&self, max_denominator: u64) -> Result<u64, ScalingError> {
+        let max_denominator = max_denominator.max(1);
+        let mut factor = 1;
+        for edge in self.edges.iter().chain(&self.arcs) {
+            if !edge.cost.is_finite() {
+                continue;
+            }
+            factor = denominator(edge.cost, max_denominator)
+                .map(|denominator| lcm(factor, denominator))
+                .filter(|&factor| factor <= max_denominator)
+                .ok_or(ScalingError::NotIntegral {
+                    from: edge.from,
+                    to: edge.to,
+                    cost: edge.cost,
+                    max_denominator,
+                })?;
+        }
+        Ok(factor)
+    }
+
+    fn scale_costs(&mut self, factor: f64) {
+        for edge in self.edges.iter_mut().chain(&mut self.arcs) {
+            if edge.cost.is_finite() {
+                edge.cost = (edge.cost * factor).round();
+            }
+        }
+    }
+}
+
+fn is_integral(value: f64) -> bool {
+    (value - value.round()).abs() <= INTEGRAL_TOLERANCE * value.abs().max(1.0)
+}
+
+/*
+ * The smallest q <= max_denominator with value * q integral, if any. The
+ * denominators of the convergents of the continued fraction of value are
+ * the only candidates.
+ */
+fn denominator(value: f64, max_denominator: u64) -> Option<u64> {
+    let (mut previous, mut current) = (0_u64, 1_u64);
+    let mut remainder = value.abs().fract();
+    loop {
+        if is_integral(value * current as f64) {
+            return Some(current);
+        }
+        if remainder == 0.0 {
+            return None;
+        }
+        let inverse = 1.0 / remainder;
+        let term = inverse.floor();
+        remainder = inverse - term;
+        let next = (term as u64).checked_mul(current)?.checked_add(previous)?;
+        if next > max_denominator {
+            return None;
+        }
+        (previous, current) = (current, next);
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    (a / x).saturating_mul(b)
+}
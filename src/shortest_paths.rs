@@ -0,0 +1,176 @@
+// Single-source shortest paths with Dijkstra's algorithm.
+// Edges can be used in both directions, arcs only from `from` to `to`.
+// Distances and predecessors of node v are stored at index v - 1, like
+// SteinerInstance::degrees.
+
+use crate::{IndexBase, SteinerInstance};
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathError {
+    SourceOutOfRange { source: usize, num_nodes: usize },
+    // Negative or NaN, Dijkstra can't handle either
+    InvalidCost { from: usize, to: usize, cost: f64 },
+    // Two nodes that had to be connected are not
+    Unreachable { from: usize, to: usize },
+    // IndexBase::Zero, see SteinerInstance::to_one_indexed
+    ZeroIndexed,
+}
+
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathError::SourceOutOfRange { source, num_nodes } => write!(
+                f,
+                "source {} is not a node, only {} nodes declared",
+                source, num_nodes
+            ),
+            PathError::InvalidCost { from, to, cost } => write!(
+                f,
+                "edge ({}, {}) has the cost {}, shortest paths need non-negative costs",
+                from, to, cost
+            ),
+            PathError::Unreachable { from, to } => {
+                write!(f, "there is no path from {} to {}", from, to)
+            }
+            PathError::ZeroIndexed => write!(
+                f,
+                "shortest paths need 1-based nodes, convert with to_one_indexed first"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/*
+ * Result of SteinerInstance::shortest_paths_with_predecessors. Nodes that
+ * can't be reached have the distance f64::INFINITY and no predecessor, as
+ * does the source.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShortestPaths {
+    pub source: usize,
+    pub distances: Vec<f64>,
+    pub predecessors: Vec<Option<usize>>,
+}
+
+impl ShortestPaths {
+    pub fn distance(&self, target: usize) -> f64 {
+        target
+            .checked_sub(1)
+            .and_then(|i| self.distances.get(i))
+            .copied()
+            .unwrap_or(f64::INFINITY)
+    }
+
+    // Nodes on a shortest path from the source to `target`, both included
+    pub fn path_to(&self, target: usize) -> Option<Vec<usize>> {
+        if !self.distance(target).is_finite() {
+            return None;
+        }
+        let mut path = vec![target];
+        let mut node = target;
+        while let Some(predecessor) = self.predecessors[node - 1] {
+            path.push(predecessor);
+            node = predecessor;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+// Heap entry ordered by distance, so that Reverse gives a min-heap
+#[derive(PartialEq)]
+pub(crate) struct Entry(pub(crate) f64, pub(crate) usize);
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+impl SteinerInstance {
+    // Distances from `source` to all nodes, see shortest_paths_with_predecessors
+    pub fn shortest_paths(&self, source: usize) -> Result<Vec<f64>, PathError> {
+        Ok(self.shortest_paths_with_predecessors(source)?.distances)
+    }
+
+    /*
+     * Dijkstra from `source` with a binary heap in O((n + m) log n). Edges
+     * and arcs to nodes outside of 1..=num_nodes are ignored, negative and
+     * NaN costs are refused before the search starts, as are 0-based
+     * instances.
+     */
+    pub fn shortest_paths_with_predecessors(
+        &self,
+        source: usize,
+    ) -> Result<ShortestPaths, PathError> {
+        if self.index_base == IndexBase::Zero {
+            return Err(PathError::ZeroIndexed);
+        }
+        let n = self.num_nodes;
+        if !(1..=n).contains(&source) {
+            return Err(PathError::SourceOutOfRange {
+                source,
+                num_nodes: n,
+            });
+        }
+
+        let in_range = |node: usize| (1..=n).contains(&node);
+        let mut neighbors = vec![Vec::new(); n + 1];
+        let edges = self.edges.iter().map(|edge| (edge, false));
+        let arcs = self.arcs.iter().map(|arc| (arc, true));
+        for (edge, directed) in edges.chain(arcs) {
+            if edge.cost.is_nan() || edge.cost < 0.0 {
+                return Err(PathError::InvalidCost {
+                    from: edge.from,
+                    to: edge.to,
+                    cost: edge.cost,
+                });
+            }
+            if in_range(edge.from) && in_range(edge.to) {
+                neighbors[edge.from].push((edge.to, edge.cost));
+                if !directed {
+                    neighbors[edge.to].push((edge.from, edge.cost));
+                }
+            }
+        }
+
+        let mut distances = vec![f64::INFINITY; n + 1];
+        let mut predecessors = vec![None; n + 1];
+        let mut heap = BinaryHeap::from([Reverse(Entry(0.0, source))]);
+        distances[source] = 0.0;
+        while let Some(Reverse(Entry(distance, node))) = heap.pop() {
+            // Outdated entry, the node was reached more cheaply already
+            if distance > distances[node] {
+                continue;
+            }
+            for &(neighbor, cost) in &neighbors[node] {
+                let candidate = distance + cost;
+                if candidate < distances[neighbor] {
+                    distances[neighbor] = candidate;
+                    predecessors[neighbor] = Some(node);
+                    heap.push(Reverse(Entry(candidate, neighbor)));
+                }
+            }
+        }
+
+        distances.remove(0);
+        predecessors.remove(0);
+        Ok(ShortestPaths {
+            source,
+            distances,
+            predecessors,
+        })
+    }
+}
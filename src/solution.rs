@@ -0,0 +1,208 @@
+// Steiner trees as returned by the solvers, and the errors they report.
+
+use crate::components::DisjointSets;
+use crate::{Edge, IndexBase, SteinerInstance};
+use std::collections::HashSet;
+use std::fmt;
+
+/*
+ * A solution of an instance: the selected edges, as stored in the instance,
+ * and the sum of their costs. Printing it gives a `VALUE <cost>` line
+ * followed by the endpoints of one edge per line.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SteinerTree {
+    pub edges: Vec<Edge>,
+    pub cost: f64,
+}
+
+impl SteinerTree {
+    // The cost is always recomputed from the edges
+    pub fn new(edges: Vec<Edge>) -> Self {
+        let cost = edges.iter().map(|edge| edge.cost).sum();
+        Self { edges, cost }
+    }
+
+    // Endpoints of the edges, sorted and without duplicates
+    pub fn nodes_spanned(&self) -> Vec<usize> {
+        let mut nodes: Vec<usize> = self
+            .edges
+            .iter()
+            .flat_map(|edge| [edge.from, edge.to])
+            .collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        nodes
+    }
+
+    // Whether the edges are connected and acyclic, true for no edges
+    pub fn is_tree(&self) -> bool {
+        let nodes = self.nodes_spanned();
+        if self.edges.len() + 1 != nodes.len().max(1) {
+            return false;
+        }
+        let mut components = DisjointSets::new(nodes.last().map_or(0, |&node| node + 1));
+        self.edges
+            .iter()
+            .all(|edge| components.union(edge.from, edge.to))
+    }
+
+    /*
+     * Whether every terminal of the instance is a node of the tree. Without
+     * edges that only holds if there is at most one terminal.
+     */
+    pub fn covers_terminals(&self, instance: &SteinerInstance) -> bool {
+        if self.edges.is_empty() {
+            return instance.terminals.len() <= 1;
+        }
+        let nodes = self.nodes_spanned();
+        instance
+            .terminals
+            .iter()
+            .all(|terminal| nodes.binary_search(terminal).is_ok())
+    }
+
+    /*
+     * How far the cost may be above the optimum, relative to the lower
+     * bound of SteinerInstance::lower_bound: (cost - bound) / bound. An
+     * optimal tree has a gap of 0 for two terminals, where the bound is
+     * exact. None if the bound fails or is 0.
+     */
+    pub fn quality_gap(&self, instance: &SteinerInstance) -> Option<f64> {
+        let bound = instance.lower_bound().ok()?;
+        (bound > 0.0).then(|| (self.cost - bound) / bound)
+    }
+}
+
+impl fmt::Display for SteinerTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "VALUE {}", self.cost)?;
+        for edge in &self.edges {
+            writeln!(f, "{} {}", edge.from, edge.to)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveError {
+    TerminalOutOfRange {
+        terminal: usize,
+        num_nodes: usize,
+    },
+    EdgeOutOfRange {
+        from: usize,
+        to: usize,
+        num_nodes: usize,
+    },
+    // Negative or NaN
+    InvalidCost {
+        from: usize,
+        to: usize,
+        cost: f64,
+    },
+    // The solvers handle undirected instances only
+    UnsupportedArcs,
+    DisconnectedTerminals {
+        from: usize,
+        to: usize,
+    },
+    // Refused by the exact solver, see ExactOptions
+    TooManyTerminals {
+        terminals: usize,
+        limit: usize,
+    },
+    // IndexBase::Zero, see SteinerInstance::to_one_indexed
+    ZeroIndexed,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::TerminalOutOfRange {
+                terminal,
+                num_nodes,
+            } => write!(
+                f,
+                "terminal {} is not a node, only {} nodes declared",
+                terminal, num_nodes
+            ),
+            SolveError::EdgeOutOfRange {
+                from,
+                to,
+                num_nodes,
+            } => write!(
+                f,
+                "edge ({}, {}) is not between nodes, only {} nodes declared",
+                from, to, num_nodes
+            ),
+            SolveError::InvalidCost { from, to, cost } => write!(
+                f,
+                "edge ({}, {}) has the cost {}, costs must be non-negative",
+                from, to, cost
+            ),
+            SolveError::UnsupportedArcs => write!(f, "instances with arcs can't be solved"),
+            SolveError::DisconnectedTerminals { from, to } => {
+                write!(f, "terminals {} and {} are not connected", from, to)
+            }
+            SolveError::TooManyTerminals { terminals, limit } => write!(
+                f,
+                "{} terminals are too many for the exact solver, the limit is {}",
+                terminals, limit
+            ),
+            SolveError::ZeroIndexed => write!(
+                f,
+                "the solvers need 1-based nodes, convert with to_one_indexed first"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+impl SteinerInstance {
+    /*
+     * The terminals without duplicates, after checking that the solvers can
+     * handle the instance: 1-based, no arcs, all nodes in 1..=num_nodes and
+     * all costs non-negative.
+     */
+    pub(crate) fn solvable_terminals(&self) -> Result<Vec<usize>, SolveError> {
+        if self.index_base == IndexBase::Zero {
+            return Err(SolveError::ZeroIndexed);
+        }
+        if !self.arcs.is_empty() {
+            return Err(SolveError::UnsupportedArcs);
+        }
+        let in_range = |node: usize| (1..=self.num_nodes).contains(&node);
+        for edge in &self.edges {
+            if !in_range(edge.from) || !in_range(edge.to) {
+                return Err(SolveError::EdgeOutOfRange {
+                    from: edge.from,
+                    to: edge.to,
+                    num_nodes: self.num_nodes,
+                });
+            }
+            if edge.cost.is_nan() || edge.cost < 0.0 {
+                return Err(SolveError::InvalidCost {
+                    from: edge.from,
+                    to: edge.to,
+                    cost: edge.cost,
+                });
+            }
+        }
+        if let Some(&terminal) = self.terminals.iter().find(|&&t| !in_range(t)) {
+            return Err(SolveError::TerminalOutOfRange {
+                terminal,
+                num_nodes: self.num_nodes,
+            });
+        }
+        let mut seen = HashSet::new();
+        Ok(self
+            .terminals
+            .iter()
+            .copied()
+            .filter(|&terminal| seen.insert(terminal))
+            .collect())
+    }
+}
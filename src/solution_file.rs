@@ -0,0 +1,176 @@
+// Reading and writing solution files as exchanged by SteinLib and PACE
+// solvers: a `VALUE <cost>` line followed by one `<from> <to>` line per edge.
+// Blank lines and comment lines starting with `c` or `#` are skipped. An
+// edge line may carry the cost of the edge as a third column.
+
+use crate::export::{FileOptions, write_file};
+use crate::solution::SteinerTree;
+use crate::{Edge, ParseError, ParseErrorKind, SteinerInstance, StpError};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+// Relative tolerance when comparing VALUE with the cost of the edges, for the
+// rounding errors of summing them up in a different order
+const VALUE_TOLERANCE: f64 = 1e-9;
+
+// The parsed VALUE line and edges, with the line numbers for error messages
+struct SolutionLines<'a> {
+    value: (usize, &'a str, f64),
+    edges: Vec<(usize, &'a str, Edge)>,
+}
+
+fn parse_lines(s: &str) -> Result<SolutionLines<'_>, ParseError> {
+    let mut value = None;
+    let mut edges = Vec::new();
+    for (index, line) in s.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |kind| ParseError {
+            line_number,
+            line: line.to_string(),
+            kind,
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let argument = |n: usize, expected: &'static str| {
+            tokens
+                .get(n)
+                .ok_or_else(|| error(ParseErrorKind::MissingArgument { expected }))
+        };
+        let invalid = |expected: &'static str, found: &str| {
+            error(ParseErrorKind::InvalidArgument {
+                expected,
+                found: found.to_string(),
+            })
+        };
+
+        match tokens.first() {
+            None => {}
+            Some(&"c") => {}
+            Some(first) if first.starts_with('#') => {}
+            Some(first) if first.eq_ignore_ascii_case("VALUE") => {
+                if value.is_some() {
+                    return Err(error(ParseErrorKind::CountMismatch {
+                        what: "VALUE lines",
+                        declared: 1,
+                        found: 2,
+                    }));
+                }
+                let found = argument(1, "solution value")?;
+                let cost = found
+                    .parse::<f64>()
+                    .map_err(|_| invalid("solution value", found))?;
+                value = Some((line_number, line, cost));
+            }
+            Some(first) => {
+                let from = first.parse().map_err(|_| invalid("node", first))?;
+                let found = argument(1, "node")?;
+                let to = found.parse().map_err(|_| invalid("node", found))?;
+                let cost = match tokens.get(2) {
+                    Some(found) => found.parse().map_err(|_| invalid("cost", found))?,
+                    None => 0.0,
+                };
+                edges.push((line_number, line, Edge { from, to, cost }));
+            }
+        }
+    }
+
+    let value = value.ok_or_else(|| ParseError {
+        line_number: s.lines().count(),
+        line: String::new(),
+        kind: ParseErrorKind::MissingArgument {
+            expected: "VALUE line",
+        },
+    })?;
+    Ok(SolutionLines { value, edges })
+}
+
+impl SteinerTree {
+    /*
+     * Parse a solution file without an instance to check it against. The cost
+     * is the one stated on the VALUE line, edges without a cost column get
+     * the cost 0.
+     */
+    pub fn from_solution_str(s: &str) -> Result<SteinerTree, ParseError> {
+        let lines = parse_lines(s)?;
+        Ok(SteinerTree {
+            edges: lines.edges.into_iter().map(|(_, _, edge)| edge).collect(),
+            cost: lines.value.2,
+        })
+    }
+
+    /*
+     * Parse a solution file of `instance`. Edges take their cost from the
+     * instance, in either orientation and using the cheapest of parallel
+     * edges, and the stated VALUE has to match the sum of those costs.
+     */
+    pub fn from_solution_str_checked(
+        s: &str,
+        instance: &SteinerInstance,
+    ) -> Result<SteinerTree, ParseError> {
+        let mut cheapest: HashMap<(usize, usize), f64> = HashMap::new();
+        for edge in &instance.edges {
+            let key = (edge.from.min(edge.to), edge.from.max(edge.to));
+            let cost = cheapest.entry(key).or_insert(edge.cost);
+            *cost = cost.min(edge.cost);
+        }
+
+        let lines = parse_lines(s)?;
+        let mut edges = Vec::with_capacity(lines.edges.len());
+        for (line_number, line, mut edge) in lines.edges {
+            let key = (edge.from.min(edge.to), edge.from.max(edge.to));
+            edge.cost = *cheapest.get(&key).ok_or_else(|| ParseError {
+                line_number,
+                line: line.to_string(),
+                kind: ParseErrorKind::UnknownEdge {
+                    from: edge.from,
+                    to: edge.to,
+                },
+            })?;
+            edges.push(edge);
+        }
+
+        let tree = SteinerTree::new(edges);
+        let (line_number, line, stated) = lines.value;
+        if (stated - tree.cost).abs() > VALUE_TOLERANCE * stated.abs().max(1.0) {
+            return Err(ParseError {
+                line_number,
+                line: line.to_string(),
+                kind: ParseErrorKind::ValueMismatch {
+                    stated,
+                    computed: tree.cost,
+                },
+            });
+        }
+        Ok(tree)
+    }
+
+    pub fn from_solution_file(path: impl AsRef<Path>) -> Result<SteinerTree, StpError> {
+        let s = std::fs::read_to_string(path)?;
+        Ok(Self::from_solution_str(&s)?)
+    }
+
+    pub fn from_solution_file_checked(
+        path: impl AsRef<Path>,
+        instance: &SteinerInstance,
+    ) -> Result<SteinerTree, StpError> {
+        let s = std::fs::read_to_string(path)?;
+        Ok(Self::from_solution_str_checked(&s, instance)?)
+    }
+
+    // Write the solution in the format of its Display impl
+    pub fn write_solution<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "{}", self)
+    }
+
+    pub fn to_solution_string(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn write_solution_to_file(
+        &self,
+        path: impl AsRef<Path>,
+        options: FileOptions,
+    ) -> io::Result<()> {
+        write_file(path.as_ref(), options, |writer| self.write_solution(writer))
+    }
+}
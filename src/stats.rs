@@ -0,0 +1,155 @@
+// Summary statistics of an instance, e.g. for tables over whole test sets.
+// Degrees and costs come from one pass over the edges, the components from a
+// union-find over the edges and arcs.
+
+use crate::components::DisjointSets;
+use crate::{IndexBase, SteinerInstance};
+use std::fmt;
+
+/*
+ * Statistics returned by SteinerInstance::stats. Degrees and costs are over
+ * the undirected edges, components treat arcs as undirected as in
+ * connected_components. Values over an empty set are 0.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceStats {
+    pub nodes: usize,
+    pub edges: usize,
+    pub terminals: usize,
+    // Edges per pair of distinct nodes
+    pub density: f64,
+    pub min_degree: usize,
+    pub max_degree: usize,
+    pub mean_degree: f64,
+    pub min_cost: f64,
+    pub max_cost: f64,
+    pub mean_cost: f64,
+    pub components: usize,
+    pub terminals_connected: bool,
+    pub has_coordinates: bool,
+}
+
+// Column names of InstanceStats::to_csv_row, in order
+const COLUMNS: [&str; 13] = [
+    "nodes",
+    "edges",
+    "terminals",
+    "density",
+    "min_degree",
+    "max_degree",
+    "mean_degree",
+    "min_cost",
+    "max_cost",
+    "mean_cost",
+    "components",
+    "terminals_connected",
+    "has_coordinates",
+];
+
+pub fn stats_csv_header() -> String {
+    COLUMNS.join(",")
+}
+
+impl InstanceStats {
+    // The values in the order of stats_csv_header, without a line break
+    pub fn to_csv_row(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for InstanceStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.nodes,
+            self.edges,
+            self.terminals,
+            self.density,
+            self.min_degree,
+            self.max_degree,
+            self.mean_degree,
+            self.min_cost,
+            self.max_cost,
+            self.mean_cost,
+            self.components,
+            self.terminals_connected,
+            self.has_coordinates
+        )
+    }
+}
+
+impl SteinerInstance {
+    /*
+     * Statistics of the instance in O(n + m). The terminal count is the
+     * length of the terminal list. Edges and arcs to nodes outside of the
+     * instance are ignored for degrees and components, terminals outside
+     * of it are never connected.
+     */
+    pub fn stats(&self) -> InstanceStats {
+        if self.index_base == IndexBase::Zero {
+            return self.to_one_indexed().stats();
+        }
+        let n = self.num_nodes;
+        let in_range = |node: usize| (1..=n).contains(&node);
+        let mut degrees = vec![0; n + 1];
+        let mut sets = DisjointSets::new(n + 1);
+        let mut components = n;
+        let (mut min_cost, mut max_cost, mut total_cost) = (f64::INFINITY, f64::NEG_INFINITY, 0.0);
+        for edge in &self.edges {
+            min_cost = min_cost.min(edge.cost);
+            max_cost = max_cost.max(edge.cost);
+            total_cost += edge.cost;
+            for node in [edge.from, edge.to] {
+                if in_range(node) {
+                    degrees[node] += 1;
+                }
+            }
+        }
+        for edge in self.edges.iter().chain(&self.arcs) {
+            if in_range(edge.from) && in_range(edge.to) && sets.union(edge.from, edge.to) {
+                components -= 1;
+            }
+        }
+
+        let degrees = &degrees[1..];
+        let m = self.edges.len();
+        let mean = |total: f64, count: usize| {
+            if count == 0 {
+                0.0
+            } else {
+                total / count as f64
+            }
+        };
+        let terminals_connected = match self.terminals.first() {
+            Some(&first) if in_range(first) => {
+                let root = sets.find(first);
+                self.terminals
+                    .iter()
+                    .all(|&terminal| in_range(terminal) && sets.find(terminal) == root)
+            }
+            Some(_) => false,
+            None => true,
+        };
+
+        InstanceStats {
+            nodes: n,
+            edges: m,
+            terminals: self.terminals.len(),
+            density: if n < 2 {
+                0.0
+            } else {
+                2.0 * m as f64 / (n * (n - 1)) as f64
+            },
+            min_degree: degrees.iter().copied().min().unwrap_or(0),
+            max_degree: degrees.iter().copied().max().unwrap_or(0),
+            mean_degree: mean(degrees.iter().sum::<usize>() as f64, n),
+            min_cost: if m == 0 { 0.0 } else { min_cost },
+            max_cost: if m == 0 { 0.0 } else { max_cost },
+            mean_cost: mean(total_cost, m),
+            components,
+            terminals_connected,
+            has_coordinates: !self.coordinates.is_empty(),
+        }
+    }
+}
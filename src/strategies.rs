@@ -0,0 +1,170 @@
+// proptest strategies for property tests against random instances.
+// Instances are put together with the builder, dropping self-loops and
+// keeping the cheapest of parallel edges, so every generated instance passes
+// validate(). Sizes shrink towards fewer nodes, edges and terminals.
+
+use crate::generate_random::UpdateOperation;
+use crate::{DuplicateEdgePolicy, Edge, SelfLoopPolicy, SteinerInstance};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use std::collections::HashSet;
+
+// Node count of the Arbitrary instances
+pub const DEFAULT_MAX_NODES: usize = 12;
+
+// Costs are multiples of 0.5, so they print and parse back exactly
+fn cost() -> impl Strategy<Value = f64> {
+    (0_u32..=200).prop_map(|cost| cost as f64 / 2.0)
+}
+
+impl Arbitrary for Edge {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Edge>;
+
+    // An edge between two distinct nodes in 1..=DEFAULT_MAX_NODES
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (1..=DEFAULT_MAX_NODES, 1..DEFAULT_MAX_NODES, cost())
+            .prop_map(|(from, offset, cost)| Edge {
+                from,
+                to: (from + offset - 1) % DEFAULT_MAX_NODES + 1,
+                cost,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for SteinerInstance {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<SteinerInstance>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        instances(DEFAULT_MAX_NODES).boxed()
+    }
+}
+
+fn build(
+    num_nodes: usize,
+    edges: impl IntoIterator<Item = (usize, usize, f64)>,
+    terminals: impl IntoIterator<Item = usize>,
+) -> SteinerInstance {
+    let mut builder = SteinerInstance::builder();
+    builder
+        .nodes(num_nodes)
+        .self_loops(SelfLoopPolicy::Drop)
+        .duplicate_edges(DuplicateEdgePolicy::KeepMinimum);
+    for (from, to, cost) in edges {
+        builder.edge(from, to, cost);
+    }
+    for terminal in terminals {
+        builder.terminal(terminal);
+    }
+    builder.build().expect("nodes are in range")
+}
+
+/*
+ * Instances with 1..=max_nodes nodes, up to two edges per node and any
+ * subset of the nodes as terminals. They may be disconnected.
+ */
+pub fn instances(max_nodes: usize) -> impl Strategy<Value = SteinerInstance> {
+    (1..=max_nodes.max(1)).prop_flat_map(|n| {
+        let edges = vec((1..=n, 1..=n, cost()), 0..=2 * n);
+        let terminals = vec(1..=n, 0..=n);
+        (edges, terminals).prop_map(move |(edges, terminals)| build(n, edges, terminals))
+    })
+}
+
+/*
+ * Connected instances with 1..=max_nodes nodes: a random spanning tree with
+ * up to one additional edge per node, and at least one terminal.
+ */
+pub fn connected_instances(max_nodes: usize) -> impl Strategy<Value = SteinerInstance> {
+    (1..=max_nodes.max(1)).prop_flat_map(|n| {
+        // Node v + 2 hangs off a node before it
+        let tree = vec((any::<Index>(), cost()), n - 1);
+        let extra = vec((1..=n, 1..=n, cost()), 0..=n);
+        let terminals = vec(1..=n, 1..=n);
+        (tree, extra, terminals).prop_map(move |(tree, extra, terminals)| {
+            let tree = tree
+                .into_iter()
+                .enumerate()
+                .map(|(i, (parent, cost))| (parent.index(i + 1) + 1, i + 2, cost));
+            build(n, tree.chain(extra), terminals)
+        })
+    })
+}
+
+/*
+ * Legal update sequences on a graph with 2..=max_nodes nodes that starts
+ * without edges and terminals: only absent edges are inserted, only present
+ * ones deleted, and the same holds for terminals. Queries carry the current
+ * instance, and every sequence ends with one.
+ */
+pub fn update_sequences(
+    max_nodes: usize,
+    max_len: usize,
+) -> impl Strategy<Value = Vec<UpdateOperation>> {
+    let steps = vec((0..4_usize, any::<Index>(), any::<bool>()), 0..=max_len);
+    (2..=max_nodes.max(2), steps).prop_map(|(n, steps)| {
+        let mut edges: Vec<Edge> = Vec::new();
+        let mut terminals: Vec<usize> = Vec::new();
+        let mut updates = Vec::new();
+        let query = |edges: &Vec<Edge>, terminals: &Vec<usize>| {
+            UpdateOperation::Query(Box::new(SteinerInstance::new(
+                n,
+                edges.clone(),
+                terminals.clone(),
+            )))
+        };
+
+        for (kind, pick, with_query) in steps {
+            let present: HashSet<(usize, usize)> =
+                edges.iter().map(|edge| (edge.from, edge.to)).collect();
+            let update = match kind {
+                0 => {
+                    let absent: Vec<(usize, usize)> = (1..=n)
+                        .flat_map(|from| (from + 1..=n).map(move |to| (from, to)))
+                        .filter(|pair| !present.contains(pair))
+                        .collect();
+                    if absent.is_empty() {
+                        continue;
+                    }
+                    let (from, to) = absent[pick.index(absent.len())];
+                    let edge = Edge {
+                        from,
+                        to,
+                        cost: 1.0,
+                    };
+                    edges.push(edge.clone());
+                    UpdateOperation::EdgeInsertion(edge)
+                }
+                1 if !edges.is_empty() => {
+                    UpdateOperation::EdgeDeletion(edges.remove(pick.index(edges.len())))
+                }
+                2 => {
+                    let inactive: Vec<usize> =
+                        (1..=n).filter(|node| !terminals.contains(node)).collect();
+                    if inactive.is_empty() {
+                        continue;
+                    }
+                    let terminal = inactive[pick.index(inactive.len())];
+                    terminals.push(terminal);
+                    UpdateOperation::TerminalActivation(terminal)
+                }
+                3 if !terminals.is_empty() => UpdateOperation::TerminalDeactivation(
+                    terminals.remove(pick.index(terminals.len())),
+                ),
+                _ => continue,
+            };
+            updates.push(update);
+            if with_query {
+                updates.push(query(&edges, &terminals));
+            }
+        }
+
+        if !matches!(updates.last(), Some(UpdateOperation::Query(_))) {
+            updates.push(query(&edges, &terminals));
+        }
+        updates
+    })
+}
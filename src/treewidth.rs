@@ -0,0 +1,314 @@
+// Exact solver for instances of small treewidth, complementing the
+// Dreyfus-Wagner solver for instances with many terminals. A tree
+// decomposition is taken from a min-degree elimination order: eliminating v
+// gives the bag of v and its neighbors at that point, whose parent is the bag
+// of the neighbor eliminated next. A dynamic program then runs over the bags
+// in elimination order, which visits every bag after its children.
+
+use crate::SteinerInstance;
+use crate::components::DisjointSets;
+use crate::solution::SteinerTree;
+use std::collections::{BTreeSet, HashMap};
+
+// Wider decompositions are refused, their tables wouldn't fit into memory
+pub const MAX_TREEWIDTH: usize = 16;
+
+/*
+ * A state of a bag: the selected nodes carry the number of the component of
+ * the partial solution they belong to, counted from 1 in order of
+ * appearance, the other nodes 0.
+ */
+type State = Vec<u8>;
+
+// How a state was reached, to collect the edges of the solution
+enum Trace {
+    Empty,
+    // This edge on top of an earlier trace
+    Edge(usize, usize),
+    // The traces of a bag and one of its children
+    Join(usize, usize),
+}
+
+// Cheapest cost and trace of every state of a bag
+struct Table {
+    nodes: Vec<usize>,
+    entries: HashMap<State, (f64, usize)>,
+}
+
+impl Table {
+    // The slot for the trace if the state got cheaper
+    fn offer(&mut self, state: State, cost: f64) -> Option<&mut usize> {
+        let entry = self.entries.entry(state).or_insert((f64::INFINITY, 0));
+        if cost < entry.0 {
+            entry.0 = cost;
+            Some(&mut entry.1)
+        } else {
+            None
+        }
+    }
+}
+
+impl SteinerInstance {
+    /*
+     * An optimal Steiner tree if the min-degree heuristic finds a tree
+     * decomposition of width at most max_width, which it does for every
+     * graph of treewidth 2 or less, such as series-parallel graphs. None if
+     * the decomposition is wider, or if the instance can't be solved (see
+     * approximate_steiner_tree for the reasons). Widths above MAX_TREEWIDTH
+     * count as MAX_TREEWIDTH. A bag of b nodes has fewer than 2^b * b!
+     * states, so the running time is linear in the number of nodes for a
+     * fixed width.
+     */
+    pub fn exact_bounded_treewidth(&self, max_width: usize) -> Option<SteinerTree> {
+        let terminals = self.solvable_terminals().ok()?;
+        let order = self.elimination_order(max_width.min(MAX_TREEWIDTH))?;
+        if terminals.len() <= 1 {
+            return Some(SteinerTree::default());
+        }
+
+        let n = self.num_nodes;
+        let mut position = vec![0; n + 1];
+        for (index, (node, _)) in order.iter().enumerate() {
+            position[*node] = index;
+        }
+        let mut is_terminal = vec![false; n + 1];
+        for &terminal in &terminals {
+            is_terminal[terminal] = true;
+        }
+
+        // Every edge belongs to the bag of the endpoint eliminated first
+        let mut local_edges = vec![Vec::new(); n + 1];
+        for (index, edge) in self.edges.iter().enumerate() {
+            if edge.from != edge.to {
+                let owner = if position[edge.from] < position[edge.to] {
+                    edge.from
+                } else {
+                    edge.to
+                };
+                local_edges[owner].push(index);
+            }
+        }
+
+        let mut traces = vec![Trace::Empty];
+        let mut tables: Vec<Option<Table>> = (0..=n).map(|_| None).collect();
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); n + 1];
+        let mut subtree_terminals = vec![0; n + 1];
+        let mut best: Option<(f64, usize)> = None;
+
+        for (node, neighbors) in &order {
+            let node = *node;
+            let bag: Vec<usize> = std::iter::once(node).chain(neighbors.clone()).collect();
+            subtree_terminals[node] += usize::from(is_terminal[node]);
+
+            // Any selection containing the terminals, without any edges yet
+            let mut table = Table {
+                nodes: bag.clone(),
+                entries: HashMap::new(),
+            };
+            for mask in 0..1u32 << bag.len() {
+                let selected = |i: usize| mask & (1 << i) != 0;
+                if (0..bag.len()).all(|i| selected(i) || !is_terminal[bag[i]]) {
+                    let state = (0..bag.len())
+                        .map(|i| if selected(i) { i as u8 + 1 } else { 0 })
+                        .collect();
+                    table.offer(canonical(state), 0.0);
+                }
+            }
+
+            for child in std::mem::take(&mut children[node]) {
+                let child_table = tables[child].take().expect("children come first");
+                table = join(table, &child_table, &mut traces);
+            }
+
+            for &index in &local_edges[node] {
+                let edge = &self.edges[index];
+                let other = if edge.from == node {
+                    edge.to
+                } else {
+                    edge.from
+                };
+                let other = bag.iter().position(|&v| v == other).expect("in the bag");
+                let updates: Vec<_> = table
+                    .entries
+                    .iter()
+                    .filter(|(state, _)| state[0] != 0 && state[other] != 0)
+                    .filter(|(state, _)| state[0] != state[other])
+                    .map(|(state, &(cost, trace))| {
+                        (merged(state, &[(0, other)]), cost + edge.cost, trace)
+                    })
+                    .collect();
+                for (state, cost, trace) in updates {
+                    if let Some(slot) = table.offer(state, cost) {
+                        traces.push(Trace::Edge(index, trace));
+                        *slot = traces.len() - 1;
+                    }
+                }
+            }
+
+            // Forget the node. A component that doesn't reach the rest of the
+            // bag is complete, which is only a solution if it contains all
+            // terminals.
+            let mut forgotten = Table {
+                nodes: neighbors.clone(),
+                entries: HashMap::new(),
+            };
+            for (state, (cost, trace)) in table.entries {
+                let rest: State = state[1..].to_vec();
+                if state[0] == 0 || rest.contains(&state[0]) {
+                    if let Some(slot) = forgotten.offer(canonical(rest), cost) {
+                        *slot = trace;
+                    }
+                } else if rest.iter().all(|&label| label == 0)
+                    && subtree_terminals[node] == terminals.len()
+                    && best.is_none_or(|(best, _)| cost < best)
+                {
+                    best = Some((cost, trace));
+                }
+            }
+
+            if let Some(parent) = neighbors.iter().copied().min_by_key(|&v| position[v]) {
+                children[parent].push(node);
+                subtree_terminals[parent] += subtree_terminals[node];
+                tables[node] = Some(forgotten);
+            }
+        }
+
+        let (_, trace) = best?;
+        let mut selected = vec![false; self.edges.len()];
+        let mut pending = vec![trace];
+        while let Some(trace) = pending.pop() {
+            match traces[trace] {
+                Trace::Empty => {}
+                Trace::Edge(index, rest) => {
+                    selected[index] = true;
+                    pending.push(rest);
+                }
+                Trace::Join(left, right) => pending.extend([left, right]),
+            }
+        }
+
+        // Components joined by several children may form cycles
+        let mut components = DisjointSets::new(n + 1);
+        let edges = self
+            .edges
+            .iter()
+            .zip(selected)
+            .filter(|&(edge, selected)| selected && components.union(edge.from, edge.to))
+            .map(|(edge, _)| edge.clone())
+            .collect();
+        Some(SteinerTree::new(edges))
+    }
+
+    /*
+     * The nodes in min-degree elimination order, each with its neighbors at
+     * the time it is eliminated, sorted. None as soon as a node has more
+     * than max_width of them.
+     */
+    fn elimination_order(&self, max_width: usize) -> Option<Vec<(usize, Vec<usize>)>> {
+        let n = self.num_nodes;
+        let mut neighbors = vec![BTreeSet::new(); n + 1];
+        for edge in &self.edges {
+            if edge.from != edge.to {
+                neighbors[edge.from].insert(edge.to);
+                neighbors[edge.to].insert(edge.from);
+            }
+        }
+        let mut queue: BTreeSet<(usize, usize)> =
+            (1..=n).map(|node| (neighbors[node].len(), node)).collect();
+
+        let mut order = Vec::with_capacity(n);
+        while let Some((_, node)) = queue.pop_first() {
+            let adjacent: Vec<usize> = std::mem::take(&mut neighbors[node]).into_iter().collect();
+            if adjacent.len() > max_width {
+                return None;
+            }
+            // The neighbors become a clique
+            for &u in &adjacent {
+                queue.remove(&(neighbors[u].len(), u));
+                neighbors[u].remove(&node);
+                neighbors[u].extend(adjacent.iter().copied().filter(|&v| v != u));
+                queue.insert((neighbors[u].len(), u));
+            }
+            order.push((node, adjacent));
+        }
+        Some(order)
+    }
+}
+
+// Combine the states of a bag with the states of a child that agree on the
+// selected nodes of the child's bag
+fn join(table: Table, child: &Table, traces: &mut Vec<Trace>) -> Table {
+    let positions: Vec<usize> = child
+        .nodes
+        .iter()
+        .map(|node| table.nodes.iter().position(|v| v == node))
+        .collect::<Option<_>>()
+        .expect("a child's bag is part of its parent's bag");
+
+    let mut by_selection: HashMap<u64, Vec<(&State, f64, usize)>> = HashMap::new();
+    for (state, &(cost, trace)) in &child.entries {
+        by_selection
+            .entry(selection(state.iter().copied()))
+            .or_default()
+            .push((state, cost, trace));
+    }
+
+    let mut joined = Table {
+        nodes: table.nodes,
+        entries: HashMap::new(),
+    };
+    for (state, (cost, trace)) in table.entries {
+        let key = selection(positions.iter().map(|&i| state[i]));
+        for &(child_state, child_cost, child_trace) in by_selection.get(&key).into_iter().flatten()
+        {
+            // Nodes in one component of the child end up in one component
+            let pairs: Vec<(usize, usize)> = (1..child_state.len())
+                .filter(|&i| child_state[i] != 0)
+                .filter_map(|i| {
+                    let first = child_state.iter().position(|&l| l == child_state[i])?;
+                    (first < i).then_some((positions[first], positions[i]))
+                })
+                .collect();
+            if let Some(slot) = joined.offer(merged(&state, &pairs), cost + child_cost) {
+                traces.push(Trace::Join(trace, child_trace));
+                *slot = traces.len() - 1;
+            }
+        }
+    }
+    joined
+}
+
+// Bit i is set if node i of the state is selected
+fn selection(labels: impl Iterator<Item = u8>) -> u64 {
+    labels
+        .enumerate()
+        .filter(|&(_, label)| label != 0)
+        .fold(0, |mask, (i, _)| mask | 1 << i)
+}
+
+// The state with the components of every pair of positions joined
+fn merged(state: &[u8], pairs: &[(usize, usize)]) -> State {
+    let mut components = DisjointSets::new(state.len() + 1);
+    for &(a, b) in pairs {
+        components.union(state[a] as usize, state[b] as usize);
+    }
+    canonical(
+        state
+            .iter()
+            .map(|&label| match label {
+                0 => 0,
+                label => components.find(label as usize) as u8,
+            })
+            .collect(),
+    )
+}
+
+// Renumber the components in order of appearance
+fn canonical(mut state: State) -> State {
+    let mut renamed = HashMap::new();
+    for label in state.iter_mut().filter(|label| **label != 0) {
+        let next = renamed.len() as u8 + 1;
+        *label = *renamed.entry(*label).or_insert(next);
+    }
+    state
+}
@@ -0,0 +1,148 @@
+// Well-formedness checks for instances, e.g. over a whole benchmark suite or
+// for generated instances before they are written to disk.
+
+use crate::{IndexBase, SteinerInstance};
+use petgraph::unionfind::UnionFind;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationIssueKind {
+    CountMismatch,
+    NodeOutOfRange,
+    DuplicateTerminal,
+    SelfLoop,
+    InvalidCost,
+    DisconnectedTerminals,
+}
+
+/*
+ * A single problem found by SteinerInstance::validate, with a message
+ * naming the offending element.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub kind: ValidationIssueKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidationOptions {
+    // Check that all terminals lie in one connected component, treating arcs
+    // as undirected. Needs a pass over the whole graph.
+    pub connectivity: bool,
+}
+
+impl SteinerInstance {
+    // Run all checks except the connectivity check, see validate_with
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        self.validate_with(&ValidationOptions::default())
+    }
+
+    /*
+     * Check that the count fields match the data, that all edges, arcs and
+     * terminals refer to nodes of the instance, that no terminal is listed
+     * twice, that there are no self-loops and that all costs are finite and
+     * non-negative. All issues are reported, not only the first one.
+     */
+    pub fn validate_with(&self, options: &ValidationOptions) -> Result<(), Vec<ValidationIssue>> {
+        let mut issues = Vec::new();
+        let mut issue = |kind, message: String| issues.push(ValidationIssue { kind, message });
+
+        for mismatch in self.check_counts() {
+            issue(ValidationIssueKind::CountMismatch, mismatch.to_string());
+        }
+
+        let in_range = |node: usize| self.node_ids().contains(&node);
+        let range = match self.index_base {
+            IndexBase::One => format!("1..={}", self.num_nodes),
+            IndexBase::Zero => format!("0..{}", self.num_nodes),
+        };
+        let edges = self.edges.iter().map(|edge| ("edge", edge));
+        let arcs = self.arcs.iter().map(|arc| ("arc", arc));
+        for (what, edge) in edges.chain(arcs) {
+            for node in [edge.from, edge.to] {
+                if !in_range(node) {
+                    issue(
+                        ValidationIssueKind::NodeOutOfRange,
+                        format!(
+                            "{} ({}, {}) references node {} outside of {}",
+                            what, edge.from, edge.to, node, range
+                        ),
+                    );
+                }
+            }
+            if edge.from == edge.to {
+                issue(
+                    ValidationIssueKind::SelfLoop,
+                    format!("{} ({}, {}) is a self-loop", what, edge.from, edge.to),
+                );
+            }
+            if !edge.cost.is_finite() || edge.cost < 0.0 {
+                issue(
+                    ValidationIssueKind::InvalidCost,
+                    format!(
+                        "{} ({}, {}) has the invalid cost {}",
+                        what, edge.from, edge.to, edge.cost
+                    ),
+                );
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for &terminal in &self.terminals {
+            if !in_range(terminal) {
+                issue(
+                    ValidationIssueKind::NodeOutOfRange,
+                    format!("terminal {} is outside of {}", terminal, range),
+                );
+            }
+            if !seen.insert(terminal) {
+                issue(
+                    ValidationIssueKind::DuplicateTerminal,
+                    format!("terminal {} is listed more than once", terminal),
+                );
+            }
+        }
+
+        if options.connectivity
+            && let Some((first, second)) = self.disconnected_terminals()
+        {
+            issue(
+                ValidationIssueKind::DisconnectedTerminals,
+                format!("terminals {} and {} are not connected", first, second),
+            );
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    // Two terminals in different components, if there are any
+    fn disconnected_terminals(&self) -> Option<(usize, usize)> {
+        let mut components = UnionFind::<usize>::new(self.num_nodes + 1);
+        let in_range = |node: usize| self.node_ids().contains(&node);
+        for edge in self.edges.iter().chain(&self.arcs) {
+            if in_range(edge.from) && in_range(edge.to) {
+                components.union(edge.from, edge.to);
+            }
+        }
+        let mut terminals = self
+            .terminals
+            .iter()
+            .copied()
+            .filter(|&terminal| in_range(terminal));
+        let first = terminals.next()?;
+        terminals
+            .find(|&terminal| !components.equiv(first, terminal))
+            .map(|terminal| (first, terminal))
+    }
+}
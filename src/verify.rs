@@ -0,0 +1,185 @@
+// Checking solutions given as lists of edges, e.g. from an external solver.
+
+use crate::SteinerInstance;
+use crate::components::DisjointSets;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/*
+ * Reason a solution was rejected by SteinerInstance::verify_solution, naming
+ * the offending edge, terminal or cycle.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolutionError {
+    MissingEdge { from: usize, to: usize },
+    DuplicateEdge { from: usize, to: usize },
+    // The nodes along the cycle, starting and ending at the same node
+    Cycle { nodes: Vec<usize> },
+    // Not connected to the first terminal
+    UnreachedTerminal { terminal: usize, root: usize },
+    // Not connected to the first terminal, or to the first edge if there are
+    // no terminals
+    DisconnectedEdge { from: usize, to: usize },
+}
+
+impl fmt::Display for SolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolutionError::MissingEdge { from, to } => {
+                write!(f, "edge ({}, {}) is not in the instance", from, to)
+            }
+            SolutionError::DuplicateEdge { from, to } => {
+                write!(f, "edge ({}, {}) is listed more than once", from, to)
+            }
+            SolutionError::Cycle { nodes } => {
+                write!(f, "the edges contain the cycle ")?;
+                for (index, node) in nodes.iter().enumerate() {
+                    let separator = if index == 0 { "" } else { " - " };
+                    write!(f, "{}{}", separator, node)?;
+                }
+                Ok(())
+            }
+            SolutionError::UnreachedTerminal { terminal, root } => write!(
+                f,
+                "terminal {} is not connected to terminal {}",
+                terminal, root
+            ),
+            SolutionError::DisconnectedEdge { from, to } => write!(
+                f,
+                "edge ({}, {}) is not connected to the rest of the solution",
+                from, to
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SolutionError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    // Accept solutions with cycles and report the cost of the tree that
+    // remains after breaking them and removing leaves that aren't terminals
+    pub allow_cycles: bool,
+}
+
+impl SteinerInstance {
+    // Verify a solution that must be a tree, see verify_solution_with
+    pub fn verify_solution(&self, edges: &[(usize, usize)]) -> Result<f64, SolutionError> {
+        self.verify_solution_with(edges, &VerifyOptions::default())
+    }
+
+    /*
+     * Check that every edge exists in the instance, in either orientation,
+     * that no edge is listed twice and that the edges form a connected
+     * subgraph containing all terminals. Returns the cost of the solution,
+     * using the cheapest of parallel edges. Edges are checked in the given
+     * order, the first problem found is reported.
+     */
+    pub fn verify_solution_with(
+        &self,
+        edges: &[(usize, usize)],
+        options: &VerifyOptions,
+    ) -> Result<f64, SolutionError> {
+        let mut cheapest: HashMap<(usize, usize), usize> = HashMap::new();
+        for (index, edge) in self.edges.iter().enumerate() {
+            let key = (edge.from.min(edge.to), edge.from.max(edge.to));
+            let entry = cheapest.entry(key).or_insert(index);
+            if edge.cost < self.edges[*entry].cost {
+                *entry = index;
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut indices = Vec::with_capacity(edges.len());
+        for &(from, to) in edges {
+            let key = (from.min(to), from.max(to));
+            let Some(&index) = cheapest.get(&key) else {
+                return Err(SolutionError::MissingEdge { from, to });
+            };
+            if !seen.insert(key) {
+                return Err(SolutionError::DuplicateEdge { from, to });
+            }
+            indices.push(index);
+        }
+
+        let max_node = edges
+            .iter()
+            .map(|&(from, to)| from.max(to))
+            .chain(self.terminals.iter().copied())
+            .max()
+            .unwrap_or(0);
+        let mut components = DisjointSets::new(max_node + 1);
+        let mut forest = Vec::with_capacity(indices.len());
+        let mut neighbors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (&(from, to), &index) in edges.iter().zip(&indices) {
+            if components.union(from, to) {
+                forest.push(index);
+                neighbors.entry(from).or_default().push(to);
+                neighbors.entry(to).or_default().push(from);
+            } else if !options.allow_cycles {
+                let mut nodes = forest_path(&neighbors, to, from);
+                nodes.push(to);
+                return Err(SolutionError::Cycle { nodes });
+            }
+        }
+
+        let root = self
+            .terminals
+            .first()
+            .copied()
+            .or_else(|| edges.first().map(|&(from, _)| from));
+        if let Some(root) = root {
+            if let Some(&terminal) = self
+                .terminals
+                .iter()
+                .find(|&&terminal| components.find(terminal) != components.find(root))
+            {
+                return Err(SolutionError::UnreachedTerminal { terminal, root });
+            }
+            if let Some(&(from, to)) = edges
+                .iter()
+                .find(|&&(from, _)| components.find(from) != components.find(root))
+            {
+                return Err(SolutionError::DisconnectedEdge { from, to });
+            }
+        }
+
+        let used = if options.allow_cycles {
+            let mut is_terminal = vec![false; self.num_nodes.max(max_node) + 1];
+            for &terminal in &self.terminals {
+                is_terminal[terminal] = true;
+            }
+            self.prune_leaves(&forest, &is_terminal)
+        } else {
+            indices
+        };
+        Ok(used.iter().map(|&index| self.edges[index].cost).sum())
+    }
+}
+
+// The nodes on the path from `from` to `to` in a forest, which must exist
+fn forest_path(neighbors: &HashMap<usize, Vec<usize>>, from: usize, to: usize) -> Vec<usize> {
+    let mut previous = HashMap::from([(from, from)]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(node) = queue.pop_front() {
+        if node == to {
+            break;
+        }
+        for &neighbor in neighbors.get(&node).into_iter().flatten() {
+            if let Entry::Vacant(entry) = previous.entry(neighbor) {
+                entry.insert(node);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    let mut path = vec![to];
+    let mut node = to;
+    while node != from {
+        node = previous[&node];
+        path.push(node);
+    }
+    path.reverse();
+    path
+}
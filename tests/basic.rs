@@ -1,12 +1,47 @@
 #[cfg(test)]
 mod tests {
 
+    use petgraph::algo::is_isomorphic_matching;
+    use petgraph::graph::{NodeIndex, UnGraph};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use std::collections::{BTreeMap, HashSet, VecDeque};
+
     use steinlib::{
-        Edge, Parser, export,
+        Coordinate, CountMismatch, DuplicateEdgePolicy, Edge, IndexBase, Metadata, ParseError,
+        ParseErrorKind, Parser, ParserOptions, Rectangle, STP_HEADER, Section, SelfLoopPolicy,
+        SteinerInstance, StpError,
+        builder::BuildError,
+        combine::MergeError,
+        conversion::ConversionError,
+        csv::CsvOptions,
+        dot::DotOptions,
+        events::{MAX_PREALLOCATED, StpEvent},
+        exact::ExactOptions,
+        export::{CostFormat, ExportOptions, FileOptions},
         generate_random::{
-            UpdateProbabilities, generate_random_with_fixed_vc, generate_update_sequence,
-            output_update_sequence,
+            ConnectivityStrategy, CostModel, DEFAULT_MAX_ATTEMPTS, DynamicInstance,
+            DynamicInstanceError, EuclideanOptions, FixedVcOptions, GenerationError,
+            GenerationPreset, GenerationProgress, GridError, ProbError, QueryPolicy,
+            ScaleFreeOptions, TerminalPlacement, TerminalSampling, UpdateOperation,
+            UpdateProbabilities, UpdateSequenceOptions, export_update_sequence, generate_batch,
+            generate_density_series, generate_grid, generate_grid_with_costs, generate_planted,
+            generate_random_directed, generate_random_euclidean,
+            generate_random_euclidean_with_options, generate_random_gnp,
+            generate_random_with_fixed_vc, generate_random_with_fixed_vc_with_rng,
+            generate_scale_free, generate_scale_free_with_options, generate_update_sequence,
+            generate_update_sequence_with_rng, is_vertex_cover, output_update_sequence,
+            updates_to_dus, write_update_sequence,
         },
+        gr::GrCosts,
+        reductions::{ReductionLog, ReductionOptions},
+        relabel::RelabelError,
+        scaling::ScalingError,
+        shortest_paths::PathError,
+        solution::{SolveError, SteinerTree},
+        stats::{InstanceStats, stats_csv_header},
+        validate::{ValidationIssueKind, ValidationOptions},
+        verify::{SolutionError, VerifyOptions},
     };
 
     const SAMPLE_STP: &str = r#"
@@ -30,7 +65,7 @@ mod tests {
     #[test]
     fn parsed_instance_correctness() {
         let mut parser = Parser::default();
-        let parsed = parser.parse_stp(SAMPLE_STP);
+        let parsed = parser.parse_stp(SAMPLE_STP).unwrap();
 
         // ✅ Check node count
         assert_eq!(parsed.num_nodes, 3, "Unexpected number of nodes");
@@ -84,7 +119,8 @@ mod tests {
             "Parsed terminals do not match expected"
         );
 
-        // Test also that the parsed instance is exported correctly
+        // Test also that the parsed instance is exported correctly, apart from
+        // the header, which SAMPLE_STP lacks
         let expected = SAMPLE_STP
             .lines()
             .map(|line| line.trim())
@@ -92,7 +128,10 @@ mod tests {
             .collect::<Vec<_>>()
             .join("\n");
         let actual = parsed
-            .to_string()
+            .to_stp_string_with(&ExportOptions {
+                header: false,
+                ..Default::default()
+            })
             .lines()
             .map(|line| line.trim())
             .filter(|line| !line.is_empty())
@@ -122,42 +161,4862 @@ mod tests {
 
     #[test]
     fn update_sequence_export() {
-        let replication_count = 5;
-        for n in [32, 64, 128, 256, 512, 1024, 2048] {
-            let prob_sparse_factor = if n > 256 { (n as f64).sqrt() } else { 2.0 };
-            for p in [prob_sparse_factor * (n as f64).ln() / (n as f64), 0.5] {
-                let t = (n as f64).log2().round() as usize;
-                let tau = (n as f64).log2();
-                for i in 1..replication_count + 1 {
-                    let u_tau = tau.round() as usize;
-                    println!(
-                        "Generate {}-th of  n={},p={},tau={},t={}",
-                        i, n, p, u_tau, t
-                    );
-                    let (steiner, vc) = generate_random_with_fixed_vc(n, t, u_tau, p);
-                    assert!(vc.len() <= u_tau);
-                    println!("Finished generating graph, computing updates");
-
-                    let update_probs = UpdateProbabilities {
-                        edge_deletion: 0.4,
-                        edge_insertion: 0.4,
-                        terminal_deactivation: 0.1,
-                        terminal_activation: 0.1,
-                    };
-                    let query_prob = 1.0;
-                    let update_sequence =
-                        generate_update_sequence(&steiner, update_probs, query_prob, vc, false, 10);
-                    let _ = output_update_sequence(
-                        update_sequence,
-                        format!(
-                            "generated_instances_clementi/{}_n={},p={},tau={},t={}",
-                            i, n, p, u_tau, t
-                        ),
-                    );
+        let presets: Vec<GenerationPreset> = [32, 64, 128, 256, 512, 1024, 2048]
+            .into_iter()
+            .flat_map(|n| {
+                [
+                    GenerationPreset::clementi_sparse(n),
+                    GenerationPreset::clementi_dense(n),
+                ]
+            })
+            .collect();
+        for (id, steiner, vc) in generate_batch(&presets, 5, 0).unwrap() {
+            assert!(vc.len() <= presets[id.preset].vc);
+            assert!(is_vertex_cover(&steiner, &vc));
+            let update_probs = UpdateProbabilities {
+                edge_deletion: 0.4,
+                edge_insertion: 0.4,
+                terminal_deactivation: 0.1,
+                terminal_activation: 0.1,
+            };
+            let update_sequence =
+                generate_update_sequence(&steiner, update_probs, 1.0, vc, false, 10).unwrap();
+            let name = steiner.metadata.name.unwrap();
+            let _ = output_update_sequence(
+                update_sequence,
+                format!("generated_instances_clementi/{}", name),
+            );
+        }
+    }
+
+    #[test]
+    fn update_sequence_to_dus() {
+        let updates = vec![
+            UpdateOperation::EdgeInsertion(Edge {
+                from: 1,
+                to: 2,
+                cost: 1.0,
+            }),
+            UpdateOperation::Query(Box::default()),
+            UpdateOperation::TerminalActivation(3),
+            UpdateOperation::Query(Box::default()),
+        ];
+
+        let dus = updates_to_dus(&updates);
+        assert_eq!(dus, "SECTION UPDATES\nE I 1 2 1\nQ 1\nT A 3\nQ 2\n");
+
+        // The in-memory rendering must agree with the file export
+        let (main_output, query_instances) = export_update_sequence(updates);
+        assert_eq!(main_output, dus);
+        assert_eq!(query_instances.len(), 2);
+    }
+
+    #[test]
+    fn planarity_detection() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(parsed.is_planar());
+
+        let complete = |n: usize| {
+            let mut builder = SteinerInstance::builder();
+            builder.nodes(n).terminal(1);
+            for i in 1..=n {
+                for j in (i + 1)..=n {
+                    builder.edge(i, j, 1.0);
+                }
+            }
+            builder.build().unwrap()
+        };
+        assert!(complete(4).is_planar());
+        assert!(!complete(5).is_planar());
+
+        // K_{3,3} has few enough edges to pass the Euler bound
+        let mut edges = Vec::new();
+        for i in 1..=3 {
+            for j in 4..=6 {
+                edges.push(Edge {
+                    from: i,
+                    to: j,
+                    cost: 1.0,
+                });
+            }
+        }
+        assert!(!SteinerInstance::new(6, edges, vec![1]).is_planar());
+
+        let graph = |n: usize, pairs: &[(usize, usize)]| {
+            let edges = pairs
+                .iter()
+                .map(|&(from, to)| Edge {
+                    from,
+                    to,
+                    cost: 1.0,
+                })
+                .collect();
+            SteinerInstance::new(n, edges, vec![1])
+        };
+
+        // Maximal planar graphs sit exactly at the 3n - 6 edges of the bound
+        let mut k5_minus_edge = Vec::new();
+        for i in 1..=5 {
+            for j in (i + 1)..=5 {
+                if (i, j) != (4, 5) {
+                    k5_minus_edge.push((i, j));
+                }
+            }
+        }
+        assert_eq!(k5_minus_edge.len(), 3 * 5 - 6);
+        assert!(graph(5, &k5_minus_edge).is_planar());
+
+        // Stacked triangulation: every new node goes into a face of the
+        // previous ones and is joined to its three corners
+        let n = 40;
+        let mut stacked = vec![(1, 2), (2, 3), (1, 3)];
+        let mut faces = vec![(1, 2, 3)];
+        for node in 4..=n {
+            let (a, b, c) = faces.swap_remove((node * 7) % faces.len());
+            stacked.extend([(a, node), (b, node), (c, node)]);
+            faces.extend([(a, b, node), (b, c, node), (a, c, node)]);
+        }
+        assert_eq!(stacked.len(), 3 * n - 6);
+        assert!(graph(n, &stacked).is_planar());
+
+        // A wheel and a grid, with hubs and long cycles for the DFS
+        let wheel: Vec<(usize, usize)> = (2..=9)
+            .flat_map(|i| [(1, i), (i, if i == 9 { 2 } else { i + 1 })])
+            .collect();
+        assert!(graph(9, &wheel).is_planar());
+        let side = 6;
+        let id = |row: usize, col: usize| row * side + col + 1;
+        let mut grid = Vec::new();
+        for row in 0..side {
+            for col in 0..side {
+                if col + 1 < side {
+                    grid.push((id(row, col), id(row, col + 1)));
+                }
+                if row + 1 < side {
+                    grid.push((id(row, col), id(row + 1, col)));
+                }
+            }
+        }
+        assert!(graph(side * side, &grid).is_planar());
+
+        // Non-planar graphs well under the bound, so only the left-right
+        // test can reject them
+        let petersen: Vec<(usize, usize)> = (0..5)
+            .flat_map(|i| {
+                [
+                    (i + 1, (i + 1) % 5 + 1),
+                    (i + 1, i + 6),
+                    (i + 6, (i + 2) % 5 + 6),
+                ]
+            })
+            .collect();
+        assert!(petersen.len() < 3 * 10 - 6);
+        assert!(!graph(10, &petersen).is_planar());
+
+        // K_{3,3} with every edge split by a node of its own
+        let mut subdivided = Vec::new();
+        let mut next = 7;
+        for i in 1..=3 {
+            for j in 4..=6 {
+                subdivided.extend([(i, next), (next, j)]);
+                next += 1;
+            }
+        }
+        assert!(!graph(next - 1, &subdivided).is_planar());
+
+        // Disconnected: one non-planar component is enough, isolated nodes
+        // and planar components don't matter
+        let shifted = |pairs: &[(usize, usize)], offset: usize| -> Vec<(usize, usize)> {
+            pairs
+                .iter()
+                .map(|&(a, b)| (a + offset, b + offset))
+                .collect()
+        };
+        let mut planar_parts = grid.clone();
+        planar_parts.extend(shifted(&wheel, 36));
+        assert!(graph(36 + 9 + 3, &planar_parts).is_planar());
+        let mut mixed = planar_parts;
+        mixed.extend(shifted(&petersen, 36 + 9));
+        assert!(!graph(36 + 9 + 10 + 3, &mixed).is_planar());
+        let mut mixed = shifted(&subdivided, 5);
+        mixed.extend(k5_minus_edge);
+        assert!(!graph(5 + next - 1, &mixed).is_planar());
+    }
+
+    #[test]
+    fn negative_cost_detection() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(!parsed.has_negative_costs());
+
+        let negative = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("E 2 3 2", "E 2 3 -2"))
+            .unwrap();
+        assert!(negative.has_negative_costs());
+
+        // Algorithms assuming non-negative costs refuse the instance instead
+        // of returning wrong results
+        let invalid_path = PathError::InvalidCost {
+            from: 2,
+            to: 3,
+            cost: -2.0,
+        };
+        assert_eq!(negative.shortest_paths(1), Err(invalid_path.clone()));
+        assert_eq!(negative.terminal_distance_matrix(), Err(invalid_path));
+        let invalid_solve = SolveError::InvalidCost {
+            from: 2,
+            to: 3,
+            cost: -2.0,
+        };
+        assert_eq!(
+            negative.approximate_steiner_tree(),
+            Err(invalid_solve.clone())
+        );
+        assert_eq!(negative.solve_exact(), Err(invalid_solve.clone()));
+        assert_eq!(negative.lower_bound(), Err(invalid_solve));
+        assert_eq!(negative.exact_bounded_treewidth(2), None);
+    }
+
+    #[test]
+    fn density_series_is_nested() {
+        let series = generate_density_series(30, 4, &[0.5, 0.1, 0.3], 7);
+        assert_eq!(series.len(), 3);
+
+        for pair in series.windows(2) {
+            assert!(pair[0].num_edges <= pair[1].num_edges);
+            assert_eq!(pair[0].terminals, pair[1].terminals);
+            let denser: HashSet<&Edge> = pair[1].edges.iter().collect();
+            assert!(pair[0].edges.iter().all(|e| denser.contains(e)));
+        }
+
+        // The same seed reproduces the same series
+        let again = generate_density_series(30, 4, &[0.5, 0.1, 0.3], 7);
+        assert_eq!(series[2].edges, again[2].edges);
+    }
+
+    #[test]
+    fn dynamic_replay_tracks_degrees() {
+        let edge = |from, to| Edge {
+            from,
+            to,
+            cost: 1.0,
+        };
+        let updates = vec![
+            UpdateOperation::EdgeInsertion(edge(1, 2)),
+            UpdateOperation::EdgeInsertion(edge(2, 3)),
+            UpdateOperation::EdgeDeletion(edge(1, 2)),
+            UpdateOperation::Query(Box::default()),
+        ];
+        let queries = vec![SteinerInstance::default().to_string()];
+        let mut dynamic = DynamicInstance::from_str(updates_to_dus(&updates), 0, &queries).unwrap();
+
+        assert_eq!(dynamic.current_degrees(), &[0, 0, 0]);
+        dynamic.get_next();
+        dynamic.get_next();
+        assert_eq!(dynamic.current_degrees(), &[1, 2, 1]);
+        dynamic.get_next();
+        assert_eq!(dynamic.current_degrees(), &[0, 1, 1]);
+
+        dynamic.reset();
+        assert_eq!(dynamic.current_degrees(), &[0, 0, 0]);
+    }
+
+    #[test]
+    fn dynamic_degrees_follow_the_graph() {
+        let edge = |from, to| Edge {
+            from,
+            to,
+            cost: 1.0,
+        };
+        let updates = vec![
+            UpdateOperation::EdgeInsertion(edge(1, 2)),
+            UpdateOperation::EdgeInsertion(edge(1, 3)),
+            UpdateOperation::EdgeInsertion(edge(1, 4)),
+            UpdateOperation::EdgeInsertion(edge(3, 4)),
+            // Absent and repeated edges leave the degrees alone
+            UpdateOperation::EdgeDeletion(edge(2, 3)),
+            UpdateOperation::EdgeInsertion(edge(2, 1)),
+            UpdateOperation::VertexDeletion(1),
+            UpdateOperation::EdgeDeletion(edge(1, 2)),
+            UpdateOperation::Query(Box::default()),
+        ];
+        let queries = vec![SteinerInstance::default().to_string()];
+        let mut dynamic = DynamicInstance::from_str(updates_to_dus(&updates), 0, &queries).unwrap();
+
+        for _ in 0..6 {
+            dynamic.get_next();
+        }
+        assert_eq!(dynamic.current_degrees(), &[3, 1, 2, 2]);
+        // Every neighbor of the deleted vertex loses an edge
+        dynamic.get_next();
+        assert_eq!(dynamic.current_degrees(), &[0, 0, 1, 1]);
+        dynamic.get_next();
+        assert_eq!(dynamic.current_degrees(), &[0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn checksum_verification() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let checksum = parsed.checksum_string();
+        assert_eq!(checksum.len(), 16);
+        assert!(parsed.verify_checksum(&checksum));
+        assert!(parsed.verify_checksum(&checksum.to_uppercase()));
+
+        // Reordering and flipping edges keeps the checksum
+        let reordered = Parser::default()
+            .parse_stp(
+                &SAMPLE_STP
+                    .replace("E 1 2 1", "E 2 1 1")
+                    .replace("T 1\n", "T 3\n")
+                    .replace("T 3\n    END", "T 1\n    END"),
+            )
+            .unwrap();
+        assert!(reordered.verify_checksum(&checksum));
+
+        // A dropped edge does not
+        let corrupted = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("E 2 3 2", ""))
+            .unwrap();
+        assert!(!corrupted.verify_checksum(&checksum));
+    }
+
+    #[test]
+    fn malformed_lines_are_reported() {
+        let err = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("E 2 3 2", "E 1a2 3 2"))
+            .unwrap_err();
+        assert_eq!(err.line_number, 6);
+        assert_eq!(err.line, "E 1a2 3 2");
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidArgument {
+                expected: "integer node id",
+                found: "1a2".to_string()
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 6: expected integer node id, got '1a2' in 'E 1a2 3 2'"
+        );
+
+        let err = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("T 3", "T"))
+            .unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::MissingArgument { .. }));
+
+        let err = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("Nodes 3", "Vertices 3"))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnknownKeyword("Vertices".to_string())
+        );
+
+        let err = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("SECTION Graph", "SECTION Graf"))
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnknownSection("Graf".to_string()));
+    }
+
+    #[test]
+    fn coordinates_section_round_trip() {
+        let stp = SAMPLE_STP.replace(
+            "EOF",
+            "SECTION Coordinates\nDD 1 0 0\nDD 2 1.5 2\nDDD 3 1 2 3\nDD 7 4 4\nEND\n\nEOF",
+        );
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+
+        assert_eq!(parsed.coordinates.len(), 4);
+        assert_eq!(
+            parsed.coordinates[&2],
+            Coordinate::Planar { x: 1.5, y: 2.0 }
+        );
+        assert_eq!(
+            parsed.coordinates[&3],
+            Coordinate::Spatial {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0
+            }
+        );
+        // Node ids beyond the declared node count are kept
+        assert!(parsed.coordinates.contains_key(&7));
+
+        let reparsed = Parser::default().parse_stp(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.coordinates, parsed.coordinates);
+
+        let err = Parser::default()
+            .parse_stp(&stp.replace("DD 1 0 0", "DD 1 0"))
+            .unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::MissingArgument { .. }));
+    }
+
+    #[test]
+    fn inline_terminal_coordinates() {
+        let mut instance = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        instance
+            .coordinates
+            .insert(1, Coordinate::Planar { x: 0.5, y: 2.0 });
+        instance
+            .coordinates
+            .insert(2, Coordinate::Planar { x: 1.0, y: 1.0 });
+        instance.coordinates.insert(
+            3,
+            Coordinate::Spatial {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+        );
+
+        // Standard output is unaffected
+        assert!(instance.to_string().contains("T 1\nT 3\n"));
+        let inline = instance.to_stp_string_with(&ExportOptions {
+            terminal_coordinates: true,
+            ..Default::default()
+        });
+        // Only planar coordinates fit on a T line
+        assert!(inline.contains("T 1 0.5 2\nT 3\n"));
+        let reparsed = Parser::default().parse_stp(&inline).unwrap();
+        assert_eq!(reparsed.terminals, instance.terminals);
+        assert_eq!(reparsed.coordinates, instance.coordinates);
+
+        // The T lines alone restore the coordinates
+        let stp = SAMPLE_STP.replace("T 3", "T 3 -1 4.25");
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        assert_eq!(parsed.terminals, vec![1, 3]);
+        assert_eq!(
+            parsed.coordinates,
+            BTreeMap::from([(3, Coordinate::Planar { x: -1.0, y: 4.25 })])
+        );
+
+        let err = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("T 3", "T 3 -1"))
+            .unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::MissingArgument { .. }));
+    }
+
+    #[test]
+    fn comment_section_metadata() {
+        let stp = format!(
+            "{}{}",
+            r#"
+            SECTION Comment
+            Name    "sample"
+            Creator "T. Koch, \"A.\" Martin"
+            Date    "2001"
+            Remark  "first"
+            Remark  "second"
+            Problem "Classical Steiner tree problem in graphs"
+            END
+            "#,
+            SAMPLE_STP
+        );
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+
+        assert_eq!(
+            parsed.metadata,
+            Metadata {
+                name: Some("sample".to_string()),
+                creator: Some("T. Koch, \"A.\" Martin".to_string()),
+                date: Some("2001".to_string()),
+                problem: Some("Classical Steiner tree problem in graphs".to_string()),
+                remarks: vec!["first".to_string(), "second".to_string()],
+            }
+        );
+        assert_eq!(parsed.num_edges, 3);
+
+        // The metadata is exported in a Comment section and read back verbatim
+        let exported = parsed.to_string();
+        assert!(exported.starts_with(&format!(
+            "{}\n\nSECTION Comment\nName \"sample\"\n",
+            STP_HEADER
+        )));
+        let reparsed = Parser::default().parse_stp(&exported).unwrap();
+        assert_eq!(reparsed.metadata, parsed.metadata);
+
+        // Generated instances record how they were created
+        let (generated, cover) =
+            generate_random_with_fixed_vc(10, 3, 2, 0.5, DEFAULT_MAX_ATTEMPTS).unwrap();
+        let remarks = &generated.metadata.remarks;
+        assert!(remarks[0].starts_with("generate_random_with_fixed_vc n=10 t=3 vc=2"));
+        assert_eq!(
+            remarks[1],
+            format!("vertex_cover: {} {}", cover[0], cover[1])
+        );
+        let reparsed = Parser::default().parse_stp(&generated.to_string()).unwrap();
+        assert_eq!(reparsed.metadata, generated.metadata);
+    }
+
+    #[test]
+    fn comment_remarks_by_key() {
+        let mut instance = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        instance.metadata.remarks.push("free text".to_string());
+        instance.set_comment_remark("vertex_cover", "3 7 12");
+        instance.set_comment_remark("seed", "4");
+        // An existing key is replaced in place
+        instance.set_comment_remark("vertex_cover", "1 2");
+        assert_eq!(
+            instance.metadata.remarks,
+            vec!["free text", "vertex_cover: 1 2", "seed: 4"]
+        );
+
+        let exported = instance.to_string();
+        assert!(exported.contains("Remark \"vertex_cover: 1 2\"\n"));
+        let reparsed = Parser::default().parse_stp(&exported).unwrap();
+        assert_eq!(reparsed.metadata.remarks, instance.metadata.remarks);
+    }
+
+    #[test]
+    fn maximum_degrees_round_trip() {
+        let stp = SAMPLE_STP.replace(
+            "EOF",
+            "SECTION MaximumDegrees\nMD 1\nMD 2\nMD 1\nEND\n\nEOF",
+        );
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        assert_eq!(parsed.max_degrees, vec![1, 2, 1]);
+
+        let reparsed = Parser::default().parse_stp(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.max_degrees, parsed.max_degrees);
+
+        // Too few entries are only refused in strict mode, whether or not
+        // the section is closed
+        let short = format!("{}\n{}", STP_HEADER, stp.replace("MD 2\n", ""));
+        let truncated = short.replace("END\n\nEOF", "");
+        assert_ne!(short, truncated);
+        for text in [&short, &truncated] {
+            let lenient = Parser::default().parse_stp(text).unwrap();
+            assert_eq!(lenient.max_degrees, vec![1, 1]);
+            let err = Parser::new(ParserOptions {
+                strict: true,
+                ..Default::default()
+            })
+            .parse_stp(text)
+            .unwrap_err();
+            assert_eq!(
+                err.kind,
+                ParseErrorKind::CountMismatch {
+                    what: "maximum degrees",
+                    declared: 3,
+                    found: 2
                 }
+            );
+        }
+    }
+
+    #[test]
+    fn root_terminal_round_trip() {
+        let parsed = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("Terminals 2", "Terminals 2\nRoot 1"))
+            .unwrap();
+        assert_eq!(parsed.root, Some(1));
+
+        let reparsed = Parser::default().parse_stp(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.root, Some(1));
+
+        let rooted_prize = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("Terminals 2", "Terminals 2\nRootP 3"))
+            .unwrap();
+        assert_eq!(rooted_prize.root, Some(3));
+
+        let err = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("Terminals 2", "Terminals 2\nRoot 4"))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::NodeOutOfRange {
+                node: 4,
+                num_nodes: 3
             }
+        );
+    }
+
+    #[test]
+    fn obstacles_round_trip() {
+        let stp = SAMPLE_STP
+            .replace("Edges 3", "Edges 3\nObstacles 2")
+            .replace(
+                "EOF",
+                "SECTION Obstacles\nRR 0 0 2 1.5\nRR 3 3 3 5\nEND\n\nEOF",
+            );
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        assert_eq!(parsed.num_obstacles, 2);
+        assert_eq!(
+            parsed.obstacles,
+            vec![
+                Rectangle {
+                    x1: 0.0,
+                    y1: 0.0,
+                    x2: 2.0,
+                    y2: 1.5
+                },
+                // Degenerate rectangles are kept
+                Rectangle {
+                    x1: 3.0,
+                    y1: 3.0,
+                    x2: 3.0,
+                    y2: 5.0
+                },
+            ]
+        );
+
+        let reparsed = Parser::default().parse_stp(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.num_obstacles, 2);
+        assert_eq!(reparsed.obstacles, parsed.obstacles);
+    }
+
+    #[test]
+    fn presolve_round_trip() {
+        let stp = SAMPLE_STP.replace(
+            "EOF",
+            "SECTION Presolve\nFIXED 2\nLOWER 3.5\nUPPER 4\nTIME 0.25\nORGNODES 10\nORGEDGES 20\nEA 4 5 1\nEC 6 7 2\nEND\n\nEOF",
+        );
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        let presolve = parsed
+            .presolve
+            .clone()
+            .expect("Presolve section was parsed");
+        assert_eq!(presolve.fixed, Some(2.0));
+        assert_eq!(presolve.lower, Some(3.5));
+        assert_eq!(presolve.upper, Some(4.0));
+        assert_eq!(presolve.time, Some(0.25));
+        assert_eq!(presolve.original_nodes, Some(10));
+        assert_eq!(presolve.original_edges, Some(20));
+        assert_eq!(presolve.ea_edges.len(), 1);
+        assert_eq!(presolve.ec_edges.len(), 1);
+
+        // Presolve lines must not leak into the graph
+        assert_eq!(parsed.edges.len(), 3);
+
+        let reparsed = Parser::default().parse_stp(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.presolve, parsed.presolve);
+
+        let plain = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(plain.presolve.is_none());
+    }
+
+    #[test]
+    fn node_weights_round_trip() {
+        let stp = SAMPLE_STP.replace("EOF", "SECTION NodeWeights\nNW 0\nNW 2.5\nNW 0\nEND\n\nEOF");
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        assert_eq!(parsed.node_weights, Some(vec![0.0, 2.5, 0.0]));
+
+        let reparsed = Parser::default().parse_stp(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.node_weights, parsed.node_weights);
+
+        // A lenient parse of a file cut off inside the section can be
+        // exported and read back, only strict mode checks the count
+        let truncated = format!("{}\n{}", STP_HEADER, stp.replace("NW 0\nEND\n\nEOF", ""));
+        let lenient = Parser::default().parse_stp(&truncated).unwrap();
+        assert_eq!(lenient.node_weights, Some(vec![0.0, 2.5]));
+        let reparsed = Parser::default()
+            .parse_stp(&lenient.to_stp_string())
+            .unwrap();
+        assert_eq!(reparsed.node_weights, lenient.node_weights);
+
+        let strict = || {
+            Parser::new(ParserOptions {
+                strict: true,
+                ..Default::default()
+            })
+        };
+        for text in [truncated, lenient.to_stp_string()] {
+            let err = strict().parse_stp(&text).unwrap_err();
+            assert!(matches!(
+                err.kind,
+                ParseErrorKind::CountMismatch {
+                    what: "node weights",
+                    ..
+                }
+            ));
+        }
+
+        let plain = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(plain.node_weights.is_none());
+    }
+
+    #[test]
+    fn prize_collecting_terminals() {
+        let stp = SAMPLE_STP
+            .replace("T 1", "TP 1 2.5")
+            .replace("T 3", "TP 3 4");
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        assert_eq!(parsed.num_terminals, 2);
+        assert_eq!(parsed.terminals, vec![1, 3]);
+        assert_eq!(parsed.terminal_prizes, vec![(1, 2.5), (3, 4.0)]);
+
+        let reparsed = Parser::default().parse_stp(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.terminals, parsed.terminals);
+        assert_eq!(reparsed.terminal_prizes, parsed.terminal_prizes);
+
+        let err = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("T 3", "TP 3 4"))
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MixedTerminals);
+    }
+
+    #[test]
+    fn terminal_groups_round_trip() {
+        let stp = SAMPLE_STP.replace("T 1\n    T 3", "TG 1 1\n    TG 2 1\n    TG 3 2");
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        assert_eq!(parsed.groups, vec![vec![1, 2], vec![3]]);
+        assert!(parsed.terminals.is_empty());
+
+        let reparsed = Parser::default().parse_stp(&parsed.to_string()).unwrap();
+        assert_eq!(reparsed.groups, parsed.groups);
+
+        let plain = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(plain.groups.is_empty());
+    }
+
+    #[test]
+    fn stp_header_recognition() {
+        let strict = || {
+            Parser::new(ParserOptions {
+                strict: true,
+                ..Default::default()
+            })
+        };
+
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert_eq!(parsed.format_version, None);
+
+        let with_header = parsed.to_stp_string();
+        assert!(with_header.starts_with("33D32945 STP File, STP Format Version 1.0\n"));
+        let reparsed = strict().parse_stp(&with_header).unwrap();
+        assert_eq!(reparsed.format_version, Some("1.0".to_string()));
+        assert_eq!(reparsed.num_edges, 3);
+
+        // The header is part of the default export, and exporting a parsed
+        // export reproduces it exactly
+        assert_eq!(parsed.to_string(), with_header);
+        assert_eq!(reparsed.to_string(), with_header);
+        let headerless = parsed.to_stp_string_with(&ExportOptions {
+            header: false,
+            ..Default::default()
+        });
+        assert!(headerless.starts_with("SECTION Graph\n"));
+        assert_eq!(
+            Parser::default()
+                .parse_stp(&headerless)
+                .unwrap()
+                .to_string(),
+            with_header
+        );
+
+        // Strict parsing refuses files without the header or with a newer major version
+        let err = strict().parse_stp(SAMPLE_STP).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidHeader);
+        let err = strict()
+            .parse_stp(&with_header.replace("Version 1.0", "Version 2.0"))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnsupportedVersion("2.0".to_string())
+        );
+        let err = strict()
+            .parse_stp(&with_header.replace("33D32945", "12345678"))
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidHeader);
+    }
+
+    #[test]
+    fn whitespace_and_case_variations() {
+        let messy = "
+            section graph
+            nodes\t3
+            EDGES  3
+            e 1  2  1
+            \tE\t2\t3\t2
+            E 1 3    3
+            end
+
+            Section TERMINALS
+            terminals 2
+            t 1
+            T\t3
+            END
+
+            eof
+        ";
+        let parsed = Parser::default().parse_stp(messy).unwrap();
+        let expected = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+
+        assert_eq!(parsed.num_nodes, 3);
+        assert_eq!(parsed.num_edges, 3);
+        assert_eq!(parsed.num_terminals, 2);
+        assert_eq!(parsed.terminals, expected.terminals);
+        assert!(
+            parsed
+                .edges
+                .iter()
+                .zip(expected.edges.iter())
+                .all(|(a, b)| edge_eq(a, b))
+        );
+    }
+
+    #[test]
+    fn end_and_eof_handling() {
+        // Stray lines after END are not interpreted as part of the section
+        let stray = SAMPLE_STP.replace("T 3\n    END", "T 3\n    END\n    T 2");
+        let parsed = Parser::default().parse_stp(&stray).unwrap();
+        assert_eq!(parsed.terminals, vec![1, 3]);
+
+        // Strict mode refuses them instead
+        let with_header = parsed.to_stp_string().replace("EOF", "T 2\nEOF");
+        let mut strict = Parser::new(ParserOptions {
+            strict: true,
+            ..Default::default()
+        });
+        let err = strict.parse_stp(&with_header).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::OutsideSection);
+
+        // Anything after EOF is ignored
+        let trailing = format!("{}\nSECTION Graf\ngarbage", SAMPLE_STP);
+        let parsed = Parser::default().parse_stp(&trailing).unwrap();
+        assert_eq!(parsed.num_edges, 3);
+
+        // A reused parser starts from scratch, even if the last file ended inside a section
+        let mut parser = Parser::default();
+        let _ = parser.parse_stp("SECTION Terminals\nT 1");
+        let parsed = parser.parse_stp(SAMPLE_STP).unwrap();
+        assert_eq!(parsed.terminals, vec![1, 3]);
+    }
+
+    #[test]
+    fn crlf_and_bom_tolerance() {
+        let expected = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let crlf = format!("\u{feff}{}", SAMPLE_STP.replace('\n', " \r\n"));
+
+        let parsed = Parser::default().parse_stp(&crlf).unwrap();
+        assert_eq!(parsed.to_string(), expected.to_string());
+
+        // Raw lines with their '\r' still attached are accepted as well
+        let mut parser = Parser::default();
+        let mut line_by_line = SteinerInstance::default();
+        for line in crlf.split('\n') {
+            parser.parse_stp_line(line, &mut line_by_line).unwrap();
+        }
+        assert_eq!(line_by_line.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn file_and_reader_parsing() {
+        let expected = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+
+        let from_reader = Parser::default()
+            .parse_stp_reader(SAMPLE_STP.as_bytes())
+            .unwrap();
+        assert_eq!(from_reader.to_string(), expected.to_string());
+
+        let path = std::env::temp_dir().join("steinlib_file_and_reader_parsing.stp");
+        std::fs::write(&path, SAMPLE_STP).unwrap();
+        let from_file = Parser::default().parse_stp_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(from_file.to_string(), expected.to_string());
+
+        // IO and parse failures are reported separately
+        let missing = Parser::default().parse_stp_file(&path).unwrap_err();
+        assert!(matches!(missing, StpError::Io(_)));
+        let invalid = Parser::default()
+            .parse_stp_reader(SAMPLE_STP.replace("E 1 2 1", "X 1 2 1").as_bytes())
+            .unwrap_err();
+        match invalid {
+            StpError::Parse(err) => assert_eq!(err.line_number, 5),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn streaming_events() {
+        let mut parser = Parser::default();
+        let events = parser
+            .events(SAMPLE_STP.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            events.first(),
+            Some(&StpEvent::SectionStart(Section::Graph))
+        );
+        assert_eq!(events.get(1), Some(&StpEvent::NodeCount(3)));
+        assert_eq!(events.last(), Some(&StpEvent::Eof));
+        assert_eq!(events.iter().filter(|e| **e == StpEvent::End).count(), 2);
+
+        // Scan for single pieces of information without building the instance
+        let terminals: Vec<usize> = parser
+            .events(SAMPLE_STP.as_bytes())
+            .filter_map(|event| match event {
+                Ok(StpEvent::Terminal(t)) => Some(t),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(terminals, vec![1, 3]);
+        let expensive = parser
+            .events(SAMPLE_STP.as_bytes())
+            .filter(|event| matches!(event, Ok(StpEvent::Edge(e)) if e.cost > 1.5))
+            .count();
+        assert_eq!(expensive, 2);
+
+        // Applying every event yields the same instance as parse_stp
+        let mut applied = SteinerInstance::default();
+        for event in events {
+            applied.apply_event(event);
         }
-        assert!(true);
+        let expected = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert_eq!(applied.to_string(), expected.to_string());
+
+        // The stream ends with the first error
+        let invalid = SAMPLE_STP.replace("E 2 3 2", "E 2 x 2");
+        let results: Vec<_> = parser.events(invalid.as_bytes()).collect();
+        assert!(matches!(results.last(), Some(Err(StpError::Parse(_)))));
+        assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn gzip_round_trip() {
+        use flate2::{Compression, write::GzEncoder};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(SAMPLE_STP.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let expected = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let path = std::env::temp_dir().join("steinlib_gzip_round_trip.stp.gz");
+        std::fs::write(&path, &compressed).unwrap();
+        let parsed = Parser::default().parse_stp_gz_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(parsed.to_string(), expected.to_string());
+
+        // Uncompressed input is rejected with an IO error
+        let err = Parser::default()
+            .parse_stp_gz_reader(SAMPLE_STP.as_bytes())
+            .unwrap_err();
+        assert!(matches!(err, StpError::Io(e) if e.kind() == std::io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn strict_and_lenient_modes() {
+        let missing_cost = SAMPLE_STP.replace("E 2 3 2", "E 2 3");
+
+        // Lenient parsing falls back to a cost of 1 and records what it tolerated
+        let mut lenient = Parser::default();
+        let parsed = lenient.parse_stp(&missing_cost).unwrap();
+        assert!(
+            parsed
+                .edges
+                .iter()
+                .any(|e| e.from == 2 && e.to == 3 && e.cost == 1.0)
+        );
+        let kinds: Vec<_> = lenient.warnings().iter().map(|w| w.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ParseErrorKind::InvalidHeader,
+                ParseErrorKind::MissingArgument {
+                    expected: "numeric edge cost"
+                },
+            ]
+        );
+        assert_eq!(lenient.warnings()[1].line_number, 6);
+
+        // Warnings belong to a single parse
+        let with_header = Parser::default()
+            .parse_stp(SAMPLE_STP)
+            .unwrap()
+            .to_stp_string();
+        lenient.parse_stp(&with_header).unwrap();
+        assert!(lenient.warnings().is_empty());
+
+        // Strict parsing refuses the missing cost, unless a default is configured
+        let header_missing_cost = with_header.replace("E 2 3 2", "E 2 3");
+        let err = Parser::new(ParserOptions {
+            strict: true,
+            ..Default::default()
+        })
+        .parse_stp(&header_missing_cost)
+        .unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::MissingArgument {
+                expected: "numeric edge cost"
+            }
+        );
+        let parsed = Parser::new(ParserOptions {
+            strict: true,
+            default_edge_cost: Some(5.0),
+            ..Default::default()
+        })
+        .parse_stp(&header_missing_cost)
+        .unwrap();
+        assert!(
+            parsed
+                .edges
+                .iter()
+                .any(|e| e.from == 2 && e.to == 3 && e.cost == 5.0)
+        );
+    }
+
+    #[test]
+    fn declared_counts_are_validated() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(parsed.check_counts().is_empty());
+
+        let missing_edge = SAMPLE_STP.replace("E 1 3 3\n", "");
+        let parsed = Parser::default().parse_stp(&missing_edge).unwrap();
+        assert_eq!(
+            parsed.check_counts(),
+            vec![CountMismatch {
+                what: "edges",
+                declared: 3,
+                found: 2
+            }]
+        );
+
+        // More terminals than declared are reported as well
+        let extra_terminal = SAMPLE_STP.replace("T 3\n", "T 3\n    T 2\n");
+        let parsed = Parser::default().parse_stp(&extra_terminal).unwrap();
+        assert_eq!(
+            parsed.check_counts(),
+            vec![CountMismatch {
+                what: "terminals",
+                declared: 2,
+                found: 3
+            }]
+        );
+
+        // Strict parsing fails on the first mismatch
+        let mut strict = Parser::new(ParserOptions {
+            strict: true,
+            ..Default::default()
+        });
+        let err = strict
+            .parse_stp(&format!("{}\n{}", STP_HEADER, extra_terminal))
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::CountMismatch {
+                what: "terminals",
+                declared: 2,
+                found: 3
+            }
+        );
+        let header = Parser::default()
+            .parse_stp(SAMPLE_STP)
+            .unwrap()
+            .to_stp_string();
+        assert!(strict.parse_stp(&header).is_ok());
+    }
+
+    #[test]
+    fn exported_counts_follow_the_data() {
+        let strict = || {
+            Parser::new(ParserOptions {
+                strict: true,
+                ..Default::default()
+            })
+        };
+
+        // Every declared count is off, the export writes what is there
+        let stale = SAMPLE_STP
+            .replace("Edges 3", "Edges 5\n    Arcs 4\n    Obstacles 3")
+            .replace("E 1 3 3", "E 1 3 3\n    A 3 1 1\n    A 2 1 1")
+            .replace("Terminals 2", "Terminals 7")
+            .replace(
+                "EOF",
+                "SECTION Obstacles\nRR 0 0 2 1.5\nRR 3 3 3 5\nEND\n\nEOF",
+            );
+        let parsed = Parser::default().parse_stp(&stale).unwrap();
+        assert_eq!(parsed.check_counts().len(), 4);
+        let exported = parsed.to_stp_string();
+        for line in ["Edges 3", "Arcs 2", "Obstacles 2", "Terminals 2"] {
+            assert!(exported.contains(&format!("{}\n", line)), "{}", line);
+        }
+        let reparsed = strict().parse_stp(&exported).unwrap();
+        assert!(reparsed.check_counts().is_empty());
+        assert_eq!(reparsed.edges, parsed.edges);
+        assert_eq!(reparsed.arcs, parsed.arcs);
+        assert_eq!(reparsed.obstacles, parsed.obstacles);
+
+        // Fields changed after parsing don't leak into the file either
+        let mut edited = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        edited.num_edges = 99;
+        edited.num_terminals = 0;
+        let reparsed = strict().parse_stp(&edited.to_stp_string()).unwrap();
+        assert_eq!(reparsed.num_edges, 3);
+        assert_eq!(reparsed.num_terminals, 2);
+
+        // Group members count as terminals of group instances
+        let groups = SAMPLE_STP
+            .replace("Terminals 2", "Terminals 1")
+            .replace("T 1\n    T 3", "TG 1 1\n    TG 2 1\n    TG 3 2");
+        let parsed = Parser::default().parse_stp(&groups).unwrap();
+        let reparsed = strict().parse_stp(&parsed.to_stp_string()).unwrap();
+        assert_eq!(reparsed.num_terminals, 3);
+        assert_eq!(reparsed.groups, parsed.groups);
+    }
+
+    #[test]
+    fn node_bounds_checking() {
+        let checked = || {
+            Parser::new(ParserOptions {
+                check_node_bounds: Some(true),
+                ..Default::default()
+            })
+        };
+        assert!(checked().parse_stp(SAMPLE_STP).is_ok());
+
+        let bad_edge = SAMPLE_STP.replace("E 2 3 2", "E 2 7 2");
+        let err = checked().parse_stp(&bad_edge).unwrap_err();
+        assert_eq!(err.line_number, 6);
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::NodeOutOfRange {
+                node: 7,
+                num_nodes: 3
+            }
+        );
+
+        let bad_terminal = SAMPLE_STP.replace("T 3", "T 0");
+        let err = checked().parse_stp(&bad_terminal).unwrap_err();
+        assert_eq!(err.line_number, 13);
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::NodeOutOfRange {
+                node: 0,
+                num_nodes: 3
+            }
+        );
+
+        // Off by default in lenient mode, on by default in strict mode
+        assert!(Parser::default().parse_stp(&bad_edge).is_ok());
+        let header = Parser::default()
+            .parse_stp(&bad_edge)
+            .unwrap()
+            .to_stp_string();
+        let err = Parser::new(ParserOptions {
+            strict: true,
+            ..Default::default()
+        })
+        .parse_stp(&header)
+        .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ParseErrorKind::NodeOutOfRange { node: 7, .. }
+        ));
+    }
+
+    #[test]
+    fn self_loop_policies() {
+        let with_loop = SAMPLE_STP.replace("E 2 3 2", "E 2 2 2");
+        let parser = |strict, self_loops| {
+            Parser::new(ParserOptions {
+                strict,
+                self_loops,
+                ..Default::default()
+            })
+        };
+
+        let mut silent = parser(false, SelfLoopPolicy::Drop);
+        let parsed = silent.parse_stp(&with_loop).unwrap();
+        assert_eq!(parsed.edges.len(), 2);
+        assert_eq!(parsed.num_edges, 2);
+        assert!(
+            silent
+                .warnings()
+                .iter()
+                .all(|w| w.kind == ParseErrorKind::InvalidHeader)
+        );
+
+        let mut warning = parser(false, SelfLoopPolicy::Warn);
+        let parsed = warning.parse_stp(&with_loop).unwrap();
+        assert_eq!(parsed.edges.len(), 2);
+        assert!(
+            warning
+                .warnings()
+                .iter()
+                .any(|w| w.kind == ParseErrorKind::SelfLoop { node: 2 } && w.line_number == 6)
+        );
+
+        let err = parser(false, SelfLoopPolicy::Error)
+            .parse_stp(&with_loop)
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::SelfLoop { node: 2 });
+        assert_eq!(err.line_number, 6);
+
+        // Silently dropped loops keep the declared counts consistent in strict mode
+        let header = format!("33D32945 STP File, STP Format Version 1.0\n{}", with_loop);
+        let parsed = parser(true, SelfLoopPolicy::Drop)
+            .parse_stp(&header)
+            .unwrap();
+        assert!(parsed.check_counts().is_empty());
+
+        // Instances built in code can be cleaned up afterwards
+        let mut instance = SteinerInstance::new(
+            2,
+            vec![
+                Edge {
+                    from: 1,
+                    to: 1,
+                    cost: 1.0,
+                },
+                Edge {
+                    from: 1,
+                    to: 2,
+                    cost: 1.0,
+                },
+            ],
+            vec![1, 2],
+        );
+        let removed = instance.remove_self_loops();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(instance.edges.len(), 1);
+        assert_eq!(instance.num_edges, 1);
+    }
+
+    #[test]
+    fn duplicate_edge_policies() {
+        let duplicated = SAMPLE_STP.replace("E 1 3 3", "E 1 3 3\n    E 3 1 0.5");
+        let parser = |duplicate_edges| {
+            Parser::new(ParserOptions {
+                duplicate_edges,
+                ..Default::default()
+            })
+        };
+
+        let parsed = parser(DuplicateEdgePolicy::KeepAll)
+            .parse_stp(&duplicated)
+            .unwrap();
+        assert_eq!(parsed.edges.len(), 4);
+
+        let parsed = parser(DuplicateEdgePolicy::KeepMinimum)
+            .parse_stp(&duplicated)
+            .unwrap();
+        assert_eq!(parsed.edges.len(), 3);
+        assert!(edge_eq(
+            &parsed.edges[2],
+            &Edge {
+                from: 1,
+                to: 3,
+                cost: 0.5
+            }
+        ));
+
+        let err = parser(DuplicateEdgePolicy::Error)
+            .parse_stp(&duplicated)
+            .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::DuplicateEdge { from: 3, to: 1 });
+        assert_eq!(err.line_number, 8);
+
+        // Arcs are only duplicates if they point the same way
+        let arcs = "SECTION Graph\nNodes 2\nArcs 3\nA 1 2 4\nA 2 1 3\nA 1 2 2\nEND\nEOF";
+        let mut parsed = Parser::default().parse_stp(arcs).unwrap();
+        let duplicates = parsed.clone().dedup_edges(DuplicateEdgePolicy::Error);
+        assert_eq!(duplicates.unwrap_err().len(), 1);
+        assert_eq!(parsed.dedup_edges(DuplicateEdgePolicy::KeepMinimum), Ok(1));
+        assert_eq!(parsed.num_arcs, 2);
+        assert_eq!(parsed.arcs[0].cost, 2.0);
+    }
+
+    #[test]
+    fn cost_parsing_and_validation() {
+        let notations = SAMPLE_STP
+            .replace("E 1 2 1", "E 1 2 1e3")
+            .replace("E 2 3 2", "E 2 3 7")
+            .replace("E 1 3 3", "E 1 3 2.5E-1");
+        let mut parser = Parser::default();
+        let parsed = parser.parse_stp(&notations).unwrap();
+        let costs: Vec<f64> = parsed.edges.iter().map(|e| e.cost).collect();
+        assert_eq!(costs, vec![1000.0, 7.0, 0.25]);
+        assert!(
+            parser
+                .warnings()
+                .iter()
+                .all(|w| w.kind == ParseErrorKind::InvalidHeader)
+        );
+
+        let header = Parser::default()
+            .parse_stp(SAMPLE_STP)
+            .unwrap()
+            .to_stp_string();
+        let strict = |allow_negative_costs| {
+            Parser::new(ParserOptions {
+                strict: true,
+                allow_negative_costs,
+                ..Default::default()
+            })
+        };
+        for (cost, message) in [
+            ("nan", "cost is NaN"),
+            ("inf", "cost is infinite"),
+            ("1e400", "cost is infinite"),
+            ("-2", "negative cost -2"),
+        ] {
+            let invalid = header.replace("E 2 3 2", &format!("E 2 3 {}", cost));
+
+            // Kept with a warning in lenient mode
+            let mut lenient = Parser::default();
+            lenient.parse_stp(&invalid).unwrap();
+            let warning = lenient.warnings().last().unwrap();
+            assert!(matches!(warning.kind, ParseErrorKind::InvalidCost(_)));
+            assert_eq!(warning.kind.to_string(), message);
+
+            let err = strict(false).parse_stp(&invalid).unwrap_err();
+            assert!(matches!(err.kind, ParseErrorKind::InvalidCost(_)));
+        }
+
+        // Negative costs can be allowed explicitly, the others not
+        let negative = header.replace("E 2 3 2", "E 2 3 -2");
+        assert!(strict(true).parse_stp(&negative).is_ok());
+        let nan = header.replace("E 2 3 2", "E 2 3 nan");
+        assert!(strict(true).parse_stp(&nan).is_err());
+    }
+
+    #[test]
+    #[ignore = "timing based, run with --ignored"]
+    fn large_instance_throughput() {
+        let n = 200_000;
+        let edges = (1..n)
+            .map(|i| Edge {
+                from: i,
+                to: i + 1,
+                cost: (i % 97) as f64,
+            })
+            .collect();
+        let instance = SteinerInstance::new(n, edges, (1..=n).step_by(1000).collect());
+        let stp = instance.to_stp_string();
+
+        let start = std::time::Instant::now();
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        let elapsed = start.elapsed();
+
+        assert_eq!(parsed.edges.len(), n - 1);
+        assert_eq!(parsed.terminals.len(), instance.terminals.len());
+        assert!(edge_eq(&parsed.edges[n - 2], &instance.edges[n - 2]));
+        // The declared counts are below MAX_PREALLOCATED, so the lists are
+        // reserved once and never grow
+        assert_eq!(parsed.edges.capacity(), n - 1);
+        assert_eq!(parsed.terminals.capacity(), instance.terminals.len());
+        // Debug builds parse about 500k lines per second, release builds more
+        // than ten times as many
+        assert!(
+            elapsed < std::time::Duration::from_secs(5),
+            "parsing {} lines took {:?}",
+            stp.lines().count(),
+            elapsed
+        );
+    }
+
+    #[test]
+    fn declared_counts_preallocate_within_limits() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(parsed.edges.capacity() >= 3);
+
+        // A lying header must not reserve memory for all the announced entries
+        let lying = SAMPLE_STP
+            .replace("Edges 3", "Edges 999999999999")
+            .replace("Terminals 2", "Terminals 999999999999");
+        let parsed = Parser::default().parse_stp(&lying).unwrap();
+        assert_eq!(parsed.edges.len(), 3);
+        assert!(parsed.edges.capacity() <= MAX_PREALLOCATED);
+        assert!(parsed.terminals.capacity() <= MAX_PREALLOCATED);
+        assert_eq!(parsed.num_edges, 999999999999);
+    }
+
+    #[test]
+    fn parser_reuse_across_files() {
+        let second = "
+            SECTION Graph
+            Nodes 5
+            Edges 2
+            E 1 5 4
+            E 4 5 1
+            END
+
+            SECTION Terminals
+            Terminals 3
+            T 1
+            T 4
+            T 5
+            END
+
+            SECTION Coordinates
+            DD 1 0 0
+            END
+
+            EOF
+        ";
+        // Ends inside the Terminals section without END or EOF
+        let truncated =
+            "SECTION Graph\nNodes 2\nEdges 1\nE 1 2 1\nEND\nSECTION Terminals\nTerminals 1";
+
+        let mut parser = Parser::default();
+        let first = parser.parse_stp(SAMPLE_STP).unwrap();
+        let _ = parser.parse_stp(truncated).unwrap();
+        let second = parser.parse_stp(second).unwrap();
+        let first_again = parser.parse_stp(SAMPLE_STP).unwrap();
+
+        assert_eq!(first.num_nodes, 3);
+        assert_eq!(first.terminals, vec![1, 3]);
+        assert_eq!(second.num_nodes, 5);
+        assert_eq!(second.edges.len(), 2);
+        assert_eq!(second.terminals, vec![1, 4, 5]);
+        assert_eq!(second.coordinates.len(), 1);
+        assert_eq!(first_again.to_string(), first.to_string());
+        assert!(first_again.coordinates.is_empty());
+    }
+
+    #[test]
+    fn directory_parsing() {
+        let dir = std::env::temp_dir().join("steinlib_directory_parsing");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("b.stp"), SAMPLE_STP).unwrap();
+        std::fs::write(dir.join("a.STP"), SAMPLE_STP.replace("E 1 3 3\n", "")).unwrap();
+        std::fs::write(dir.join("broken.stp"), "SECTION Graph\nX 1\nEOF").unwrap();
+        std::fs::write(dir.join("notes.txt"), "not an instance").unwrap();
+        std::fs::write(dir.join("nested").join("c.stp"), SAMPLE_STP).unwrap();
+
+        let flat = Parser::default().parse_stp_dir(&dir, false).unwrap();
+        let recursive = Parser::default().parse_stp_dir(&dir, true).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = flat.instances.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(flat.instances[0].1.edges.len(), 2);
+        assert_eq!(flat.failures.len(), 1);
+        assert!(flat.failures[0].0.ends_with("broken.stp"));
+        assert!(matches!(flat.failures[0].1, StpError::Parse(_)));
+
+        let names: Vec<&str> = recursive
+            .instances
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn gr_parsing() {
+        // The query instances written by output_update_sequence read back as-is
+        let expected = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let (_, query_instances) =
+            export_update_sequence(vec![UpdateOperation::Query(Box::new(expected.clone()))]);
+        let mut parser = Parser::default();
+        let parsed = parser.parse_gr(&query_instances[0]).unwrap();
+        assert_eq!(parsed.to_string(), expected.to_string());
+        assert!(parser.warnings().is_empty());
+
+        // PACE track 2 files carry a tree decomposition after the terminals
+        let pace = format!(
+            "{}\nSECTION Tree Decomposition\ns td 1 3 3\nb 1 1 2 3\nEND\n\nEOF",
+            query_instances[0].trim_end().trim_end_matches("EOF")
+        );
+        let parsed = Parser::default().parse_gr(&pace).unwrap();
+        assert_eq!(parsed.to_string(), expected.to_string());
+
+        // DIMACS flavour
+        let dimacs =
+            "c sample instance\np sp 3 3\ne 1 2 1\ne 2 3 2\nc terminals\ne 1 3 3\nt 1\nt 3\n";
+        let parsed = Parser::default().parse_gr(dimacs).unwrap();
+        assert_eq!(parsed.num_nodes, 3);
+        assert_eq!(parsed.num_terminals, 2);
+        assert_eq!(parsed.to_string(), expected.to_string());
+
+        let err = Parser::default()
+            .parse_gr("p sp 3 1\nc\ne 1 x 1")
+            .unwrap_err();
+        assert_eq!(err.line_number, 3);
+    }
+
+    #[test]
+    fn gr_export_round_trip() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let gr = parsed.to_gr_string(GrCosts::RequireIntegral).unwrap();
+        assert!(gr.contains("E 2 3 2\n"));
+        assert!(!gr.contains("33D32945"));
+
+        let reparsed = Parser::default().parse_gr(&gr).unwrap();
+        assert_eq!(reparsed.num_nodes, parsed.num_nodes);
+        assert_eq!(reparsed.terminals, parsed.terminals);
+        assert!(
+            parsed
+                .edges
+                .iter()
+                .zip(&reparsed.edges)
+                .all(|(a, b)| edge_eq(a, b))
+        );
+
+        // Fractional costs are refused unless they are scaled explicitly
+        let fractional = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("E 2 3 2", "E 2 3 2.25"))
+            .unwrap();
+        let err = fractional
+            .to_gr_string(GrCosts::RequireIntegral)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let scaled = fractional.to_gr_string(GrCosts::Scale(4.0)).unwrap();
+        assert!(scaled.contains("E 1 2 4\n"));
+        assert!(scaled.contains("E 2 3 9\n"));
+
+        let mut written = Vec::new();
+        parsed
+            .write_gr(&mut written, GrCosts::RequireIntegral)
+            .unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), gr);
+    }
+
+    #[test]
+    fn arcs_round_trip() {
+        let directed = "
+            SECTION Graph
+            Nodes 3
+            Edges 1
+            Arcs 2
+            E 1 2 1
+            A 2 3 4
+            A 3 1 0.5
+            END
+
+            SECTION Terminals
+            Terminals 2
+            T 1
+            T 3
+            END
+
+            EOF
+        ";
+        let mut parsed = Parser::default().parse_stp(directed).unwrap();
+        let exported = parsed.to_string();
+        assert!(exported.contains("Arcs 2\n"));
+        assert!(exported.contains("A 2 3 4\nA 3 1 0.5\n"));
+
+        let reparsed = Parser::default().parse_stp(&exported).unwrap();
+        assert_eq!(reparsed.num_arcs, 2);
+        assert!(
+            parsed
+                .arcs
+                .iter()
+                .zip(&reparsed.arcs)
+                .all(|(a, b)| edge_eq(a, b))
+        );
+        assert_eq!(reparsed.to_string(), exported);
+
+        // The count is taken from the arcs, not the possibly stale field
+        parsed.num_arcs = 7;
+        assert!(parsed.to_string().contains("Arcs 2\n"));
+
+        // Undirected instances have no Arcs line
+        let undirected = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(!undirected.to_string().contains("Arcs"));
+    }
+
+    #[test]
+    fn writer_export() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let mut written = Vec::new();
+        parsed.write_stp(&mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), parsed.to_string());
+
+        let options = ExportOptions {
+            header: false,
+            ..Default::default()
+        };
+        let mut headerless = Vec::new();
+        parsed.write_stp_with(&mut headerless, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(headerless).unwrap(),
+            format!("{}", parsed.display_with(&options))
+        );
+
+        let edge = Edge {
+            from: 1,
+            to: 2,
+            cost: 2.5,
+        };
+        assert_eq!(edge.to_string(), "1 2 2.5");
+        assert_eq!(
+            UpdateOperation::EdgeDeletion(edge.clone()).to_string(),
+            "E D 1 2 2.5"
+        );
+
+        // Streaming an update sequence produces the same files as exporting it
+        let updates = vec![
+            UpdateOperation::EdgeInsertion(edge),
+            UpdateOperation::Query(Box::new(parsed.clone())),
+            UpdateOperation::TerminalActivation(2),
+            UpdateOperation::Query(Box::default()),
+        ];
+        let mut main = Vec::new();
+        let mut queries = vec![Vec::new(); 2];
+        let mut numbers = Vec::new();
+        let mut query_iter = queries.iter_mut();
+        write_update_sequence(&updates, &mut main, |query_no| {
+            numbers.push(query_no);
+            Ok(query_iter.next().unwrap())
+        })
+        .unwrap();
+        assert_eq!(numbers, [1, 2]);
+
+        let (expected_main, expected_queries) = export_update_sequence(updates);
+        assert_eq!(String::from_utf8(main).unwrap(), expected_main);
+        let queries: Vec<String> = queries
+            .into_iter()
+            .map(|query| String::from_utf8(query).unwrap())
+            .collect();
+        assert_eq!(queries, expected_queries);
+    }
+
+    #[test]
+    fn file_export() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let dir = std::env::temp_dir().join("steinlib_file_export");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join("sample.stp");
+
+        // Missing directories are only created on request
+        let err = parsed
+            .write_to_file(&path, FileOptions::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        let create_dirs = FileOptions {
+            create_dirs: true,
+            ..Default::default()
+        };
+        parsed.write_to_file(&path, create_dirs).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), parsed.to_string());
+
+        // Existing files are kept unless overwriting is allowed
+        let mut changed = parsed.clone();
+        changed.edges[0].cost = 7.0;
+        let err = changed.write_to_file(&path, create_dirs).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), parsed.to_string());
+        let overwrite = FileOptions {
+            overwrite: true,
+            ..Default::default()
+        };
+        changed.write_to_file(&path, overwrite).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), changed.to_string());
+
+        let gr_path = dir.join("sample.gr");
+        parsed
+            .write_gr_to_file(&gr_path, GrCosts::default(), FileOptions::default())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&gr_path).unwrap(),
+            parsed.to_gr_string(GrCosts::default()).unwrap()
+        );
+        let err = parsed
+            .write_gr_to_file(&gr_path, GrCosts::default(), FileOptions::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+
+        // A failed export leaves no partial file behind
+        changed.edges[0].cost = 0.5;
+        let fractional = dir.join("fractional.gr");
+        assert!(
+            changed
+                .write_gr_to_file(&fractional, GrCosts::default(), FileOptions::default())
+                .is_err()
+        );
+        assert!(!fractional.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cost_formatting() {
+        let mut instance = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        instance.edges[1].cost = 0.5;
+        instance.edges[2].cost = 0.1 + 0.2;
+        let lines = |instance: &SteinerInstance, options: &ExportOptions| {
+            instance
+                .to_stp_string_with(options)
+                .lines()
+                .filter(|line| line.starts_with("E "))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        // Lossless output keeps every bit of the pathological cost
+        let lossless = ExportOptions::default();
+        assert_eq!(
+            lines(&instance, &lossless),
+            ["E 1 2 1", "E 2 3 0.5", "E 1 3 0.30000000000000004"]
+        );
+        let reparsed = Parser::default()
+            .parse_stp(&instance.to_stp_string_with(&lossless))
+            .unwrap();
+        assert_eq!(reparsed.edges[2].cost, 0.1 + 0.2);
+
+        let precise = |decimals| ExportOptions {
+            costs: CostFormat::Precision(decimals),
+            ..Default::default()
+        };
+        assert_eq!(
+            lines(&instance, &precise(6)),
+            ["E 1 2 1", "E 2 3 0.5", "E 1 3 0.3"]
+        );
+        assert_eq!(
+            lines(&instance, &precise(0)),
+            ["E 1 2 1", "E 2 3 0", "E 1 3 0"]
+        );
+
+        instance.edges[0].cost = -0.0001;
+        instance.edges[1].cost = 1e20;
+        assert_eq!(
+            lines(&instance, &precise(2)),
+            ["E 1 2 0", "E 2 3 100000000000000000000", "E 1 3 0.3"]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let json = serde_json::to_string(&parsed).unwrap();
+        let restored: SteinerInstance = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_string(), parsed.to_string());
+
+        let edge = Edge {
+            from: 1,
+            to: 2,
+            cost: 1.0,
+        };
+        let updates = vec![
+            UpdateOperation::EdgeInsertion(edge.clone()),
+            UpdateOperation::EdgeDeletion(edge),
+            UpdateOperation::VertexInsertion,
+            UpdateOperation::VertexDeletion(3),
+            UpdateOperation::TerminalActivation(2),
+            UpdateOperation::TerminalDeactivation(2),
+            UpdateOperation::Query(Box::new(parsed)),
+        ];
+        assert_eq!(
+            serde_json::to_string(&updates[0]).unwrap(),
+            r#"{"type":"EdgeInsertion","from":1,"to":2,"cost":1.0}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&updates[3]).unwrap(),
+            r#"{"type":"VertexDeletion","vertex":3}"#
+        );
+
+        let json = serde_json::to_string(&updates).unwrap();
+        let restored: Vec<UpdateOperation> = serde_json::from_str(&json).unwrap();
+        assert_eq!(updates_to_dus(&restored), updates_to_dus(&updates));
+        assert_eq!(
+            export_update_sequence(restored).1,
+            export_update_sequence(updates).1
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        let with_metadata = SAMPLE_STP.replacen(
+            "SECTION Graph",
+            "SECTION Comment\nName \"sample\"\nEND\n\nSECTION Graph",
+            1,
+        );
+        let mut parsed = Parser::default().parse_stp(&with_metadata).unwrap();
+        parsed
+            .coordinates
+            .insert(1, Coordinate::Planar { x: 0.0, y: 1.5 });
+        parsed.coordinates.insert(
+            3,
+            Coordinate::Spatial {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            },
+        );
+
+        let json = parsed.to_json();
+        assert!(json.starts_with(r#"{"nodes":3,"edges":[{"from":1,"to":2,"cost":1.0},"#));
+        assert!(json.contains(r#""terminals":[1,3]"#));
+        assert!(json.contains(r#""metadata":{"name":"sample"}"#));
+        let restored = SteinerInstance::from_json(&json).unwrap();
+        assert_eq!(restored.to_string(), parsed.to_string());
+
+        // Optional parts may be left out
+        let minimal = SteinerInstance::from_json(
+            r#"{"nodes": 2, "edges": [{"from": 1, "to": 2, "cost": 4}], "terminals": [2]}"#,
+        )
+        .unwrap();
+        assert_eq!(minimal.num_edges, 1);
+        assert_eq!(minimal.num_terminals, 1);
+        assert!(minimal.coordinates.is_empty());
+
+        // The invariants of the STP parser are enforced with the same errors
+        let err = SteinerInstance::from_json(
+            r#"{"nodes": 2, "edges": [{"from": 1, "to": 3, "cost": 1}], "terminals": []}"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::NodeOutOfRange {
+                node: 3,
+                num_nodes: 2
+            }
+        );
+        assert_eq!(err.line, "edges[0]");
+        let err = SteinerInstance::from_json(
+            r#"{"nodes": 2, "edges": [{"from": 1, "to": 2, "cost": -1}], "terminals": []}"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidCost(-1.0));
+        let err = SteinerInstance::from_json(r#"{"nodes": 2, "edges": [], "terminals": [0]}"#)
+            .unwrap_err();
+        assert_eq!(err.line, "terminals[0]");
+
+        let err = SteinerInstance::from_json("{\n\"nodes\": 2,\n\"edges\": 7}").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::InvalidJson(_)));
+        assert_eq!(err.line_number, 3);
+    }
+
+    #[test]
+    fn graphml_export() {
+        let mut parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        parsed.metadata.name = Some("a <b> & \"c\"".to_string());
+        parsed.arcs.push(Edge {
+            from: 3,
+            to: 1,
+            cost: 0.5,
+        });
+        parsed
+            .coordinates
+            .insert(2, Coordinate::Planar { x: 1.0, y: -2.5 });
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="terminal" for="node" attr.name="terminal" attr.type="boolean"><default>false</default></key>
+  <key id="x" for="node" attr.name="x" attr.type="double"/>
+  <key id="y" for="node" attr.name="y" attr.type="double"/>
+  <key id="cost" for="edge" attr.name="cost" attr.type="double"/>
+  <graph id="G" edgedefault="undirected">
+    <desc>a &lt;b&gt; &amp; &quot;c&quot;</desc>
+    <node id="n1"><data key="terminal">true</data></node>
+    <node id="n2"><data key="x">1</data><data key="y">-2.5</data></node>
+    <node id="n3"><data key="terminal">true</data></node>
+    <edge id="e1" source="n1" target="n2"><data key="cost">1</data></edge>
+    <edge id="e2" source="n2" target="n3"><data key="cost">2</data></edge>
+    <edge id="e3" source="n1" target="n3"><data key="cost">3</data></edge>
+    <edge id="e4" source="n3" target="n1" directed="true"><data key="cost">0.5</data></edge>
+  </graph>
+</graphml>
+"#;
+        assert_eq!(parsed.to_graphml().unwrap(), expected);
+
+        // Without coordinates there are no position attributes
+        let plain = Parser::default()
+            .parse_stp(SAMPLE_STP)
+            .unwrap()
+            .to_graphml()
+            .unwrap();
+        assert!(!plain.contains(r#"key id="x""#));
+        assert!(!plain.contains("<desc>"));
+
+        // Nothing is written for edges to undeclared nodes or costs that
+        // aren't valid GraphML doubles
+        let refused = |instance: &SteinerInstance| {
+            let mut output = Vec::new();
+            let err = instance.write_graphml(&mut output).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            assert!(output.is_empty());
+        };
+        let mut dangling = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        dangling.edges[1].to = 4;
+        refused(&dangling);
+        for cost in [f64::INFINITY, f64::NAN] {
+            let mut infinite = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+            infinite.edges[0].cost = cost;
+            refused(&infinite);
+        }
+        parsed.coordinates.insert(
+            3,
+            Coordinate::Planar {
+                x: f64::NAN,
+                y: 0.0,
+            },
+        );
+        refused(&parsed);
+    }
+
+    #[test]
+    fn dot_export() {
+        let mut parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        parsed.arcs.push(Edge {
+            from: 3,
+            to: 2,
+            cost: 0.5,
+        });
+        let expected = r#"graph "steiner" {
+  node [shape=circle];
+  1 [style=filled, fillcolor=gold, shape=doublecircle];
+  2;
+  3 [style=filled, fillcolor=gold, shape=doublecircle];
+  1 -- 2 [label="1"];
+  2 -- 3 [label="2"];
+  1 -- 3 [label="3"];
+  3 -- 2 [dir=forward, label="0.5"];
+}
+"#;
+        assert_eq!(parsed.to_dot(&DotOptions::default()), expected);
+
+        // Highlighted edges match in either orientation, arcs only as given
+        let tree = vec![
+            Edge {
+                from: 2,
+                to: 1,
+                cost: 1.0,
+            },
+            Edge {
+                from: 2,
+                to: 3,
+                cost: 0.5,
+            },
+        ];
+        parsed.metadata.name = Some("say \"hi\"".to_string());
+        let dot = parsed.to_dot(&DotOptions {
+            edge_labels: false,
+            highlight: tree,
+        });
+        assert!(dot.starts_with("graph \"say \\\"hi\\\"\" {\n"));
+        assert!(dot.contains("  1 -- 2 [color=red, penwidth=2];\n"));
+        assert!(dot.contains("  2 -- 3 [color=red, penwidth=2];\n"));
+        assert!(dot.contains("  1 -- 3;\n"));
+        assert!(dot.contains("  3 -- 2 [dir=forward];\n"));
+
+        // Every statement is terminated and the braces are balanced
+        let body: Vec<&str> = dot.lines().skip(1).collect();
+        assert_eq!(body.last(), Some(&"}"));
+        assert!(
+            body[..body.len() - 1]
+                .iter()
+                .all(|line| line.ends_with(';'))
+        );
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let options = CsvOptions::default();
+        let (edges, terminals) = parsed.to_csv(&options).unwrap();
+        assert_eq!(edges, "1,2,1\n2,3,2\n1,3,3\n");
+        assert_eq!(terminals, "1\n3\n");
+        let restored =
+            SteinerInstance::from_csv(edges.as_bytes(), terminals.as_bytes(), Some(3), &options)
+                .unwrap();
+        assert_eq!(restored.to_string(), parsed.to_string());
+
+        // Semicolons with a header row, and the node count taken from the data
+        let options = CsvOptions {
+            delimiter: ';',
+            header: true,
+        };
+        let (edges, terminals) = parsed.to_csv(&options).unwrap();
+        assert!(edges.starts_with("from;to;cost\n1;2;1\n"));
+        assert!(terminals.starts_with("terminal\n"));
+        let restored =
+            SteinerInstance::from_csv(edges.as_bytes(), terminals.as_bytes(), None, &options)
+                .unwrap();
+        assert_eq!(restored.num_nodes, 3);
+        assert_eq!(restored.to_string(), parsed.to_string());
+        let restored = SteinerInstance::from_csv(
+            "from;to;cost\n1 ; 2 ; 0.5\n\n".as_bytes(),
+            "terminal\n7\n".as_bytes(),
+            None,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(restored.num_nodes, 7);
+        assert_eq!(restored.edges[0].cost, 0.5);
+
+        // Bad rows give the errors of the STP parser, with their line
+        let parse_error = |edges: &str, terminals: &str| match SteinerInstance::from_csv(
+            edges.as_bytes(),
+            terminals.as_bytes(),
+            Some(3),
+            &CsvOptions::default(),
+        ) {
+            Err(StpError::Parse(err)) => err,
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+        };
+        let err = parse_error("1,2,1\n2,4,1\n", "");
+        assert_eq!(err.line_number, 2);
+        assert_eq!(err.line, "2,4,1");
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::NodeOutOfRange {
+                node: 4,
+                num_nodes: 3
+            }
+        );
+        let err = parse_error("1,2,1\n", "0\n");
+        assert_eq!(err.kind.to_string(), "node 0 is outside of 1..=3");
+        let err = parse_error("1,2\n", "");
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::MissingArgument {
+                expected: "edge cost"
+            }
+        );
+        let err = parse_error("1,x,1\n", "");
+        assert!(matches!(err.kind, ParseErrorKind::InvalidArgument { .. }));
+        let err = parse_error("1,2,-1\n", "");
+        assert_eq!(err.kind, ParseErrorKind::InvalidCost(-1.0));
+    }
+
+    #[test]
+    fn petgraph_conversion() {
+        let mut parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        // Node 4 is isolated but still part of the graph
+        parsed.num_nodes = 4;
+
+        let graph = parsed.to_petgraph().unwrap();
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(graph.edge_count(), 3);
+        assert_eq!(graph[NodeIndex::new(3)], 4);
+        let (a, b) = (NodeIndex::new(1), NodeIndex::new(2));
+        let edge = graph.find_edge(b, a).unwrap();
+        assert_eq!(graph[edge], 2.0);
+        let costs: Vec<f64> = graph.edge_weights().copied().collect();
+        assert_eq!(costs, [1.0, 2.0, 3.0]);
+
+        parsed.arcs.push(Edge {
+            from: 4,
+            to: 1,
+            cost: 0.5,
+        });
+        let directed = parsed.to_petgraph_directed().unwrap();
+        assert_eq!(directed.node_count(), 4);
+        assert_eq!(directed.edge_count(), 7);
+        let arc = directed
+            .find_edge(NodeIndex::new(3), NodeIndex::new(0))
+            .unwrap();
+        assert_eq!(directed[arc], 0.5);
+        assert!(
+            directed
+                .find_edge(NodeIndex::new(0), NodeIndex::new(3))
+                .is_none()
+        );
+        assert!(directed.find_edge(b, a).is_some() && directed.find_edge(a, b).is_some());
+
+        // The lenient parser accepts endpoints that aren't nodes, they are
+        // refused instead of overflowing the index
+        for (line, from, to) in [("E 0 3 2.5", 0, 3), ("E 3 9 2.5", 3, 9)] {
+            let stp = SAMPLE_STP.replace("E 1 3 3", line);
+            let lenient = Parser::default().parse_stp(&stp).unwrap();
+            let err = ConversionError::EdgeOutOfRange {
+                from,
+                to,
+                nodes: 1..4,
+            };
+            assert_eq!(lenient.to_petgraph().unwrap_err(), err);
+            assert_eq!(lenient.to_petgraph_directed().unwrap_err(), err);
+        }
+        let mut bad_arc = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        bad_arc.arcs.push(Edge {
+            from: 2,
+            to: 0,
+            cost: 1.0,
+        });
+        let err = bad_arc.to_petgraph_directed().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "edge (2, 0) has an endpoint outside of the nodes 1..4"
+        );
+        // Node 0 is fine once the instance is 0-based, num_nodes no longer
+        let mut zero = Parser::default()
+            .parse_stp(SAMPLE_STP)
+            .unwrap()
+            .to_zero_indexed()
+            .unwrap();
+        assert!(zero.to_petgraph().is_ok());
+        zero.edges.push(Edge {
+            from: 0,
+            to: 3,
+            cost: 1.0,
+        });
+        assert_eq!(
+            zero.to_petgraph().unwrap_err(),
+            ConversionError::EdgeOutOfRange {
+                from: 0,
+                to: 3,
+                nodes: 0..3
+            }
+        );
+    }
+
+    #[test]
+    fn instance_from_petgraph() {
+        let mut graph = UnGraph::<&str, f64>::new_undirected();
+        let a = graph.add_node("a");
+        let b = graph.add_node("b");
+        let c = graph.add_node("c");
+        let d = graph.add_node("d");
+        graph.add_edge(a, b, 1.5);
+        graph.add_edge(c, b, 2.0);
+        graph.add_edge(a, c, 4.0);
+
+        let instance = SteinerInstance::from_petgraph(&graph, &[a, c]);
+        assert_eq!(instance.num_nodes, 4);
+        assert_eq!(instance.num_edges, 3);
+        assert_eq!(instance.num_terminals, 2);
+        assert_eq!(instance.terminals, [1, 3]);
+        assert!(edge_eq(
+            &instance.edges[1],
+            &Edge {
+                from: 3,
+                to: 2,
+                cost: 2.0
+            }
+        ));
+
+        // Converting back yields the same structure and costs
+        let back = instance.to_petgraph().unwrap();
+        assert!(is_isomorphic_matching(
+            &graph,
+            &back,
+            |_, _| true,
+            |x, y| x == y
+        ));
+        assert_eq!(back[d], 4);
+
+        // Self-loops and parallel edges are copied, or refused by TryFrom
+        assert!(SteinerInstance::try_from(&graph).is_ok());
+        graph.add_edge(b, a, 1.0);
+        let with_parallel = SteinerInstance::from_petgraph(&graph, &[]);
+        assert_eq!(with_parallel.num_edges, 4);
+        assert_eq!(
+            SteinerInstance::try_from(&graph).unwrap_err().kind,
+            ParseErrorKind::DuplicateEdge { from: 2, to: 1 }
+        );
+        let mut with_loop = UnGraph::<(), f64>::new_undirected();
+        let node = with_loop.add_node(());
+        with_loop.add_edge(node, node, 1.0);
+        assert_eq!(
+            SteinerInstance::try_from(&with_loop).unwrap_err().kind,
+            ParseErrorKind::SelfLoop { node: 1 }
+        );
+    }
+
+    #[test]
+    fn instance_builder() {
+        let instance = SteinerInstance::builder()
+            .nodes(4)
+            .edge(1, 2, 1.0)
+            .edge(2, 3, 2.5)
+            .arc(3, 4, 1.0)
+            .terminal(1)
+            .terminal(3)
+            .terminal(1)
+            .coordinate(4, 1.0, 2.0)
+            .build()
+            .unwrap();
+        assert_eq!(instance.num_nodes, 4);
+        assert_eq!(instance.num_edges, 2);
+        assert_eq!(instance.num_arcs, 1);
+        assert_eq!(instance.num_terminals, 2);
+        assert_eq!(instance.terminals, [1, 3]);
+        assert_eq!(
+            instance.coordinates[&4],
+            Coordinate::Planar { x: 1.0, y: 2.0 }
+        );
+        assert!(instance.check_counts().is_empty());
+
+        // Errors name the offending call
+        let err = SteinerInstance::builder()
+            .nodes(5)
+            .edge(7, 2, 1.0)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "edge (7, 2) references node 7 but only 5 nodes declared"
+        );
+        let err = SteinerInstance::builder()
+            .nodes(2)
+            .arc(1, 0, 1.0)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            BuildError::ArcOutOfRange {
+                from: 1,
+                to: 0,
+                node: 0,
+                num_nodes: 2
+            }
+        );
+        let err = SteinerInstance::builder()
+            .nodes(2)
+            .terminal(3)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "terminal 3 is not a node, only 2 nodes declared"
+        );
+
+        // Self-loops and parallel edges follow the policies
+        let mut builder = SteinerInstance::builder();
+        builder
+            .nodes(3)
+            .edge(1, 1, 1.0)
+            .edge(1, 2, 3.0)
+            .edge(2, 1, 2.0);
+        let built = builder.build().unwrap();
+        assert_eq!(built.num_edges, 2);
+        builder.self_loops(SelfLoopPolicy::Error);
+        assert_eq!(
+            builder.build().unwrap_err(),
+            BuildError::SelfLoop { node: 1 }
+        );
+        builder.self_loops(SelfLoopPolicy::Drop);
+        builder.duplicate_edges(DuplicateEdgePolicy::KeepMinimum);
+        let built = builder.build().unwrap();
+        assert_eq!(built.num_edges, 1);
+        assert_eq!(built.edges[0].cost, 2.0);
+        builder.duplicate_edges(DuplicateEdgePolicy::Error);
+        assert_eq!(
+            builder.build().unwrap_err().to_string(),
+            "duplicate edges: (2, 1)"
+        );
+    }
+
+    #[test]
+    fn instance_validation() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert_eq!(parsed.validate(), Ok(()));
+        let connected = ValidationOptions { connectivity: true };
+        assert_eq!(parsed.validate_with(&connected), Ok(()));
+
+        let mut broken = parsed.clone();
+        broken.edges.push(Edge {
+            from: 2,
+            to: 2,
+            cost: -1.0,
+        });
+        broken.arcs.push(Edge {
+            from: 1,
+            to: 9,
+            cost: f64::NAN,
+        });
+        broken.num_arcs = 1;
+        broken.terminals.push(3);
+        broken.num_terminals = 3;
+        let issues = broken.validate().unwrap_err();
+        let kinds: Vec<ValidationIssueKind> = issues.iter().map(|issue| issue.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ValidationIssueKind::CountMismatch,
+                ValidationIssueKind::SelfLoop,
+                ValidationIssueKind::InvalidCost,
+                ValidationIssueKind::NodeOutOfRange,
+                ValidationIssueKind::InvalidCost,
+                ValidationIssueKind::DuplicateTerminal,
+            ]
+        );
+        assert_eq!(issues[0].to_string(), "expected 3 edges, found 4");
+        assert_eq!(
+            issues[3].message,
+            "arc (1, 9) references node 9 outside of 1..=3"
+        );
+
+        // Connectivity is only checked on request
+        let mut split = parsed.clone();
+        split.num_nodes = 4;
+        split.terminals.push(4);
+        split.num_terminals = 3;
+        assert_eq!(split.validate(), Ok(()));
+        let issues = split.validate_with(&connected).unwrap_err();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, ValidationIssueKind::DisconnectedTerminals);
+        assert_eq!(issues[0].message, "terminals 1 and 4 are not connected");
+    }
+
+    #[test]
+    fn semantic_instance_equality() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let reordered = Parser::default()
+            .parse_stp(
+                &SAMPLE_STP
+                    .replace("E 1 2 1", "E 3 1 3")
+                    .replacen("E 1 3 3", "E 2 1 1", 1)
+                    .replace("T 1\n", "T 3\n")
+                    .replace("T 3\n    END", "T 1\n    END"),
+            )
+            .unwrap();
+        assert_ne!(reordered.edges, parsed.edges);
+        assert_eq!(reordered, parsed);
+        assert_eq!(parsed.clone(), parsed);
+
+        // Costs are compared exactly, while edges alone compare by endpoints
+        let mut changed = parsed.clone();
+        changed.edges[0].cost += 1e-12;
+        assert_eq!(changed.edges[0], parsed.edges[0]);
+        assert_ne!(changed, parsed);
+
+        // Parallel edges count as often as they occur
+        let mut doubled = parsed.clone();
+        doubled.edges.push(parsed.edges[0].clone());
+        let mut other = parsed.clone();
+        other.edges.push(parsed.edges[1].clone());
+        doubled.num_edges = 4;
+        other.num_edges = 4;
+        assert_ne!(doubled, other);
+
+        let mut flipped = parsed.clone();
+        flipped.arcs.push(Edge {
+            from: 1,
+            to: 2,
+            cost: 1.0,
+        });
+        let mut reversed = parsed.clone();
+        reversed.arcs.push(Edge {
+            from: 2,
+            to: 1,
+            cost: 1.0,
+        });
+        assert_ne!(flipped, reversed);
+    }
+
+    #[test]
+    fn undirected_edge_identity() {
+        let edge = |from, to| Edge {
+            from,
+            to,
+            cost: 1.0,
+        };
+        assert_eq!(edge(2, 1).normalized(), edge(1, 2));
+        assert_eq!(edge(1, 2).normalized(), edge(1, 2));
+        assert_ne!(edge(2, 1), edge(1, 2));
+
+        let mut edges = vec![edge(3, 1), edge(1, 2), edge(2, 1), edge(1, 1)];
+        edges.sort();
+        assert_eq!(edges, [edge(1, 1), edge(1, 2), edge(2, 1), edge(3, 1)]);
+        let normalized: HashSet<Edge> = edges.iter().map(Edge::normalized).collect();
+        assert_eq!(normalized.len(), 3);
+        assert!(normalized.contains(&edge(2, 1).normalized()));
+
+        // An edge stored as (2, 1) can be deleted when (1, 2) is picked
+        let instance = SteinerInstance::new(2, vec![edge(2, 1)], vec![1]);
+        let deletions_only = UpdateProbabilities::edges_only(0.0, 1.0);
+        let updates =
+            generate_update_sequence(&instance, deletions_only, 0.0, vec![1, 2], false, 1).unwrap();
+        assert!(matches!(
+            &updates[0],
+            UpdateOperation::EdgeDeletion(deleted) if *deleted == edge(1, 2)
+        ));
+        let UpdateOperation::Query(last) = updates.last().unwrap() else {
+            panic!("update sequences end with a query");
+        };
+        assert!(last.edges.is_empty());
+
+        // Inserting (1, 2) after deleting it brings it back
+        let churn = UpdateProbabilities::edges_only(1.0, 1.0);
+        let updates =
+            generate_update_sequence(&instance, churn, 0.0, vec![1, 2], false, 6).unwrap();
+        let mut present = true;
+        for update in &updates {
+            match update {
+                UpdateOperation::EdgeInsertion(inserted) => {
+                    assert!(!present);
+                    assert_eq!(*inserted, edge(1, 2));
+                    present = true;
+                }
+                UpdateOperation::EdgeDeletion(_) => {
+                    assert!(present);
+                    present = false;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    #[test]
+    fn normalized_export() {
+        let shuffled = "
+            SECTION Terminals
+            Terminals 2
+            T 3
+            T 1
+            T 3
+            END
+
+            SECTION Graph
+            Nodes 3
+            Edges 3
+            E 3 1 3
+            E 3 2 2
+            E 1 2 1
+            END
+
+            EOF
+        ";
+        let mut parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let mut reordered = Parser::default().parse_stp(shuffled).unwrap();
+        assert_ne!(parsed.to_string(), reordered.to_string());
+
+        parsed.normalize();
+        reordered.normalize();
+        assert_eq!(parsed.to_string(), reordered.to_string());
+        assert_eq!(reordered.terminals, [1, 3]);
+        assert_eq!(reordered.num_terminals, 2);
+        assert!(reordered.check_counts().is_empty());
+        assert!(reordered.edges.iter().map(|edge| (edge.from, edge.to)).eq([
+            (1, 2),
+            (1, 3),
+            (2, 3)
+        ]));
+
+        // Parallel edges are ordered by cost and arcs keep their direction
+        let mut instance = SteinerInstance::builder()
+            .nodes(3)
+            .edge(2, 1, 5.0)
+            .edge(1, 2, 4.0)
+            .arc(3, 1, 1.0)
+            .arc(1, 3, 2.0)
+            .build()
+            .unwrap();
+        instance.normalize();
+        assert_eq!(instance.edges[0].cost, 4.0);
+        assert_eq!((instance.edges[1].from, instance.edges[1].to), (1, 2));
+        assert_eq!((instance.arcs[0].from, instance.arcs[0].to), (1, 3));
+        assert_eq!((instance.arcs[1].from, instance.arcs[1].to), (3, 1));
+    }
+
+    #[test]
+    fn content_fingerprint() {
+        let parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let fingerprint = parsed.fingerprint();
+        assert_eq!(parsed.checksum_string(), format!("{:016x}", fingerprint));
+        // Pinned, since fingerprints must not change between versions
+        assert_eq!(fingerprint, 0x3294_c175_f2b2_346c);
+
+        let reformatted = Parser::default()
+            .parse_stp(
+                &SAMPLE_STP
+                    .replace("E 1 2 1", "E 1 2 swap")
+                    .replace("E 1 3 3", "E 2 1 1.000")
+                    .replace("E 1 2 swap", "E 1 3 3.0"),
+            )
+            .unwrap();
+        assert_ne!(reformatted.edges, parsed.edges);
+        assert_eq!(reformatted.fingerprint(), fingerprint);
+
+        let mut changed = parsed.clone();
+        changed.edges[2].cost = 3.0000001;
+        assert_ne!(changed.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn adjacency_view() {
+        let mut parsed = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        // Node 4 is isolated
+        parsed.num_nodes = 4;
+        let adjacency = parsed.adjacency();
+
+        let mut neighbors: Vec<(usize, f64)> = adjacency.neighbors(3).collect();
+        neighbors.sort_by_key(|&(node, _)| node);
+        assert_eq!(neighbors, [(1, 3.0), (2, 2.0)]);
+        assert_eq!(adjacency.neighbors(4).count(), 0);
+        assert_eq!(adjacency.neighbors(0).count(), 0);
+        assert_eq!(adjacency.neighbors(9).count(), 0);
+        let incident: Vec<&Edge> = adjacency.incident_edges(2).collect();
+        assert_eq!(incident, [&parsed.edges[0], &parsed.edges[1]]);
+
+        assert!(adjacency.has_edge(1, 2) && adjacency.has_edge(2, 1));
+        assert!(adjacency.has_edge(3, 1));
+        assert!(!adjacency.has_edge(1, 4));
+
+        let n = 1000;
+        let path = path_instance(n);
+        let adjacency = path.adjacency();
+        assert_eq!(adjacency.neighbors(1).collect::<Vec<_>>(), [(2, 1.0)]);
+        assert_eq!(adjacency.neighbors(n / 2).count(), 2);
+        assert_eq!(adjacency.neighbors(n).collect::<Vec<_>>(), [(n - 1, 1.0)]);
+    }
+
+    #[test]
+    #[ignore = "timing based, run with --ignored"]
+    fn large_adjacency_view() {
+        // Building the view is linear in the size of the instance
+        let n = 1_000_000;
+        let path = path_instance(n);
+        let start = std::time::Instant::now();
+        let adjacency = path.adjacency();
+        let elapsed = start.elapsed();
+
+        assert_eq!(adjacency.neighbors(1).collect::<Vec<_>>(), [(2, 1.0)]);
+        assert_eq!(adjacency.neighbors(n / 2).count(), 2);
+        assert!(
+            elapsed < std::time::Duration::from_secs(10),
+            "building the adjacency of {} edges took {:?}",
+            n - 1,
+            elapsed
+        );
+    }
+
+    #[test]
+    fn degree_queries() {
+        let mut instance = SteinerInstance::builder()
+            .nodes(5)
+            .edge(1, 2, 1.0)
+            .edge(2, 3, 1.0)
+            .edge(3, 1, 1.0)
+            .arc(4, 1, 1.0)
+            .arc(4, 2, 1.0)
+            .terminal(1)
+            .terminal(4)
+            .terminal(5)
+            .build()
+            .unwrap();
+        assert_eq!(instance.degree(1), 2);
+        assert_eq!(instance.degree(4), 0);
+        assert_eq!(instance.degrees(), [2, 2, 2, 0, 0]);
+        assert_eq!(instance.out_degree(4), 2);
+        assert_eq!(instance.in_degree(1), 1);
+        assert_eq!(instance.in_degree(4), 0);
+        assert_eq!(
+            instance.degree_histogram(),
+            BTreeMap::from([(0, 2), (2, 3)])
+        );
+        // Node 4 has arcs, node 5 nothing at all
+        assert_eq!(instance.isolated_terminals(), [5]);
+
+        // Self-loops count twice
+        instance.edges.push(Edge {
+            from: 5,
+            to: 5,
+            cost: 1.0,
+        });
+        assert_eq!(instance.degree(5), 2);
+        assert_eq!(instance.degrees()[4], 2);
+        assert!(instance.isolated_terminals().is_empty());
+    }
+
+    #[test]
+    fn connected_components() {
+        let mut instance = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(instance.is_connected());
+        assert_eq!(instance.connected_components(), [vec![1, 2, 3]]);
+
+        instance.edges.retain(|edge| edge.to != 3 && edge.from != 3);
+        assert!(!instance.is_connected());
+        assert_eq!(instance.connected_components(), [vec![1, 2], vec![3]]);
+
+        // Arcs connect in either direction, unused nodes stay alone
+        instance.num_nodes = 5;
+        instance.arcs.push(Edge {
+            from: 4,
+            to: 2,
+            cost: 1.0,
+        });
+        assert_eq!(
+            instance.connected_components(),
+            [vec![1, 2, 4], vec![3], vec![5]]
+        );
+
+        assert!(SteinerInstance::new(0, vec![], vec![]).is_connected());
+    }
+
+    #[test]
+    fn terminal_connectivity() {
+        let mut instance = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert!(instance.terminals_connected());
+
+        instance.edges.retain(|edge| edge.to != 3 && edge.from != 3);
+        assert!(!instance.terminals_connected());
+        assert_eq!(instance.unreachable_terminals(), [3]);
+
+        // Terminal 2 is reached, 7 is not a node
+        instance.terminals = vec![1, 2, 7];
+        assert_eq!(instance.unreachable_terminals(), [7]);
+
+        instance.terminals = vec![3];
+        assert!(instance.terminals_connected());
+        instance.terminals.clear();
+        assert!(instance.terminals_connected());
+        instance.terminals = vec![7];
+        assert_eq!(instance.unreachable_terminals(), [7]);
+    }
+
+    #[test]
+    fn dijkstra_shortest_paths() {
+        let mut instance = SteinerInstance::builder()
+            .nodes(5)
+            .edge(1, 2, 4.0)
+            .edge(1, 3, 1.0)
+            .edge(3, 2, 2.0)
+            .edge(2, 4, 5.0)
+            .edge(3, 4, 8.0)
+            .build()
+            .unwrap();
+
+        // Node 5 has no edges
+        let inf = f64::INFINITY;
+        assert_eq!(
+            instance.shortest_paths(1).unwrap(),
+            [0.0, 3.0, 1.0, 8.0, inf]
+        );
+        assert_eq!(
+            instance.shortest_paths(4).unwrap(),
+            [8.0, 5.0, 7.0, 0.0, inf]
+        );
+        assert_eq!(
+            instance.shortest_paths(5).unwrap(),
+            [inf, inf, inf, inf, 0.0]
+        );
+
+        let paths = instance.shortest_paths_with_predecessors(1).unwrap();
+        assert_eq!(paths.path_to(4), Some(vec![1, 3, 2, 4]));
+        assert_eq!(paths.path_to(1), Some(vec![1]));
+        assert_eq!(paths.path_to(5), None);
+        assert_eq!(paths.predecessors[0], None);
+
+        // Arcs only lead one way
+        instance.arcs.push(Edge {
+            from: 4,
+            to: 5,
+            cost: 1.0,
+        });
+        assert_eq!(instance.shortest_paths(1).unwrap()[4], 9.0);
+        assert_eq!(instance.shortest_paths(5).unwrap()[0], inf);
+
+        assert_eq!(
+            instance.shortest_paths(6),
+            Err(PathError::SourceOutOfRange {
+                source: 6,
+                num_nodes: 5
+            })
+        );
+        instance.edges[0].cost = -1.0;
+        assert!(matches!(
+            instance.shortest_paths(1),
+            Err(PathError::InvalidCost { from: 1, to: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn terminal_metric_closure() {
+        // The direct edges between terminals are more expensive than the
+        // detours over node 4
+        let mut instance = SteinerInstance::builder()
+            .nodes(4)
+            .edge(1, 2, 5.0)
+            .edge(2, 3, 10.0)
+            .edge(1, 4, 1.0)
+            .edge(2, 4, 1.0)
+            .edge(3, 4, 3.0)
+            .terminal(3)
+            .terminal(1)
+            .terminal(2)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            instance.terminal_distance_matrix().unwrap(),
+            [[0.0, 4.0, 4.0], [4.0, 0.0, 2.0], [4.0, 2.0, 0.0]]
+        );
+
+        let (closure, nodes) = instance.metric_closure().unwrap();
+        assert_eq!(nodes, [3, 1, 2]);
+        assert_eq!(closure.num_nodes, 3);
+        assert_eq!(closure.terminals, [1, 2, 3]);
+        let costs: Vec<_> = closure
+            .edges
+            .iter()
+            .map(|edge| (nodes[edge.from - 1], nodes[edge.to - 1], edge.cost))
+            .collect();
+        assert_eq!(costs, [(3, 1, 4.0), (3, 2, 4.0), (1, 2, 2.0)]);
+
+        instance.num_nodes = 5;
+        instance.terminals.push(5);
+        assert_eq!(
+            instance.metric_closure(),
+            Err(PathError::Unreachable { from: 3, to: 5 })
+        );
+
+        // The lenient parser accepts terminal 0, it is refused before any
+        // distance is looked up
+        let stp = SAMPLE_STP.replace("T 3", "T 0");
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        let out_of_range = PathError::SourceOutOfRange {
+            source: 0,
+            num_nodes: 3,
+        };
+        assert_eq!(parsed.terminal_distance_matrix(), Err(out_of_range.clone()));
+        assert_eq!(parsed.metric_closure().unwrap_err(), out_of_range);
+        instance.terminals.push(7);
+        assert_eq!(
+            instance.metric_closure().unwrap_err(),
+            PathError::SourceOutOfRange {
+                source: 7,
+                num_nodes: 5
+            }
+        );
+    }
+
+    #[test]
+    fn approximate_steiner_tree() {
+        // The tree spans the terminals and has no cycles
+        let assert_tree = |instance: &SteinerInstance, tree: &SteinerTree| {
+            let spanned = SteinerInstance::new(
+                instance.num_nodes,
+                tree.edges.clone(),
+                instance.terminals.clone(),
+            );
+            assert!(spanned.terminals_connected());
+            let nodes: HashSet<_> = tree.edges.iter().flat_map(|e| [e.from, e.to]).collect();
+            assert_eq!(tree.edges.len() + 1, nodes.len());
+        };
+
+        // The optimum is the star around node 4 with cost 3, the heuristic
+        // may take two of the direct edges between terminals instead
+        let star = SteinerInstance::builder()
+            .nodes(4)
+            .edge(1, 2, 1.9)
+            .edge(1, 3, 1.9)
+            .edge(2, 3, 1.9)
+            .edge(1, 4, 1.0)
+            .edge(2, 4, 1.0)
+            .edge(3, 4, 1.0)
+            .terminal(1)
+            .terminal(2)
+            .terminal(3)
+            .build()
+            .unwrap();
+        let tree = star.approximate_steiner_tree().unwrap();
+        assert_tree(&star, &tree);
+        assert!((3.0..=6.0).contains(&tree.cost));
+
+        // The path over the Steiner nodes 2 and 4 is optimal, the pendant
+        // node 6 is pruned
+        let mut path = SteinerInstance::builder()
+            .nodes(6)
+            .edge(1, 2, 1.0)
+            .edge(2, 3, 1.0)
+            .edge(3, 4, 1.0)
+            .edge(4, 5, 1.0)
+            .edge(1, 5, 10.0)
+            .edge(2, 6, 0.5)
+            .terminal(1)
+            .terminal(3)
+            .terminal(5)
+            .build()
+            .unwrap();
+        let tree = path.approximate_steiner_tree().unwrap();
+        assert_tree(&path, &tree);
+        assert_eq!(tree.cost, 4.0);
+        assert_eq!(tree.edges, path.edges[..4]);
+
+        let single = SteinerInstance::new(2, path.edges[..1].to_vec(), vec![2]);
+        assert_eq!(
+            single.approximate_steiner_tree().unwrap(),
+            SteinerTree::default()
+        );
+
+        path.edges.retain(|edge| edge.from != 4 && edge.to != 4);
+        path.edges.retain(|edge| edge.cost != 10.0);
+        assert_eq!(
+            path.approximate_steiner_tree(),
+            Err(SolveError::DisconnectedTerminals { from: 1, to: 5 })
+        );
+        path.arcs.push(Edge {
+            from: 1,
+            to: 2,
+            cost: 1.0,
+        });
+        assert_eq!(
+            path.approximate_steiner_tree(),
+            Err(SolveError::UnsupportedArcs)
+        );
+    }
+
+    #[test]
+    fn exact_steiner_tree() {
+        // Cheapest subset of the edges that connects the terminals
+        let brute_force = |instance: &SteinerInstance| {
+            let m = instance.edges.len();
+            (0..1u32 << m)
+                .filter_map(|mask| {
+                    let edges: Vec<Edge> = (0..m)
+                        .filter(|i| mask >> i & 1 == 1)
+                        .map(|i| instance.edges[i].clone())
+                        .collect();
+                    let spanned = SteinerInstance::new(
+                        instance.num_nodes,
+                        edges.clone(),
+                        instance.terminals.clone(),
+                    );
+                    spanned
+                        .terminals_connected()
+                        .then(|| edges.iter().map(|edge| edge.cost).sum::<f64>())
+                })
+                .min_by(f64::total_cmp)
+        };
+
+        for _ in 0..10 {
+            let (mut instance, _) =
+                generate_random_with_fixed_vc(6, 3, 3, 0.6, DEFAULT_MAX_ATTEMPTS).unwrap();
+            for edge in &mut instance.edges {
+                edge.cost = ((edge.from * 7 + edge.to * 3) % 5 + 1) as f64;
+            }
+            let tree = instance.solve_exact().unwrap();
+            assert_eq!(Some(tree.cost), brute_force(&instance));
+            assert_eq!(tree.cost, tree.edges.iter().map(|e| e.cost).sum::<f64>());
+            assert!(instance.approximate_steiner_tree().unwrap().cost <= 2.0 * tree.cost);
+        }
+
+        // The optimum takes the Steiner node 4 even though the direct edges
+        // are cheaper than the ones to it
+        let star = SteinerInstance::builder()
+            .nodes(4)
+            .edge(1, 2, 1.9)
+            .edge(1, 3, 1.9)
+            .edge(2, 3, 1.9)
+            .edge(1, 4, 1.0)
+            .edge(2, 4, 1.0)
+            .edge(3, 4, 1.0)
+            .terminal(1)
+            .terminal(2)
+            .terminal(3)
+            .build()
+            .unwrap();
+        let tree = star.solve_exact().unwrap();
+        assert_eq!(tree.cost, 3.0);
+        assert_eq!(tree.edges, star.edges[3..]);
+
+        assert_eq!(
+            star.solve_exact_with(&ExactOptions { max_terminals: 2 }),
+            Err(SolveError::TooManyTerminals {
+                terminals: 3,
+                limit: 2
+            })
+        );
+        let disconnected = SteinerInstance::new(4, star.edges[..1].to_vec(), vec![1, 2, 4]);
+        assert_eq!(
+            disconnected.solve_exact(),
+            Err(SolveError::DisconnectedTerminals { from: 1, to: 4 })
+        );
+    }
+
+    #[test]
+    fn steiner_tree_introspection() {
+        let instance = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let edge = |from, to, cost| Edge { from, to, cost };
+
+        let path = SteinerTree::new(vec![edge(1, 2, 1.0), edge(2, 3, 2.0)]);
+        assert_eq!(path.cost, 3.0);
+        assert_eq!(path.nodes_spanned(), [1, 2, 3]);
+        assert!(path.is_tree());
+        assert!(path.covers_terminals(&instance));
+        assert_eq!(path.to_string(), "VALUE 3\n1 2\n2 3\n");
+
+        let cycle = SteinerTree::new(instance.edges.clone());
+        assert_eq!(cycle.cost, 6.0);
+        assert!(!cycle.is_tree());
+        assert!(cycle.covers_terminals(&instance));
+
+        let forest = SteinerTree::new(vec![edge(1, 2, 1.0), edge(3, 4, 1.0)]);
+        assert!(!forest.is_tree());
+        let partial = SteinerTree::new(vec![edge(1, 2, 1.0)]);
+        assert!(partial.is_tree());
+        assert!(!partial.covers_terminals(&instance));
+
+        let empty = SteinerTree::default();
+        assert!(empty.is_tree());
+        assert!(!empty.covers_terminals(&instance));
+        assert_eq!(empty.to_string(), "VALUE 0\n");
+    }
+
+    #[test]
+    fn solution_verification() {
+        let mut instance = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert_eq!(instance.verify_solution(&[(2, 1), (3, 2)]), Ok(3.0));
+        assert_eq!(instance.verify_solution(&[(1, 3)]), Ok(3.0));
+
+        assert_eq!(
+            instance.verify_solution(&[(1, 2), (2, 4)]),
+            Err(SolutionError::MissingEdge { from: 2, to: 4 })
+        );
+        assert_eq!(
+            instance.verify_solution(&[(1, 2), (2, 1)]),
+            Err(SolutionError::DuplicateEdge { from: 2, to: 1 })
+        );
+        assert_eq!(
+            instance.verify_solution(&[(1, 2)]),
+            Err(SolutionError::UnreachedTerminal {
+                terminal: 3,
+                root: 1
+            })
+        );
+        let cycle = instance
+            .verify_solution(&[(1, 2), (2, 3), (3, 1)])
+            .unwrap_err();
+        assert_eq!(
+            cycle,
+            SolutionError::Cycle {
+                nodes: vec![1, 2, 3, 1]
+            }
+        );
+        assert_eq!(
+            cycle.to_string(),
+            "the edges contain the cycle 1 - 2 - 3 - 1"
+        );
+
+        // The most expensive edge of the cycle is dropped by the forest
+        let options = VerifyOptions { allow_cycles: true };
+        assert_eq!(
+            instance.verify_solution_with(&[(1, 2), (2, 3), (3, 1)], &options),
+            Ok(3.0)
+        );
+
+        // A pendant Steiner node is fine unless cycles are allowed, which
+        // prunes it
+        instance.num_nodes = 5;
+        instance.edges.push(Edge {
+            from: 3,
+            to: 4,
+            cost: 5.0,
+        });
+        instance.edges.push(Edge {
+            from: 5,
+            to: 4,
+            cost: 1.0,
+        });
+        assert_eq!(instance.verify_solution(&[(1, 3), (3, 4)]), Ok(8.0));
+        assert_eq!(
+            instance.verify_solution_with(&[(1, 3), (3, 4)], &options),
+            Ok(3.0)
+        );
+        assert_eq!(
+            instance.verify_solution(&[(1, 3), (4, 5)]),
+            Err(SolutionError::DisconnectedEdge { from: 4, to: 5 })
+        );
+
+        instance.terminals = vec![2];
+        assert_eq!(instance.verify_solution(&[]), Ok(0.0));
+    }
+
+    #[test]
+    fn solution_files() {
+        let instance = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let solution = "c found by hand\nVALUE 3\n\n1 2\n# second edge\n3 2\n";
+
+        let tree = SteinerTree::from_solution_str_checked(solution, &instance).unwrap();
+        assert_eq!(tree.cost, 3.0);
+        assert_eq!(tree.edges[1].from, 3);
+        assert_eq!(tree.edges[1].cost, 2.0);
+        assert_eq!(tree.to_solution_string(), "VALUE 3\n1 2\n3 2\n");
+        let reparsed =
+            SteinerTree::from_solution_str_checked(&tree.to_solution_string(), &instance);
+        assert_eq!(reparsed.unwrap(), tree);
+
+        // Without an instance the costs are only known from the file
+        let unchecked = SteinerTree::from_solution_str("VALUE 7.5\n1 3 7.5\n2 1\n").unwrap();
+        assert_eq!(unchecked.cost, 7.5);
+        assert_eq!(unchecked.edges[0].cost, 7.5);
+        assert_eq!(unchecked.edges[1].cost, 0.0);
+
+        let err =
+            SteinerTree::from_solution_str_checked("VALUE 4\n1 2\n2 3\n", &instance).unwrap_err();
+        assert_eq!(err.line_number, 1);
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::ValueMismatch {
+                stated: 4.0,
+                computed: 3.0
+            }
+        );
+        assert!(
+            err.to_string()
+                .contains("stated value 4 differs from the cost 3")
+        );
+
+        let err = SteinerTree::from_solution_str_checked("VALUE 1\n1 4\n", &instance).unwrap_err();
+        assert_eq!((err.line_number, err.line.as_str()), (2, "1 4"));
+        assert_eq!(err.kind, ParseErrorKind::UnknownEdge { from: 1, to: 4 });
+
+        let err = SteinerTree::from_solution_str("1 2\n").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::MissingArgument {
+                expected: "VALUE line"
+            }
+        );
+        let err = SteinerTree::from_solution_str("VALUE 1\n1 x\n").unwrap_err();
+        assert_eq!(err.line_number, 2);
+
+        let path = std::env::temp_dir().join("steinlib_solution_files.sol");
+        let _ = std::fs::remove_file(&path);
+        tree.write_solution_to_file(&path, FileOptions::default())
+            .unwrap();
+        let read = SteinerTree::from_solution_file_checked(&path, &instance).unwrap();
+        assert_eq!(read, tree);
+        assert_eq!(SteinerTree::from_solution_file(&path).unwrap().cost, 3.0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn degree1_reduction() {
+        // The sample triangle with the path 3 - 4 - 5 - 6 hanging off
+        // terminal 3 and the isolated node 7
+        let mut original = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        original.num_nodes = 7;
+        for (from, to) in [(3, 4), (4, 5), (5, 6)] {
+            original.edges.push(Edge {
+                from,
+                to,
+                cost: 1.0,
+            });
+        }
+        original.num_edges = original.edges.len();
+        original
+            .coordinates
+            .insert(3, Coordinate::Planar { x: 1.0, y: 2.0 });
+        let optimum = original.solve_exact().unwrap().cost;
+
+        let mut stable = original.clone();
+        let log = stable.reduce_degree1();
+        assert_eq!(log.removed_nodes, [6, 5, 4]);
+        assert_eq!(log.removed_edges.len(), 3);
+        assert_eq!(log.relabeling, None);
+        assert_eq!(stable.num_nodes, 7);
+        assert_eq!(stable.edges, original.edges[..3]);
+        assert!(stable.check_counts().is_empty());
+        assert_eq!(stable.solve_exact().unwrap().cost, optimum);
+
+        // Nothing left to remove
+        assert_eq!(stable.reduce_degree1(), ReductionLog::default());
+
+        let mut compact = original.clone();
+        let log = compact.reduce_degree1_with(&ReductionOptions {
+            compact: true,
+            ..Default::default()
+        });
+        assert_eq!(log.removed_nodes, [6, 5, 4, 7]);
+        assert_eq!(log.relabeling, Some(vec![1, 2, 3]));
+        assert_eq!(compact.num_nodes, 3);
+        assert_eq!(compact.terminals, [1, 3]);
+        assert_eq!(compact.coordinates.keys().collect::<Vec<_>>(), [&3]);
+        assert!(compact.validate().is_ok());
+        assert_eq!(compact.solve_exact().unwrap().cost, optimum);
+
+        // Terminals are never removed, even as leaves
+        let mut path = SteinerInstance::new(
+            3,
+            original.edges[3..5]
+                .iter()
+                .map(|e| Edge {
+                    from: e.from - 2,
+                    to: e.to - 2,
+                    cost: 1.0,
+                })
+                .collect(),
+            vec![1, 3],
+        );
+        assert!(path.reduce_degree1().removed_nodes.is_empty());
+    }
+
+    #[test]
+    fn degree2_reduction() {
+        // The chain 1 - 4 - 5 - 3 is cheaper than both the direct edge and
+        // the detour over node 2, node 6 is isolated
+        let mut original = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        original.num_nodes = 6;
+        for (from, to) in [(1, 4), (4, 5), (5, 3)] {
+            original.edges.push(Edge {
+                from,
+                to,
+                cost: 0.5,
+            });
+        }
+        original.num_edges = original.edges.len();
+
+        let mut reduced = original.clone();
+        let log = reduced.reduce_degree2();
+        assert_eq!(log.removed_nodes, [5, 4, 2]);
+        assert_eq!(log.removed_edges.len(), 6);
+        assert_eq!(log.contractions.len(), 3);
+        assert_eq!(log.contractions[2].shortcut, None);
+        assert_eq!(reduced.edges.len(), 1);
+        assert_eq!((reduced.edges[0].from, reduced.edges[0].to), (1, 3));
+        assert_eq!(reduced.edges[0].cost, 1.5);
+        assert!(reduced.check_counts().is_empty());
+
+        let tree = reduced.solve_exact().unwrap();
+        let lifted = log.lift_solution(&tree);
+        assert_eq!(lifted.cost, 1.5);
+        let pairs: Vec<_> = lifted.edges.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(pairs, [(1, 4), (4, 5), (5, 3)]);
+        assert_eq!(original.verify_solution(&pairs), Ok(1.5));
+
+        let mut compact = original.clone();
+        let log = compact.reduce_degree2_with(&ReductionOptions {
+            compact: true,
+            ..Default::default()
+        });
+        assert_eq!(log.relabeling, Some(vec![1, 3]));
+        assert_eq!(compact.terminals, [1, 2]);
+        let tree = compact.solve_exact().unwrap();
+        assert_eq!(log.lift_solution(&tree), lifted);
+
+        // Optimal costs survive both reductions on random instances, and
+        // lifted solutions are valid
+        for _ in 0..10 {
+            let (mut instance, _) =
+                generate_random_with_fixed_vc(8, 3, 3, 0.5, DEFAULT_MAX_ATTEMPTS).unwrap();
+            for edge in &mut instance.edges {
+                edge.cost = ((edge.from * 7 + edge.to * 3) % 5 + 1) as f64;
+            }
+            let optimum = instance.solve_exact().unwrap().cost;
+
+            let mut reduced = instance.clone();
+            reduced.reduce_degree1();
+            let log = reduced.reduce_degree2();
+            let tree = log.lift_solution(&reduced.solve_exact().unwrap());
+            assert_eq!(tree.cost, optimum);
+            let pairs: Vec<_> = tree.edges.iter().map(|e| (e.from, e.to)).collect();
+            assert_eq!(instance.verify_solution(&pairs), Ok(optimum));
+        }
+    }
+
+    #[test]
+    fn long_edge_reduction() {
+        // E 1 3 costs exactly as much as the path over node 2
+        let original = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let mut kept = original.clone();
+        assert!(kept.reduce_long_edges().removed_edges.is_empty());
+        assert_eq!(kept, original);
+
+        let mut dropped = original.clone();
+        let log = dropped.reduce_long_edges_with(&ReductionOptions {
+            drop_ties: true,
+            ..Default::default()
+        });
+        assert_eq!(log.removed_edges.len(), 1);
+        assert_eq!((log.removed_edges[0].from, log.removed_edges[0].to), (1, 3));
+        assert_eq!(dropped.num_edges, 2);
+
+        // Of two equally long parallel edges one has to stay
+        let mut parallel = original.clone();
+        parallel.edges.push(parallel.edges[0].clone());
+        parallel.num_edges = 4;
+        parallel.reduce_long_edges_with(&ReductionOptions {
+            drop_ties: true,
+            ..Default::default()
+        });
+        assert_eq!(parallel.edges.len(), 2);
+        assert!(parallel.terminals_connected());
+
+        let mut longer = original.clone();
+        longer.edges[2].cost = 3.5;
+        assert_eq!(longer.reduce_long_edges().removed_edges.len(), 1);
+
+        // Dense random instances lose many edges, but no optimal tree
+        let (mut dense, _) =
+            generate_random_with_fixed_vc(30, 5, 10, 0.8, DEFAULT_MAX_ATTEMPTS).unwrap();
+        for edge in &mut dense.edges {
+            edge.cost = ((edge.from * 7 + edge.to * 3) % 5 + 1) as f64;
+        }
+        let before = dense.edges.len();
+        let optimum = dense.solve_exact().unwrap().cost;
+        let log = dense.reduce_long_edges();
+        assert!(!log.removed_edges.is_empty());
+        assert_eq!(dense.edges.len(), before - log.removed_edges.len());
+        assert_eq!(dense.solve_exact().unwrap().cost, optimum);
+    }
+
+    #[test]
+    fn metric_closure_lower_bound() {
+        let sample = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        // Exact for two terminals
+        assert_eq!(sample.lower_bound(), Ok(3.0));
+
+        for _ in 0..10 {
+            let (mut instance, _) =
+                generate_random_with_fixed_vc(8, 4, 3, 0.5, DEFAULT_MAX_ATTEMPTS).unwrap();
+            for edge in &mut instance.edges {
+                edge.cost = ((edge.from * 7 + edge.to * 3) % 5 + 1) as f64;
+            }
+            let bound = instance.lower_bound().unwrap();
+            let optimum = instance.solve_exact().unwrap().cost;
+            let approximation = instance.approximate_steiner_tree().unwrap().cost;
+            assert!(bound > 0.0);
+            assert!(bound <= optimum + 1e-9);
+            assert!(optimum <= approximation);
+        }
+
+        let single = SteinerInstance::new(3, sample.edges.clone(), vec![2]);
+        assert_eq!(single.lower_bound(), Ok(0.0));
+        let disconnected = SteinerInstance::new(4, sample.edges.clone(), vec![1, 4]);
+        assert_eq!(
+            disconnected.lower_bound(),
+            Err(SolveError::DisconnectedTerminals { from: 1, to: 4 })
+        );
+    }
+
+    #[test]
+    fn solution_quality_gap() {
+        let sample = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let optimum = sample.solve_exact().unwrap();
+        assert_eq!(optimum.quality_gap(&sample), Some(0.0));
+        // The path over node 2 is as cheap as the direct edge
+        let detour = SteinerTree::new(sample.edges[..2].to_vec());
+        assert_eq!(detour.quality_gap(&sample), Some(0.0));
+
+        // Three terminals at distance 1.9 of each other bound the optimum
+        // of 3 by 3.8 / (4 / 3) = 2.85
+        let star = SteinerInstance::builder()
+            .nodes(4)
+            .edge(1, 2, 1.9)
+            .edge(1, 3, 1.9)
+            .edge(2, 3, 1.9)
+            .edge(1, 4, 1.0)
+            .edge(2, 4, 1.0)
+            .edge(3, 4, 1.0)
+            .terminal(1)
+            .terminal(2)
+            .terminal(3)
+            .build()
+            .unwrap();
+        let optimum = star.solve_exact().unwrap();
+        let gap = optimum.quality_gap(&star).unwrap();
+        assert!((gap - 0.15 / 2.85).abs() < 1e-12);
+        let direct = SteinerTree::new(star.edges[..2].to_vec());
+        assert!(direct.quality_gap(&star).unwrap() > gap);
+
+        // A single terminal gives a bound of 0, a missing one an error
+        let single = SteinerInstance::new(3, sample.edges.clone(), vec![2]);
+        assert_eq!(SteinerTree::default().quality_gap(&single), None);
+        let disconnected = SteinerInstance::new(4, sample.edges.clone(), vec![1, 4]);
+        assert_eq!(optimum.quality_gap(&disconnected), None);
+    }
+
+    #[test]
+    fn shortest_path_upper_bound() {
+        let sample = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let tree = sample
+            .upper_bound(3, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        assert_eq!(tree.cost, 3.0);
+        assert!(tree.is_tree() && tree.covers_terminals(&sample));
+
+        for seed in 0..10 {
+            let (mut instance, _) =
+                generate_random_with_fixed_vc(10, 4, 3, 0.5, DEFAULT_MAX_ATTEMPTS).unwrap();
+            for edge in &mut instance.edges {
+                edge.cost = ((edge.from * 7 + edge.to * 3) % 5 + 1) as f64;
+            }
+            let tree = instance
+                .upper_bound(4, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert!(tree.is_tree() && tree.covers_terminals(&instance));
+            assert!(instance.solve_exact().unwrap().cost <= tree.cost);
+            // More repetitions never give a worse tree for the same seed
+            let single = instance
+                .upper_bound(1, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert!(tree.cost <= single.cost);
+            // Same seed, same tree
+            let again = instance
+                .upper_bound(4, &mut StdRng::seed_from_u64(seed))
+                .unwrap();
+            assert_eq!(again, tree);
+        }
+
+        let disconnected = SteinerInstance::new(4, sample.edges.clone(), vec![1, 4]);
+        assert!(matches!(
+            disconnected.upper_bound(2, &mut StdRng::seed_from_u64(0)),
+            Err(SolveError::DisconnectedTerminals { .. })
+        ));
+    }
+
+    #[test]
+    fn instance_stats() {
+        let sample = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let stats = sample.stats();
+        assert_eq!(
+            stats,
+            InstanceStats {
+                nodes: 3,
+                edges: 3,
+                terminals: 2,
+                density: 1.0,
+                min_degree: 2,
+                max_degree: 2,
+                mean_degree: 2.0,
+                min_cost: 1.0,
+                max_cost: 3.0,
+                mean_cost: 2.0,
+                components: 1,
+                terminals_connected: true,
+                has_coordinates: false,
+            }
+        );
+        assert_eq!(
+            stats_csv_header(),
+            "nodes,edges,terminals,density,min_degree,max_degree,mean_degree,\
+             min_cost,max_cost,mean_cost,components,terminals_connected,has_coordinates"
+        );
+        assert_eq!(stats.to_csv_row(), "3,3,2,1,2,2,2,1,3,2,1,true,false");
+        assert_eq!(
+            stats_csv_header().split(',').count(),
+            stats.to_csv_row().split(',').count()
+        );
+
+        let split = SteinerInstance::new(5, sample.edges.clone(), vec![1, 4]);
+        let stats = split.stats();
+        assert_eq!((stats.min_degree, stats.max_degree), (0, 2));
+        assert_eq!(stats.mean_degree, 6.0 / 5.0);
+        assert_eq!(stats.components, 3);
+        assert!(!stats.terminals_connected);
+        assert_eq!(stats.components, split.connected_components().len());
+
+        let empty = SteinerInstance::new(0, Vec::new(), Vec::new()).stats();
+        assert_eq!(empty.to_csv_row(), "0,0,0,0,0,0,0,0,0,0,0,true,false");
+    }
+
+    #[test]
+    fn compact_and_relabel() {
+        let edge = |from, to, cost| Edge { from, to, cost };
+        let mut instance =
+            SteinerInstance::new(6, vec![edge(2, 4, 1.0), edge(4, 6, 2.0)], vec![2, 6]);
+        instance
+            .coordinates
+            .insert(1, Coordinate::Planar { x: 0.0, y: 0.0 });
+        instance
+            .coordinates
+            .insert(4, Coordinate::Planar { x: 1.0, y: 2.0 });
+        instance.max_degrees = vec![1, 2, 3, 4, 5, 6];
+
+        assert_eq!(instance.compact(), vec![2, 4, 6]);
+        assert_eq!(instance.num_nodes, 3);
+        assert_eq!(instance.edges, vec![edge(1, 2, 1.0), edge(2, 3, 2.0)]);
+        assert_eq!(instance.terminals, vec![1, 3]);
+        assert_eq!(
+            instance.coordinates.keys().copied().collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(instance.max_degrees, vec![2, 4, 6]);
+        // Nothing left to drop
+        assert_eq!(instance.compact(), vec![1, 2, 3]);
+
+        instance.relabel(&[3, 1, 2]).unwrap();
+        assert_eq!(instance.edges, vec![edge(3, 1, 1.0), edge(1, 2, 2.0)]);
+        assert_eq!(instance.terminals, vec![3, 2]);
+        assert_eq!(
+            instance.coordinates.keys().copied().collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(instance.max_degrees, vec![4, 6, 2]);
+
+        let before = instance.clone();
+        assert_eq!(
+            instance.relabel(&[1, 1, 2]),
+            Err(RelabelError::NotInjective {
+                first: 1,
+                second: 2,
+                new_id: 1
+            })
+        );
+        assert_eq!(
+            instance.relabel(&[1, 4, 2]),
+            Err(RelabelError::OutOfRange {
+                node: 2,
+                new_id: 4,
+                nodes: 1..4
+            })
+        );
+        assert_eq!(
+            instance.relabel(&[1, 2]),
+            Err(RelabelError::LengthMismatch {
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(instance, before);
+    }
+
+    #[test]
+    fn zero_based_compact_and_relabel() {
+        let edge = |from, to, cost| Edge { from, to, cost };
+        let mut one = SteinerInstance::new(
+            6,
+            vec![edge(1, 2, 1.0), edge(2, 4, 2.0), edge(4, 5, 1.5)],
+            vec![1, 4],
+        );
+        one.max_degrees = vec![1, 2, 3, 4, 5, 6];
+        let zero = one.to_zero_indexed().unwrap();
+
+        // Node 0 is kept and the ids stay 0-based
+        let mut compacted = zero.clone();
+        assert_eq!(compacted.compact(), vec![0, 1, 3, 4]);
+        assert_eq!(compacted.index_base, IndexBase::Zero);
+        assert_eq!(compacted.terminals, vec![0, 2]);
+        assert_eq!(compacted.max_degrees, vec![1, 2, 4, 5]);
+        assert!(compacted.validate().is_ok());
+        let mut expected = one.clone();
+        assert_eq!(expected.compact(), vec![1, 2, 4, 5]);
+        assert_eq!(compacted.to_stp_string(), expected.to_stp_string());
+        assert_eq!(compacted.to_one_indexed(), expected);
+
+        // The mapping is indexed by the 0-based ids and permutes them
+        let mut relabeled = compacted.clone();
+        relabeled.relabel(&[3, 0, 2, 1]).unwrap();
+        expected.relabel(&[4, 1, 3, 2]).unwrap();
+        assert_eq!(relabeled.to_one_indexed(), expected);
+        assert_eq!(
+            relabeled.clone().relabel(&[1, 2, 3, 4]),
+            Err(RelabelError::OutOfRange {
+                node: 3,
+                new_id: 4,
+                nodes: 0..4
+            })
+        );
+        let (permuted, permutation) = zero.permuted(&mut StdRng::seed_from_u64(3));
+        let mut sorted = permutation.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..6).collect::<Vec<_>>());
+        assert_eq!(permuted.terminals.len(), 2);
+
+        // Compacting reductions and lifting solutions back
+        let mut reduced = zero.clone();
+        let log = reduced.reduce_degree1_with(&ReductionOptions {
+            compact: true,
+            ..Default::default()
+        });
+        assert_eq!(reduced.index_base, IndexBase::Zero);
+        assert_eq!(reduced.node_ids(), 0..3);
+        assert_eq!(log.relabeling, Some(vec![0, 1, 3]));
+        let tree = SteinerTree::new(reduced.edges.clone());
+        let lifted = log.lift_solution(&tree);
+        assert_eq!(lifted.edges, vec![edge(0, 1, 1.0), edge(1, 3, 2.0)]);
+
+        // Node 0 of a leniently parsed 1-based instance has no 0-based id
+        let parsed = Parser::default()
+            .parse_stp(&SAMPLE_STP.replace("E 1 3 3", "E 0 3 3"))
+            .unwrap();
+        assert_eq!(parsed.to_zero_indexed(), Err(ConversionError::NodeZero));
+    }
+
+    #[test]
+    fn disjoint_union_and_merge() {
+        let sample = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let union = sample.disjoint_union(&sample);
+        assert_eq!(
+            (union.num_nodes, union.num_edges, union.num_terminals),
+            (6, 6, 4)
+        );
+        assert_eq!(union.terminals, vec![1, 3, 4, 6]);
+        assert!(union.edges.contains(&Edge {
+            from: 5,
+            to: 6,
+            cost: 2.0
+        }));
+        assert!(union.validate().is_ok());
+        assert_eq!(union.connected_components().len(), 2);
+
+        // Glue terminal 3 of the first copy onto terminal 1 of the second
+        let merged = sample.merge_on(&sample, &[(3, 1)]).unwrap();
+        assert_eq!(
+            (merged.num_nodes, merged.num_edges, merged.num_terminals),
+            (5, 6, 3)
+        );
+        assert_eq!(merged.terminals, vec![1, 3, 5]);
+        assert!(merged.edges.contains(&Edge {
+            from: 3,
+            to: 4,
+            cost: 1.0
+        }));
+        assert!(merged.validate().is_ok());
+        assert!(merged.is_connected());
+        // Both halves are solved independently through the cut node
+        assert_eq!(merged.solve_exact().unwrap().cost, 6.0);
+
+        let mut with_data = sample.clone();
+        with_data.max_degrees = vec![1, 2, 3];
+        let merged = with_data.merge_on(&sample, &[(2, 2)]).unwrap();
+        assert_eq!(merged.max_degrees, vec![1, 2, 3, 6, 6]);
+        assert!(merged.validate().is_ok());
+
+        assert_eq!(
+            sample.merge_on(&sample, &[(4, 1)]),
+            Err(MergeError::NodeOutOfRange {
+                pair: (4, 1),
+                num_nodes: (3, 3)
+            })
+        );
+        assert_eq!(
+            sample.merge_on(&sample, &[(1, 1), (2, 1)]),
+            Err(MergeError::DuplicateNode { pair: (2, 1) })
+        );
+    }
+
+    #[test]
+    fn symmetrize_and_bidirect() {
+        let sample = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let bidirected = sample.bidirected();
+        assert!(bidirected.edges.is_empty());
+        assert_eq!((bidirected.num_edges, bidirected.num_arcs), (0, 6));
+        assert!(bidirected.arcs.contains(&Edge {
+            from: 3,
+            to: 2,
+            cost: 2.0
+        }));
+        assert_eq!(bidirected.symmetrized(), sample);
+
+        let (instance, _) =
+            generate_random_with_fixed_vc(12, 4, 4, 0.4, DEFAULT_MAX_ATTEMPTS).unwrap();
+        assert_eq!(instance.bidirected().symmetrized(), instance);
+
+        let edge = |from, to, cost| Edge { from, to, cost };
+        let mut directed = SteinerInstance::new(4, vec![edge(1, 2, 1.0)], vec![1, 4]);
+        directed.arcs = vec![
+            edge(2, 3, 5.0),
+            edge(3, 2, 2.0),
+            edge(2, 1, 4.0),
+            edge(3, 4, 1.0),
+        ];
+        directed.num_arcs = 4;
+        let symmetrized = directed.symmetrized();
+        assert!(symmetrized.arcs.is_empty());
+        assert_eq!((symmetrized.num_edges, symmetrized.num_arcs), (3, 0));
+        let costs: Vec<(usize, usize, f64)> = symmetrized
+            .edges
+            .iter()
+            .map(|edge| (edge.from, edge.to, edge.cost))
+            .collect();
+        // The existing edge (1, 2) is cheaper than the arc (2, 1)
+        assert_eq!(costs, vec![(1, 2, 1.0), (2, 3, 2.0), (3, 4, 1.0)]);
+        assert!(symmetrized.validate().is_ok());
+    }
+
+    #[test]
+    fn random_permutation() {
+        let (mut instance, _) =
+            generate_random_with_fixed_vc(10, 4, 3, 0.5, DEFAULT_MAX_ATTEMPTS).unwrap();
+        for edge in &mut instance.edges {
+            edge.cost = ((edge.from * 7 + edge.to * 3) % 5 + 1) as f64;
+        }
+        instance
+            .coordinates
+            .insert(2, Coordinate::Planar { x: 1.0, y: 2.0 });
+
+        let (permuted, permutation) = instance.permuted(&mut StdRng::seed_from_u64(7));
+        let mut sorted = permutation.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (1..=10).collect::<Vec<_>>());
+        assert_eq!(
+            permuted.coordinates.keys().copied().collect::<Vec<_>>(),
+            vec![permutation[1]]
+        );
+        let translated: HashSet<usize> = instance
+            .terminals
+            .iter()
+            .map(|&terminal| permutation[terminal - 1])
+            .collect();
+        assert_eq!(
+            translated,
+            permuted.terminals.iter().copied().collect::<HashSet<_>>()
+        );
+        assert_eq!(
+            permuted.solve_exact().unwrap().cost,
+            instance.solve_exact().unwrap().cost
+        );
+
+        // Same seed, same permutation
+        let (again, _) = instance.permuted(&mut StdRng::seed_from_u64(7));
+        assert_eq!(again, permuted);
+        assert_eq!(instance.permuted_by(&permutation).unwrap(), permuted);
+        assert!(instance.permuted_by(&[1, 1]).is_err());
+    }
+
+    #[test]
+    fn integral_costs() {
+        let edge = |from, to, cost| Edge { from, to, cost };
+        let costs = |instance: &SteinerInstance| -> Vec<f64> {
+            instance
+                .edges
+                .iter()
+                .chain(&instance.arcs)
+                .map(|edge| edge.cost)
+                .collect()
+        };
+
+        let mut halves =
+            SteinerInstance::new(3, vec![edge(1, 2, 0.5), edge(2, 3, 1.5)], vec![1, 3]);
+        halves.arcs = vec![edge(3, 1, 2.0)];
+        halves.num_arcs = 1;
+        assert_eq!(halves.clone().integerize_costs_exact(100), Ok(2.0));
+        assert_eq!(halves.integerize_costs(100), 2.0);
+        assert_eq!(costs(&halves), vec![1.0, 3.0, 4.0]);
+
+        // Decimal fractions are exact despite their binary representation
+        let mut decimals =
+            SteinerInstance::new(3, vec![edge(1, 2, 0.1), edge(2, 3, 0.25)], vec![1, 3]);
+        assert_eq!(decimals.integerize_costs(1000), 20.0);
+        assert_eq!(costs(&decimals), vec![2.0, 5.0]);
+
+        // A third needs the rounding path below a denominator of 3, and for
+        // 1/3, 1/7 and 1/11 the common factor of 231 is too large
+        let thirds = SteinerInstance::new(2, vec![edge(1, 2, 1.0 / 3.0)], vec![1, 2]);
+        let mut rounded = thirds.clone();
+        assert_eq!(rounded.integerize_costs(2), 2.0);
+        assert_eq!(costs(&rounded), vec![1.0]);
+        assert!((costs(&rounded)[0] / 2.0 - 1.0 / 3.0).abs() <= 1.0 / (2.0 * 2.0));
+        assert_eq!(thirds.clone().integerize_costs(3), 3.0);
+
+        let mut mixed = SteinerInstance::new(
+            4,
+            vec![
+                edge(1, 2, 1.0 / 3.0),
+                edge(2, 3, 1.0 / 7.0),
+                edge(3, 4, 1.0 / 11.0),
+            ],
+            vec![1, 4],
+        );
+        assert_eq!(
+            mixed.clone().integerize_costs_exact(100),
+            Err(ScalingError::NotIntegral {
+                from: 3,
+                to: 4,
+                cost: 1.0 / 11.0,
+                max_denominator: 100
+            })
+        );
+        assert_eq!(mixed.clone().integerize_costs_exact(231), Ok(231.0));
+        let original = costs(&mixed);
+        let factor = mixed.integerize_costs(100);
+        assert_eq!(factor, 100.0);
+        for (scaled, cost) in costs(&mixed).iter().zip(original) {
+            assert_eq!(scaled.fract(), 0.0);
+            assert!((scaled / factor - cost).abs() <= 1.0 / (2.0 * factor));
+        }
+    }
+
+    #[test]
+    fn zero_based_indices() {
+        let mut sample = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        assert_eq!(sample.index_base, IndexBase::One);
+        sample
+            .coordinates
+            .insert(3, Coordinate::Planar { x: 1.0, y: 2.0 });
+
+        let zero = sample.to_zero_indexed().unwrap();
+        assert_eq!(zero.index_base, IndexBase::Zero);
+        let endpoints: Vec<(usize, usize)> =
+            zero.edges.iter().map(|edge| (edge.from, edge.to)).collect();
+        assert_eq!(endpoints, vec![(0, 1), (1, 2), (0, 2)]);
+        assert_eq!(zero.terminals, vec![0, 2]);
+        assert_eq!(
+            zero.coordinates.keys().copied().collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_ne!(zero, sample);
+
+        // Converting twice doesn't shift again
+        assert_eq!(zero.to_zero_indexed().unwrap(), zero);
+        assert_eq!(zero.to_one_indexed(), sample);
+        assert_eq!(sample.to_one_indexed(), sample);
+        assert_eq!(zero.to_one_indexed().to_one_indexed(), sample);
+
+        // Exporters always write 1-based ids
+        assert_eq!(zero.to_stp_string(), sample.to_stp_string());
+        assert_eq!(
+            zero.to_dot(&DotOptions::default()),
+            sample.to_dot(&DotOptions::default())
+        );
+        assert_eq!(zero.to_graphml().unwrap(), sample.to_graphml().unwrap());
+        assert_eq!(
+            zero.to_csv(&CsvOptions::default()).unwrap(),
+            sample.to_csv(&CsvOptions::default()).unwrap()
+        );
+        assert_eq!(
+            zero.to_gr_string(GrCosts::RequireIntegral).unwrap(),
+            sample.to_gr_string(GrCosts::RequireIntegral).unwrap()
+        );
+        #[cfg(feature = "json")]
+        assert_eq!(zero.to_json(), sample.to_json());
+        let reparsed = Parser::default().parse_stp(&zero.to_stp_string()).unwrap();
+        assert_eq!(reparsed.index_base, IndexBase::One);
+        assert_eq!(reparsed.edges, sample.edges);
+    }
+
+    #[test]
+    fn zero_based_views_and_solvers() {
+        let sample = Parser::default().parse_stp(SAMPLE_STP).unwrap();
+        let zero = sample.to_zero_indexed().unwrap();
+        assert_eq!(zero.node_ids(), 0..3);
+        assert_eq!(sample.node_ids(), 1..4);
+
+        // Node 0 is NodeIndex 0 and keeps its id as the weight
+        let graph = zero.to_petgraph().unwrap();
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.edge_count(), 3);
+        assert_eq!(graph[NodeIndex::new(0)], 0);
+        assert!(graph.contains_edge(NodeIndex::new(0), NodeIndex::new(2)));
+        assert!(is_isomorphic_matching(
+            &graph,
+            &sample.to_petgraph().unwrap(),
+            |_, _| true,
+            |a, b| a == b
+        ));
+        assert_eq!(zero.to_petgraph_directed().unwrap().edge_count(), 6);
+
+        let connectivity = ValidationOptions { connectivity: true };
+        assert_eq!(zero.validate_with(&connectivity), Ok(()));
+        let mut broken = zero.clone();
+        broken.terminals.push(3);
+        let issues = broken.validate().unwrap_err();
+        assert!(
+            issues
+                .iter()
+                .any(|issue| issue.message == "terminal 3 is outside of 0..3")
+        );
+
+        let mut neighbors: Vec<usize> = zero.adjacency().neighbors(0).map(|(v, _)| v).collect();
+        neighbors.sort();
+        assert_eq!(neighbors, vec![1, 2]);
+        assert_eq!(zero.degrees(), sample.degrees());
+        assert_eq!(zero.connected_components(), vec![vec![0, 1, 2]]);
+        assert!(zero.unreachable_terminals().is_empty());
+        assert_eq!(zero.stats(), sample.stats());
+
+        // The solvers refuse instead of misreading node 0
+        assert_eq!(
+            zero.approximate_steiner_tree(),
+            Err(SolveError::ZeroIndexed)
+        );
+        assert_eq!(zero.solve_exact(), Err(SolveError::ZeroIndexed));
+        assert_eq!(zero.lower_bound(), Err(SolveError::ZeroIndexed));
+        assert_eq!(zero.exact_bounded_treewidth(2), None);
+        assert_eq!(zero.shortest_paths(0), Err(PathError::ZeroIndexed));
+        assert_eq!(zero.metric_closure().unwrap_err(), PathError::ZeroIndexed);
+        assert_eq!(
+            zero.to_one_indexed().solve_exact().unwrap().cost,
+            sample.solve_exact().unwrap().cost
+        );
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        for line in [
+            "",
+            "   ",
+            "E",
+            "E I",
+            "E I 1 2",
+            "E I 1 x 3",
+            "T",
+            "T A",
+            "V",
+            "V D -1",
+            "X 1",
+        ] {
+            assert!(line.parse::<UpdateOperation>().is_err(), "{:?}", line);
+        }
+        assert!(matches!(
+            "E I 1 2 3.5".parse::<UpdateOperation>(),
+            Ok(UpdateOperation::EdgeInsertion(Edge { from: 1, to: 2, .. }))
+        ));
+
+        let updates = "SECTION UPDATES\n\nE I 1 2 1\nE X 1 2 1\n".to_string();
+        assert_eq!(
+            DynamicInstance::from_str(updates, 0, &[]).err(),
+            Some(DynamicInstanceError::InvalidUpdate {
+                line_number: 4,
+                line: "E X 1 2 1".to_string()
+            })
+        );
+        assert_eq!(
+            DynamicInstance::from_str("E I 1 2 1\nQ 1\n".to_string(), 0, &[]).err(),
+            Some(DynamicInstanceError::MissingQuery { query: 1 })
+        );
+        assert!(matches!(
+            DynamicInstance::from_str("E I 1 99999999999999 1".to_string(), 0, &[]),
+            Err(DynamicInstanceError::VertexOutOfRange { line_number: 1, .. })
+        ));
+        assert_eq!(
+            DynamicInstance::from_str(String::new(), 0, &[])
+                .unwrap()
+                .num_vertices,
+            0
+        );
+
+        // Group ids index a vector and must not allocate without bounds
+        let groups = "SECTION Terminals\nTG 1 99999999999999\nEND\nEOF\n";
+        assert!(matches!(
+            Parser::default().parse_stp(groups),
+            Err(ParseError {
+                kind: ParseErrorKind::InvalidArgument { .. },
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "proptest")]
+    use proptest::prelude::*;
+    #[cfg(feature = "proptest")]
+    use steinlib::strategies::{connected_instances, instances, update_sequences};
+
+    #[cfg(feature = "proptest")]
+    proptest! {
+        // Random lines, mostly made of STP and update keywords so that they
+        // reach deep into the parsers
+        #[test]
+        fn parsers_never_panic(
+            lines in prop::collection::vec(
+                prop_oneof![
+                    "[ -~]{0,30}",
+                    prop::collection::vec(
+                        prop::sample::select(vec![
+                            "SECTION", "Graph", "Terminals", "Coordinates", "Presolve",
+                            "MaximumDegrees", "NodeWeights", "Obstacles", "Comment",
+                            "33D32945", "Nodes", "Edges", "Arcs", "E", "A", "T", "TP",
+                            "TG", "Root", "DD", "DDD", "RR", "MD", "NW", "EA", "END",
+                            "EOF", "Q", "I", "D", "V", "0", "1", "2", "-1", "2.5",
+                            "1e308", "NaN", "inf", "99999999999999999999", "\"",
+                        ]),
+                        0..6,
+                    )
+                    .prop_map(|tokens| tokens.join(" ")),
+                ],
+                0..20,
+            ),
+            strict in any::<bool>(),
+        ) {
+            let document = lines.join("\n");
+            let mut parser = Parser::new(ParserOptions {
+                strict,
+                ..ParserOptions::default()
+            });
+            let _ = parser.parse_stp(&document);
+            let _ = parser.parse_stp_reader(document.as_bytes());
+            let mut instance = SteinerInstance::default();
+            for line in &lines {
+                let _ = parser.parse_stp_line(line, &mut instance);
+                let _ = parser.move_section(line);
+                let _ = line.parse::<UpdateOperation>();
+            }
+            let queries = vec![document.clone(); 3];
+            let _ = DynamicInstance::from_str(document, 0, &queries);
+        }
+
+        #[test]
+        fn stp_export_round_trip(instance in any::<SteinerInstance>()) {
+            prop_assert!(instance.validate().is_ok());
+            let mut parsed = Parser::default().parse_stp(&instance.to_stp_string()).unwrap();
+            let mut expected = instance.clone();
+            parsed.normalize();
+            expected.normalize();
+            prop_assert_eq!(parsed, expected);
+        }
+
+        #[test]
+        fn generated_instances_are_valid(
+            instance in instances(8),
+            connected in connected_instances(8),
+        ) {
+            prop_assert!(instance.num_nodes <= 8);
+            prop_assert!(instance.validate().is_ok());
+            let options = ValidationOptions { connectivity: true };
+            prop_assert!(connected.validate_with(&options).is_ok());
+            prop_assert!(connected.is_connected());
+        }
+
+        #[test]
+        fn generated_update_sequences_are_legal(updates in update_sequences(6, 30)) {
+            let mut edges = HashSet::new();
+            let mut terminals = HashSet::new();
+            for update in &updates {
+                match update {
+                    UpdateOperation::EdgeInsertion(edge) => {
+                        prop_assert!(edges.insert((edge.from, edge.to)));
+                    }
+                    UpdateOperation::EdgeDeletion(edge) => {
+                        prop_assert!(edges.remove(&(edge.from, edge.to)));
+                    }
+                    UpdateOperation::TerminalActivation(node) => {
+                        prop_assert!(terminals.insert(*node));
+                    }
+                    UpdateOperation::TerminalDeactivation(node) => {
+                        prop_assert!(terminals.remove(node));
+                    }
+                    UpdateOperation::Query(instance) => {
+                        prop_assert!(instance.validate().is_ok());
+                        prop_assert_eq!(instance.edges.len(), edges.len());
+                    }
+                    _ => prop_assert!(false, "unexpected update {:?}", update),
+                }
+            }
+            prop_assert!(matches!(updates.last(), Some(UpdateOperation::Query(_))));
+        }
+    }
+
+    #[test]
+    fn seeded_generators_are_reproducible() {
+        let probs = || UpdateProbabilities {
+            edge_insertion: 0.3,
+            edge_deletion: 0.3,
+            terminal_activation: 0.2,
+            terminal_deactivation: 0.2,
+        };
+        let generate = || {
+            let mut rng = StdRng::seed_from_u64(42);
+            let (instance, vc) = generate_random_with_fixed_vc_with_rng(
+                20,
+                5,
+                4,
+                0.5,
+                &FixedVcOptions {
+                    max_attempts: 1000,
+                    ..Default::default()
+                },
+                None,
+                &mut rng,
+            )
+            .unwrap();
+            let updates = generate_update_sequence_with_rng(
+                &instance,
+                probs(),
+                vc.clone(),
+                30,
+                &UpdateSequenceOptions {
+                    queries: QueryPolicy::PerOp(0.5),
+                    ..Default::default()
+                },
+                None,
+                &mut rng,
+            )
+            .unwrap();
+            (instance, vc, updates)
+        };
+
+        let (first, first_vc, first_updates) = generate();
+        let (second, second_vc, second_updates) = generate();
+        assert_eq!(first, second);
+        assert_eq!(first_vc, second_vc);
+        assert_eq!(
+            updates_to_dus(&first_updates),
+            updates_to_dus(&second_updates)
+        );
+        for (a, b) in first_updates.iter().zip(&second_updates) {
+            if let (UpdateOperation::Query(a), UpdateOperation::Query(b)) = (a, b) {
+                assert_eq!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_cost_models() {
+        let generate = |costs: CostModel, seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            generate_random_with_fixed_vc_with_rng(
+                15,
+                4,
+                5,
+                0.6,
+                &FixedVcOptions {
+                    costs,
+                    max_attempts: 1000,
+                    ..Default::default()
+                },
+                None,
+                &mut rng,
+            )
+            .unwrap()
+            .0
+        };
+        let costs = |instance: &SteinerInstance| -> Vec<f64> {
+            instance.edges.iter().map(|edge| edge.cost).collect()
+        };
+
+        let unit = generate(CostModel::Unit, 1);
+        assert!(costs(&unit).iter().all(|&cost| cost == 1.0));
+
+        let integral = CostModel::UniformInt { lo: 3, hi: 7 };
+        assert!(integral.is_integral());
+        let instance = generate(integral, 2);
+        assert!(!instance.edges.is_empty());
+        for cost in costs(&instance) {
+            assert!((3.0..=7.0).contains(&cost) && cost.fract() == 0.0);
+        }
+        // Integral costs are written without a decimal point
+        let stp = instance.to_string();
+        let edge_lines: Vec<&str> = stp.lines().filter(|line| line.starts_with("E ")).collect();
+        assert_eq!(edge_lines.len(), instance.edges.len());
+        assert!(edge_lines.iter().all(|line| !line.contains('.')));
+
+        let uniform = CostModel::UniformFloat { lo: 0.5, hi: 2.5 };
+        assert!(!uniform.is_integral());
+        let instance = generate(uniform, 3);
+        assert!(
+            costs(&instance)
+                .iter()
+                .all(|cost| (0.5..=2.5).contains(cost))
+        );
+        assert!(costs(&instance).iter().any(|cost| cost.fract() != 0.0));
+
+        let exponential = CostModel::Exponential { lambda: 2.0 };
+        let instance = generate(exponential, 4);
+        assert!(
+            costs(&instance)
+                .iter()
+                .all(|&cost| cost >= 0.0 && cost.is_finite())
+        );
+
+        // The same seed and model reproduce the same costs
+        for model in [integral, uniform, exponential] {
+            assert_eq!(costs(&generate(model, 5)), costs(&generate(model, 5)));
+        }
+
+        // Candidate edges of update sequences draw their costs from the model
+        let mut rng = StdRng::seed_from_u64(6);
+        let instance = SteinerInstance::new(4, Vec::new(), vec![1, 2]);
+        let insertions_only = UpdateProbabilities::edges_only(1.0, 0.0);
+        let updates = generate_update_sequence_with_rng(
+            &instance,
+            insertions_only,
+            vec![1, 2, 3, 4],
+            6,
+            &UpdateSequenceOptions {
+                start_empty: true,
+                costs: integral,
+                ..Default::default()
+            },
+            None,
+            &mut rng,
+        )
+        .unwrap();
+        let inserted: Vec<f64> = updates
+            .iter()
+            .filter_map(|update| match update {
+                UpdateOperation::EdgeInsertion(edge) => Some(edge.cost),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(inserted.len(), 6);
+        assert!(inserted.iter().all(|cost| (3.0..=7.0).contains(cost)));
+    }
+
+    #[test]
+    fn euclidean_instances() {
+        let bounding_box = Rectangle {
+            x1: 100.0,
+            y1: 50.0,
+            x2: 0.0,
+            y2: 0.0,
+        };
+        let generate = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            generate_random_euclidean(12, 4, bounding_box, &mut rng)
+        };
+
+        let instance = generate(42);
+        assert_eq!(instance, generate(42));
+        assert_eq!(instance.coordinates, generate(42).coordinates);
+        assert_eq!(instance.num_nodes, 12);
+        assert_eq!(instance.terminals.len(), 4);
+        assert_eq!(instance.edges.len(), 12 * 11 / 2);
+        assert_eq!(instance.coordinates.len(), 12);
+
+        let point = |node: usize| match instance.coordinates[&node] {
+            Coordinate::Planar { x, y } => (x, y),
+            Coordinate::Spatial { .. } => panic!("generated points are planar"),
+        };
+        for node in 1..=12 {
+            let (x, y) = point(node);
+            assert!((0.0..=100.0).contains(&x) && (0.0..=50.0).contains(&y));
+        }
+        let mut cost = BTreeMap::new();
+        for edge in &instance.edges {
+            let ((x1, y1), (x2, y2)) = (point(edge.from), point(edge.to));
+            assert!((edge.cost - (x1 - x2).hypot(y1 - y2)).abs() < 1e-9);
+            cost.insert((edge.from, edge.to), edge.cost);
+            cost.insert((edge.to, edge.from), edge.cost);
+        }
+        for a in 1..=12 {
+            for b in 1..=12 {
+                for c in 1..=12 {
+                    if a != b && b != c && a != c {
+                        assert!(cost[&(a, c)] <= cost[&(a, b)] + cost[&(b, c)] + 1e-9);
+                    }
+                }
+            }
+        }
+
+        // The points are exported and read back
+        let stp = instance.to_string();
+        assert!(stp.contains("SECTION Coordinates"));
+        let parsed = Parser::default().parse_stp(&stp).unwrap();
+        assert_eq!(parsed.coordinates, instance.coordinates);
+
+        // Sparse variant: k nearest neighbours plus a spanning tree
+        let options = EuclideanOptions {
+            nearest_neighbors: Some(2),
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        let sparse =
+            generate_random_euclidean_with_options(40, 5, bounding_box, &options, &mut rng);
+        assert!(sparse.is_connected());
+        assert!(sparse.edges.len() >= 39);
+        assert!(sparse.edges.len() <= 40 * 2 + 39);
+    }
+
+    #[test]
+    fn grid_instances() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let grid = generate_grid(5, 4, 6, &[], &mut rng).unwrap();
+        assert_eq!(grid.num_nodes, 20);
+        assert_eq!(grid.edges.len(), 4 * 4 + 5 * 3);
+        assert!(grid.edges.iter().all(|edge| edge.cost == 1.0));
+        assert_eq!(grid.terminals.len(), 6);
+        // Row-major numbering: column 2 of row 1 is node 8
+        assert_eq!(grid.coordinates[&8], Coordinate::Planar { x: 2.0, y: 1.0 });
+        assert!(grid.edges.contains(&Edge {
+            from: 3,
+            to: 8,
+            cost: 1.0
+        }));
+        let mut rng = StdRng::seed_from_u64(42);
+        assert_eq!(grid, generate_grid(5, 4, 6, &[], &mut rng).unwrap());
+
+        // A wall in column 2 with a gap in the last row
+        let wall = Rectangle {
+            x1: 2.0,
+            y1: 0.0,
+            x2: 2.0,
+            y2: 2.0,
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let walled = generate_grid(5, 4, 17, &[wall], &mut rng).unwrap();
+        let blocked = [3, 8, 13];
+        assert!(
+            walled
+                .edges
+                .iter()
+                .all(|edge| { !blocked.contains(&edge.from) && !blocked.contains(&edge.to) })
+        );
+        assert!(walled.terminals.iter().all(|t| !blocked.contains(t)));
+        assert!(walled.terminals_connected());
+        assert_eq!(walled.obstacles, vec![wall]);
+        assert!(walled.to_string().contains("SECTION Obstacles"));
+
+        // Closing the gap leaves two regions of 8 cells
+        let closed = Rectangle { y2: 3.0, ..wall };
+        let mut rng = StdRng::seed_from_u64(2);
+        let split = generate_grid(5, 4, 8, &[closed], &mut rng).unwrap();
+        assert!(split.terminals_connected());
+        assert_eq!(
+            generate_grid(5, 4, 9, &[closed], &mut rng),
+            Err(GridError::TooFewConnectedCells {
+                num_terminals: 9,
+                largest_region: 8,
+            })
+        );
+
+        // Random per-edge costs
+        let costs = CostModel::UniformInt { lo: 1, hi: 9 };
+        let weighted = generate_grid_with_costs(6, 6, 4, &[], costs, &mut rng).unwrap();
+        assert!(
+            weighted
+                .edges
+                .iter()
+                .all(|edge| (1.0..=9.0).contains(&edge.cost))
+        );
+    }
+
+    #[test]
+    fn gnp_instances() {
+        let generate = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            generate_random_gnp(60, 3, 0.3, CostModel::Unit, &mut rng)
+        };
+        let instance = generate(42);
+        assert_eq!(instance, generate(42));
+        assert!(instance.terminals_connected());
+        assert_eq!(instance.terminals.len(), 3);
+
+        // 1770 potential edges give 531 expected edges with a standard
+        // deviation of about 19
+        let expected = 60.0 * 59.0 / 2.0 * 0.3;
+        assert!((instance.edges.len() as f64 - expected).abs() < 80.0);
+
+        // Unlike the fixed vertex cover generator, edges appear between
+        // any two nodes
+        let mut covered = HashSet::new();
+        for edge in &instance.edges {
+            covered.insert(edge.from);
+            covered.insert(edge.to);
+        }
+        assert_eq!(covered.len(), 60);
+    }
+
+    #[test]
+    fn connectivity_repair() {
+        // Hardly any edges, so rejection never finds connected terminals
+        let generate = |connectivity: ConnectivityStrategy| {
+            let mut rng = StdRng::seed_from_u64(42);
+            generate_random_with_fixed_vc_with_rng(
+                200,
+                20,
+                10,
+                1e-6,
+                &FixedVcOptions {
+                    connectivity,
+                    max_attempts: 5,
+                    ..Default::default()
+                },
+                None,
+                &mut rng,
+            )
+        };
+
+        let Err(GenerationError::TerminalsDisconnected {
+            attempts,
+            terminal_components,
+        }) = generate(ConnectivityStrategy::Reject)
+        else {
+            panic!("rejection can't connect the terminals");
+        };
+        assert_eq!(attempts, 5);
+        assert!(terminal_components > 1);
+
+        // Without edges every terminal is a component of its own
+        assert_eq!(
+            generate_random_with_fixed_vc(30, 10, 1, 0.0, 3),
+            Err(GenerationError::TerminalsDisconnected {
+                attempts: 3,
+                terminal_components: 10,
+            })
+        );
+
+        let (instance, cover) = generate(ConnectivityStrategy::Repair).unwrap();
+        assert!(instance.terminals_connected());
+        assert_eq!(instance.num_edges, instance.edges.len());
+        // At most two edges per joined component, all touching the cover
+        assert!(instance.edges.len() <= 2 * 19);
+        assert!(is_vertex_cover(&instance, &cover));
+    }
+
+    #[test]
+    fn scale_free_instances() {
+        let generate = |n: usize, options: &ScaleFreeOptions| {
+            let mut rng = StdRng::seed_from_u64(42);
+            generate_scale_free_with_options(n, 100, 2, CostModel::Unit, options, &mut rng)
+        };
+        let uniform = ScaleFreeOptions::default();
+        let small = generate(100, &uniform);
+        let large = generate(2000, &uniform);
+        assert_eq!(large, generate(2000, &uniform));
+
+        for instance in [&small, &large] {
+            assert!(instance.validate().is_ok());
+            assert!(instance.is_connected());
+            // A triangle to start with and two edges per further node
+            assert_eq!(instance.edges.len(), 3 + 2 * (instance.num_nodes - 3));
+            assert_eq!(instance.terminals.len(), 100);
+        }
+        let max_degree = |instance: &SteinerInstance| *instance.degrees().iter().max().unwrap();
+        assert!(max_degree(&large) > max_degree(&small));
+
+        // Degree-biased terminals sit on hubs more often
+        let biased = generate(
+            2000,
+            &ScaleFreeOptions {
+                terminals: TerminalSampling::DegreeBiased,
+            },
+        );
+        let unique: HashSet<usize> = biased.terminals.iter().copied().collect();
+        assert_eq!(unique.len(), 100);
+        let mean_terminal_degree = |instance: &SteinerInstance| {
+            let degrees = instance.degrees();
+            let total: usize = instance.terminals.iter().map(|&t| degrees[t - 1]).sum();
+            total as f64 / instance.terminals.len() as f64
+        };
+        assert!(mean_terminal_degree(&biased) > mean_terminal_degree(&large));
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let weighted =
+            generate_scale_free(50, 5, 3, CostModel::UniformInt { lo: 2, hi: 4 }, &mut rng);
+        assert!(
+            weighted
+                .edges
+                .iter()
+                .all(|edge| (2.0..=4.0).contains(&edge.cost))
+        );
+    }
+
+    #[test]
+    fn directed_instances() {
+        let generate = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            generate_random_directed(50, 8, 7, 0.05, CostModel::Unit, &mut rng)
+        };
+        let instance = generate(42);
+        assert_eq!(instance, generate(42));
+        assert!(instance.edges.is_empty());
+        assert_eq!(instance.num_arcs, instance.arcs.len());
+        assert_eq!(instance.root, Some(7));
+        assert_eq!(instance.terminals.len(), 8);
+        assert!(instance.terminals.contains(&7));
+
+        // 49 planted arcs plus about 0.05 of the remaining 2401 pairs
+        let expected = 49.0 + 0.05 * (50.0 * 49.0 - 49.0);
+        assert!((instance.arcs.len() as f64 - expected).abs() < 45.0);
+
+        // Every terminal is reachable from the root along arcs
+        let mut reached = [false; 51];
+        reached[7] = true;
+        let mut queue = VecDeque::from([7]);
+        while let Some(node) = queue.pop_front() {
+            for arc in instance.arcs.iter().filter(|arc| arc.from == node) {
+                if !reached[arc.to] {
+                    reached[arc.to] = true;
+                    queue.push_back(arc.to);
+                }
+            }
+        }
+        assert!(instance.terminals.iter().all(|&t| reached[t]));
+
+        // The root and the arcs survive an export
+        let reparsed = Parser::default().parse_stp(&instance.to_string()).unwrap();
+        assert_eq!(reparsed.root, Some(7));
+        assert_eq!(reparsed.arcs.len(), instance.arcs.len());
+    }
+
+    #[test]
+    fn planted_solutions() {
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (instance, planted) = generate_planted(12, 5, 0.5, 2.0, 2.5..=6.0, &mut rng);
+            assert_eq!(planted.cost, 8.0);
+            assert!(planted.is_tree());
+            assert!(planted.covers_terminals(&instance));
+            assert!(instance.edges.len() > planted.edges.len());
+
+            let optimum = instance.solve_exact().unwrap();
+            assert_eq!(optimum.cost, planted.cost);
+            let sorted = |tree: &SteinerTree| {
+                let mut edges: Vec<Edge> = tree.edges.iter().map(Edge::normalized).collect();
+                edges.sort();
+                edges
+            };
+            assert_eq!(sorted(&optimum), sorted(&planted));
+        }
+    }
+
+    #[test]
+    fn generation_presets() {
+        let sparse = GenerationPreset::clementi_sparse(64);
+        assert_eq!((sparse.terminals, sparse.vc), (6, 6));
+        assert!((sparse.p - 2.0 * 64f64.ln() / 64.0).abs() < 1e-12);
+        assert_eq!(
+            GenerationPreset::clementi_dense(64).to_string(),
+            "n=64,p=0.5,tau=6,t=6"
+        );
+
+        let presets = [
+            GenerationPreset::clementi_dense(16),
+            GenerationPreset::custom(12, 0.6, 3, 4),
+        ];
+        let batch = generate_batch(&presets, 2, 7).unwrap();
+        let ids: Vec<(usize, usize)> = batch
+            .iter()
+            .map(|(id, _, _)| (id.preset, id.replication))
+            .collect();
+        assert_eq!(ids, [(0, 1), (0, 2), (1, 1), (1, 2)]);
+
+        let (_, instance, cover) = &batch[3];
+        assert_eq!(instance.num_nodes, 12);
+        assert_eq!(instance.terminals.len(), 3);
+        assert_eq!(cover.len(), 4);
+        assert_eq!(
+            instance.metadata.name.as_deref(),
+            Some("2_n=12,p=0.6,tau=4,t=3")
+        );
+        assert_eq!(
+            instance.metadata.remarks.last().unwrap(),
+            "preset custom replication 2 of 2 seed 7"
+        );
+        let reparsed = Parser::default().parse_stp(&instance.to_string()).unwrap();
+        assert_eq!(reparsed.metadata, instance.metadata);
+
+        let again = generate_batch(&presets, 2, 7).unwrap();
+        for ((_, a, _), (_, b, _)) in batch.iter().zip(&again) {
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn generation_progress() {
+        let mut events = Vec::new();
+        let mut record = |event: GenerationProgress| events.push(event);
+        let mut rng = StdRng::seed_from_u64(42);
+        let (instance, vc) = generate_random_with_fixed_vc_with_rng(
+            30,
+            6,
+            5,
+            0.3,
+            &FixedVcOptions::default(),
+            Some(&mut record),
+            &mut rng,
+        )
+        .unwrap();
+        let probs = UpdateProbabilities {
+            edge_insertion: 0.4,
+            edge_deletion: 0.4,
+            terminal_activation: 0.1,
+            terminal_deactivation: 0.1,
+        };
+        generate_update_sequence_with_rng(
+            &instance,
+            probs,
+            vc,
+            12,
+            &UpdateSequenceOptions {
+                queries: QueryPolicy::PerOp(0.5),
+                ..Default::default()
+            },
+            Some(&mut record),
+            &mut rng,
+        )
+        .unwrap();
+
+        let (samples, updates): (Vec<_>, Vec<_>) = events
+            .iter()
+            .partition(|event| matches!(event, GenerationProgress::Sample { .. }));
+        let attempts: Vec<usize> = samples
+            .iter()
+            .map(|event| match event {
+                GenerationProgress::Sample { attempt, .. } => *attempt,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(attempts, (1..=attempts.len()).collect::<Vec<_>>());
+        assert!(matches!(
+            samples.last(),
+            Some(GenerationProgress::Sample {
+                terminals_connected: 6,
+                terminals: 6,
+                edges,
+                ..
+            }) if *edges == instance.edges.len()
+        ));
+
+        let generated: Vec<usize> = updates
+            .iter()
+            .map(|event| match event {
+                GenerationProgress::Updates { generated, total } => {
+                    assert_eq!(*total, 12);
+                    *generated
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(generated, (1..=12).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn generated_vertex_covers() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (instance, cover) = generate_random_with_fixed_vc_with_rng(
+                25,
+                5,
+                4,
+                0.5,
+                &FixedVcOptions {
+                    connectivity: ConnectivityStrategy::Repair,
+                    ..Default::default()
+                },
+                None,
+                &mut rng,
+            )
+            .unwrap();
+            assert!(is_vertex_cover(&instance, &cover));
+        }
+
+        // Arcs need a covered endpoint as well
+        let edge = |from: usize, to: usize| Edge {
+            from,
+            to,
+            cost: 1.0,
+        };
+        let mut instance = SteinerInstance::new(4, vec![edge(1, 2), edge(2, 3)], vec![1, 3]);
+        assert!(is_vertex_cover(&instance, &[2]));
+        assert!(!is_vertex_cover(&instance, &[1]));
+        instance.arcs.push(edge(4, 3));
+        assert!(!is_vertex_cover(&instance, &[2]));
+        assert!(is_vertex_cover(&instance, &[2, 3]));
+        assert!(is_vertex_cover(&SteinerInstance::default(), &[]));
+    }
+
+    #[test]
+    fn terminal_placement() {
+        // Attempts needed over a few seeds at a sparse p
+        let attempts = |placement: TerminalPlacement| {
+            let mut total = 0;
+            for seed in 0..5 {
+                let mut attempts = 0;
+                let mut count = |event: GenerationProgress| {
+                    if let GenerationProgress::Sample { attempt, .. } = event {
+                        attempts = attempt;
+                    }
+                };
+                let mut rng = StdRng::seed_from_u64(seed);
+                let (instance, cover) = generate_random_with_fixed_vc_with_rng(
+                    100,
+                    7,
+                    7,
+                    0.1,
+                    &FixedVcOptions {
+                        placement,
+                        ..Default::default()
+                    },
+                    Some(&mut count),
+                    &mut rng,
+                )
+                .unwrap();
+                assert!(
+                    instance.metadata.remarks[0].ends_with(&format!("placement={:?}", placement))
+                );
+                match placement {
+                    TerminalPlacement::CoverBiased => {
+                        assert!(instance.terminals.iter().all(|t| cover.contains(t)))
+                    }
+                    TerminalPlacement::PostHoc => {
+                        let degrees = instance.degrees();
+                        assert!(instance.terminals.iter().all(|&t| degrees[t - 1] > 0));
+                    }
+                    TerminalPlacement::Uniform => {}
+                }
+                total += attempts;
+            }
+            total
+        };
+        let uniform = attempts(TerminalPlacement::Uniform);
+        assert!(uniform > 10 * attempts(TerminalPlacement::CoverBiased));
+        assert!(uniform > 10 * attempts(TerminalPlacement::PostHoc));
+
+        // Terminals beyond the cover go to the other nodes
+        let mut rng = StdRng::seed_from_u64(3);
+        let (instance, cover) = generate_random_with_fixed_vc_with_rng(
+            20,
+            6,
+            2,
+            0.5,
+            &FixedVcOptions {
+                connectivity: ConnectivityStrategy::Repair,
+                placement: TerminalPlacement::CoverBiased,
+                ..Default::default()
+            },
+            None,
+            &mut rng,
+        )
+        .unwrap();
+        let unique: HashSet<usize> = instance.terminals.iter().copied().collect();
+        assert_eq!(unique.len(), 6);
+        assert!(cover.iter().all(|v| unique.contains(v)));
+    }
+
+    #[test]
+    fn update_probability_weights() {
+        let probs = UpdateProbabilities {
+            edge_insertion: 2.0,
+            edge_deletion: 1.0,
+            terminal_activation: 0.5,
+            terminal_deactivation: 0.5,
+        };
+        assert_eq!(probs.validate(), Ok(()));
+        assert_eq!(
+            probs.normalized(),
+            UpdateProbabilities {
+                edge_insertion: 0.5,
+                edge_deletion: 0.25,
+                terminal_activation: 0.125,
+                terminal_deactivation: 0.125,
+            }
+        );
+        assert_eq!(
+            UpdateProbabilities::terminals_only(1.0, 3.0).normalized(),
+            UpdateProbabilities::terminals_only(0.25, 0.75)
+        );
+
+        let zero = UpdateProbabilities::edges_only(0.0, 0.0);
+        assert_eq!(zero.validate(), Err(ProbError::AllZero));
+        assert_eq!(zero.normalized(), zero);
+        let negative = UpdateProbabilities {
+            terminal_activation: -1.0,
+            ..probs
+        };
+        assert_eq!(
+            negative.validate(),
+            Err(ProbError::InvalidWeight {
+                update: "terminal_activation",
+                weight: -1.0,
+            })
+        );
+        let nan = UpdateProbabilities::edges_only(f32::NAN, 1.0);
+        assert!(matches!(
+            nan.validate(),
+            Err(ProbError::InvalidWeight {
+                update: "edge_insertion",
+                ..
+            })
+        ));
+
+        // Invalid weights are an error instead of a panic
+        let instance = SteinerInstance::new(3, Vec::new(), vec![1, 2]);
+        assert_eq!(
+            generate_update_sequence(&instance, zero, 0.5, vec![1], false, 4).unwrap_err(),
+            ProbError::AllZero
+        );
+        assert!(generate_update_sequence(&instance, negative, 0.5, vec![1], false, 4).is_err());
+    }
+
+    #[test]
+    fn update_kind_frequencies() {
+        // 45 candidate edges among nodes 1..=10, half of them present
+        let edges = (1..=10)
+            .flat_map(|from| (from + 1..=10).map(move |to| (from, to)))
+            .step_by(2)
+            .map(|(from, to)| Edge {
+                from,
+                to,
+                cost: 1.0,
+            })
+            .collect();
+        let instance = SteinerInstance::new(20, edges, (1..=20).collect());
+        let cover: Vec<usize> = (1..=10).collect();
+        let count = |probs: UpdateProbabilities, seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let updates = generate_update_sequence_with_rng(
+                &instance,
+                probs,
+                cover.clone(),
+                4000,
+                &UpdateSequenceOptions::default(),
+                None,
+                &mut rng,
+            )
+            .unwrap();
+            let mut counts = [0_usize; 4];
+            for update in &updates {
+                match update {
+                    UpdateOperation::EdgeInsertion(_) => counts[0] += 1,
+                    UpdateOperation::EdgeDeletion(_) => counts[1] += 1,
+                    UpdateOperation::TerminalActivation(_) => counts[2] += 1,
+                    UpdateOperation::TerminalDeactivation(_) => counts[3] += 1,
+                    _ => {}
+                }
+            }
+            counts
+        };
+
+        // Kinds without weight never appear
+        let no_deletions = UpdateProbabilities {
+            edge_insertion: 1.0,
+            edge_deletion: 0.0,
+            terminal_activation: 1.0,
+            terminal_deactivation: 0.0,
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        let updates = generate_update_sequence_with_rng(
+            &instance,
+            no_deletions,
+            cover.clone(),
+            30,
+            &UpdateSequenceOptions {
+                start_empty: true,
+                ..Default::default()
+            },
+            None,
+            &mut rng,
+        )
+        .unwrap();
+        assert!(updates.iter().all(|update| !matches!(
+            update,
+            UpdateOperation::EdgeDeletion(_) | UpdateOperation::TerminalDeactivation(_)
+        )));
+
+        // Frequencies follow the weights while every kind stays possible
+        let probs = UpdateProbabilities {
+            edge_insertion: 0.3,
+            edge_deletion: 0.3,
+            terminal_activation: 0.2,
+            terminal_deactivation: 0.2,
+        };
+        let counts = count(probs, 2);
+        for (count, weight) in counts.iter().zip([0.3, 0.3, 0.2, 0.2]) {
+            let frequency = *count as f64 / 4000.0;
+            assert!((frequency - weight).abs() < 0.04, "{:?}", counts);
+        }
+
+        // Running out of possible updates is an error, not a hang
+        let deletions_only = UpdateProbabilities::edges_only(0.0, 1.0);
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(
+            generate_update_sequence_with_rng(
+                &instance,
+                deletions_only,
+                cover.clone(),
+                30,
+                &UpdateSequenceOptions::default(),
+                None,
+                &mut rng,
+            )
+            .unwrap_err(),
+            ProbError::NoPossibleUpdate { generated: 23 }
+        );
+    }
+
+    #[test]
+    fn query_policies() {
+        let instance = SteinerInstance::new(8, Vec::new(), (1..=8).collect());
+        let probs = UpdateProbabilities::terminals_only(1.0, 1.0);
+        let generate = |queries: QueryPolicy, total: usize, seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            generate_update_sequence_with_rng(
+                &instance,
+                probs,
+                vec![1, 2],
+                total,
+                &UpdateSequenceOptions {
+                    queries: queries.clone(),
+                    start_empty: true,
+                    ..Default::default()
+                },
+                None,
+                &mut rng,
+            )
+            .unwrap()
+        };
+        // Positions of the queries in the sequence
+        let query_positions = |updates: &[UpdateOperation]| -> Vec<usize> {
+            updates
+                .iter()
+                .enumerate()
+                .filter(|(_, update)| matches!(update, UpdateOperation::Query(_)))
+                .map(|(position, _)| position)
+                .collect()
+        };
+
+        let every_third = generate(QueryPolicy::EveryK(3), 9, 1);
+        assert_eq!(every_third.len(), 12);
+        assert_eq!(query_positions(&every_third), [3, 7, 11]);
+
+        // The trailing query is only added when needed
+        let every_fourth = generate(QueryPolicy::EveryK(4), 9, 1);
+        assert_eq!(query_positions(&every_fourth), [4, 9, 11]);
+
+        let explicit = generate(QueryPolicy::AtIndices(vec![1, 5]), 6, 1);
+        assert_eq!(query_positions(&explicit), [1, 6, 8]);
+
+        // A query follows every operation, and no failed attempt adds one
+        let always = generate(QueryPolicy::PerOp(1.0), 10, 1);
+        assert_eq!(
+            query_positions(&always),
+            (0..10).map(|i| 2 * i + 1).collect::<Vec<_>>()
+        );
+
+        // Queries show the instance after the preceding operation
+        let UpdateOperation::Query(query) = &always[1] else {
+            unreachable!();
+        };
+        assert_eq!(query.terminals.len(), 1);
+
+        // Per-operation queries come from the seeded generator
+        let sometimes = |seed| updates_to_dus(&generate(QueryPolicy::PerOp(0.4), 30, seed));
+        assert_eq!(sometimes(5), sometimes(5));
+    }
+
+    #[test]
+    fn bounded_treewidth_solver() {
+        use rand::Rng;
+        let mut rng = StdRng::seed_from_u64(726);
+
+        // Series-parallel graphs, grown by subdividing an edge or adding a
+        // path of length two parallel to it
+        for _ in 0..20 {
+            let mut edges = vec![(1, 2)];
+            let mut n = 2;
+            while n < 12 {
+                let (u, v) = edges[rng.random_range(0..edges.len())];
+                n += 1;
+                if rng.random_bool(0.5) {
+                    edges.retain(|&edge| edge != (u, v));
+                }
+                edges.extend([(u, n), (n, v)]);
+            }
+            let mut builder = SteinerInstance::builder();
+            builder.nodes(n);
+            for (u, v) in edges {
+                builder.edge(u, v, rng.random_range(1..=9) as f64);
+            }
+            for terminal in rand::seq::index::sample(&mut rng, n, 5) {
+                builder.terminal(terminal + 1);
+            }
+            let instance = builder.build().unwrap();
+
+            let tree = instance.exact_bounded_treewidth(2).unwrap();
+            assert_eq!(tree.cost, instance.solve_exact().unwrap().cost);
+            assert!(tree.is_tree());
+            assert!(tree.covers_terminals(&instance));
+        }
+
+        for _ in 0..5 {
+            let grid = generate_grid_with_costs(
+                4,
+                3,
+                6,
+                &[],
+                CostModel::UniformInt { lo: 1, hi: 9 },
+                &mut rng,
+            )
+            .unwrap();
+            let tree = grid.exact_bounded_treewidth(4).unwrap();
+            assert_eq!(tree.cost, grid.solve_exact().unwrap().cost);
+            assert!(tree.is_tree());
+            assert!(tree.covers_terminals(&grid));
+        }
+
+        // K5 has treewidth 4
+        let mut builder = SteinerInstance::builder();
+        builder.nodes(5).terminal(1).terminal(5);
+        for u in 1..=5 {
+            for v in u + 1..=5 {
+                builder.edge(u, v, if v == u + 1 { 1.0 } else { 5.0 });
+            }
+        }
+        let complete = builder.build().unwrap();
+        assert_eq!(complete.exact_bounded_treewidth(3), None);
+        assert_eq!(complete.exact_bounded_treewidth(4).unwrap().cost, 4.0);
+
+        let disconnected = SteinerInstance::new(3, complete.edges[..1].to_vec(), vec![1, 3]);
+        assert_eq!(disconnected.exact_bounded_treewidth(2), None);
+    }
+
+    /// Helper building the path 1 - 2 - ... - n of unit costs, with the
+    /// edges pointing backwards
+    fn path_instance(n: usize) -> SteinerInstance {
+        SteinerInstance::new(
+            n,
+            (1..n)
+                .map(|i| Edge {
+                    from: i + 1,
+                    to: i,
+                    cost: 1.0,
+                })
+                .collect(),
+            vec![1],
+        )
     }
 
     /// Helper for fuzzy float comparison in edges